@@ -15,11 +15,44 @@ TOPIC_HEARTBEAT = bytes(f"{TOPIC_BASE}/heartbeat", "utf-8")
 TOPIC_TEMP_OUT = bytes(f"{TOPIC_BASE}/{SENSOR_ID}/read", "utf-8")
 TOPIC_TEMP_POLL = bytes(f"{TOPIC_BASE}/{SENSOR_ID}/poll", "utf-8")
 DISCONNECT_TOPIC = bytes(f"{TOPIC_BASE}/{SENSOR_ID}/disconnect", "utf-8")
+CONFIG_TOPIC = bytes(f"{TOPIC_BASE}/{SENSOR_ID}/config", "utf-8")
 # 28:CD:C1:03:E2:9F
 MAC_ADDR = [40, 205, 193, 3, 226, 159]
 
 first_loop = False
 
+# Settings which can be changed remotely by publishing to CONFIG_TOPIC. Changes are saved to
+# CONFIG_FILE along with their version, so they survive a reboot.
+CONFIG_FILE = "config.json"
+config = {
+    "alias": "temp_01",
+    "poll_interval": 60000,
+    "location": "rp-pico-w",
+    "calibration": None,
+}
+config_version = 0
+
+
+def load_config():
+    global config
+    global config_version
+    try:
+        with open(CONFIG_FILE) as f:
+            saved = json.load(f)
+        config.update(saved["config"])
+        config_version = saved["version"]
+    except (OSError, ValueError, KeyError) as e:
+        # Nothing has been saved yet, so keep the defaults
+        print(f"Using default config: {e}")
+
+
+def save_config():
+    with open(CONFIG_FILE, "w") as f:
+        json.dump({"config": config, "version": config_version}, f)
+
+
+load_config()
+
 while True:
     try:
         ip_addr = connect_wifi(SSID, PSWD)
@@ -29,25 +62,33 @@ while True:
 
         def heartbeat_cb(timer):
             global client
+            global config
+            global config_version
             current_time = time()
             print(f"HEARTBEAT {current_time}")
             heartbeat_info = {
                 "id": SENSOR_ID, 
-                "alias": "temp_01",
-                "poll_interval": 60000,
+                "alias": config["alias"],
+                "poll_interval": config["poll_interval"],
                 "poll_topic": TOPIC_TEMP_POLL,
                 "read_topic": TOPIC_TEMP_OUT,
                 "disconnect_topic": DISCONNECT_TOPIC,
+                "config_topic": CONFIG_TOPIC,
+                "config_version": config_version,
                 "ip_addr": ip_addr,
                 "mac_addr": MAC_ADDR,
-                "location": "rp-pico-w"
+                "location": config["location"]
             }
+            if config["calibration"] is not None:
+                heartbeat_info["calibration"] = config["calibration"]
             client.publish(TOPIC_HEARTBEAT, json.dumps(heartbeat_info))
 
 
         def sub_cb(topic, msg):
             global led
             global client
+            global config
+            global config_version
             led.high()
 
             print(f"RECEIVED\ntopic: {topic}\nmsg: {msg}\n")
@@ -61,12 +102,30 @@ while True:
                 #     "timestamp": str(time())
                 # }
                 client.publish(TOPIC_TEMP_OUT, f"\"{temp.get_temp():.2f}\"")
+
+            if topic == CONFIG_TOPIC:
+                print("CONFIG")
+                update = json.loads(msg)
+                version = update.pop("version")
+                unsupported = [key for key in update if key not in config]
+                if unsupported:
+                    # Nothing is applied and the version isn't reported, so the push isn't
+                    # treated as applied
+                    print(f"Rejecting config version {version}, unsupported settings: {unsupported}")
+                else:
+                    config.update(update)
+                    config_version = version
+                    save_config()
+                    # Report the new config straight away rather than waiting for the next
+                    # heartbeat
+                    heartbeat_cb(None)
             
             led.low()
 
 
         client.set_callback(sub_cb)
         client.subscribe(TOPIC_TEMP_POLL)
+        client.subscribe(CONFIG_TOPIC)
 
         if not first_loop:
             tim = Timer()
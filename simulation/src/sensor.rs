@@ -22,6 +22,8 @@ pub struct SensorSimulator {
     pub ip_addr: IpAddr,
     pub mac_addr: MacAddr6,
     pub location: String,
+    pub unit: String,
+    pub range: ValueRange,
 
     #[serde(skip)]
     pub sim_range: Range<f64>,
@@ -31,13 +33,22 @@ pub struct SensorSimulator {
     pub nats_client: async_nats::Client,
 }
 
+/// Values the sensor declares it can read, the same as the range it's simulated over
+#[derive(Serialize, Clone)]
+pub struct ValueRange {
+    pub min: f64,
+    pub max: f64,
+}
+
 impl SensorSimulator {
     const DEFAULT_POLL_INTERVAL: u64 = 10_000;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         alias: String,
         location: String,
         sim_range: Range<f64>,
+        unit: String,
         mean: f64,
         std_dev: f64,
         poll_interval: Option<u64>,
@@ -58,6 +69,11 @@ impl SensorSimulator {
             location: format!("SIMULATION-{}", location),
             mac_addr: MacAddr6::from(mac_addr),
             ip_addr: IpAddr::V4(Ipv4Addr::from(rng.gen::<u32>())),
+            unit,
+            range: ValueRange {
+                min: sim_range.start,
+                max: sim_range.end,
+            },
             sim_range,
             normal: Normal::new(mean, std_dev).unwrap(),
             nats_client,
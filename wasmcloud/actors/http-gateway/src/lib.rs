@@ -1,10 +1,14 @@
-use actor_interfaces::{PangeaApi, PangeaApiSender, SearchParams, SearchResponse, ui::{Ui, UiSender, GetAssetResponse}};
+use actor_interfaces::{LogEvent, PangeaApi, PangeaApiSender, SearchParams, SearchResponse, ui::{Ui, UiSender, GetAssetResponse}};
 use wasmbus_rpc::actor::prelude::*;
 use wasmcloud_interface_httpserver::{HeaderMap, HttpRequest, HttpResponse, HttpServer, HttpServerReceiver};
+use wasmcloud_interface_keyvalue::{KeyValue, KeyValueSender};
 use wasmcloud_interface_logging::{debug, error, info};
+use wasmcloud_interface_polling::control::{CommandRequest, CommandResponse, ConfigRequest, Control, ControlSender};
 
 const PANGEA_API_ACTOR: &str = "iiot/pangea_api";
 const UI_ACTOR: &str = "iiot/ui";
+/// Key of the bearer token which has to be given to send commands or push configuration
+const CONTROL_API_KEY: &str = "CONTROL_API_KEY";
 
 #[derive(Debug, Default, Actor, HealthResponder)]
 #[services(Actor, HttpServer)]
@@ -19,6 +23,8 @@ impl HttpServer for HttpGatewayActor {
         match (method.as_str(), path) {
             ("POST", "api/logs") => get_logs(ctx, req).await,
             ("POST", "api/results") => page_results(ctx, req).await,
+            ("POST", "api/command") => send_command(ctx, req).await,
+            ("POST", "api/config") => push_config(ctx, req).await,
             ("GET", _) => get_ui(ctx, req).await,
             _ => {
                 error!("Received POST request for unknown path: {}", path);
@@ -87,3 +93,125 @@ async fn page_results(ctx: &Context, req: &HttpRequest) -> RpcResult<HttpRespons
     debug!("Request: {:?}", req);
     todo!()
 }
+
+/// Commands and configuration pushes actuate physical equipment, so they're only accepted with
+/// the control API key from the key value store as a bearer token. Anyone holding the key is
+/// trusted to control every device the gateway can reach, and the control endpoints are
+/// disabled if no key is set.
+async fn authorized(ctx: &Context, req: &HttpRequest) -> RpcResult<bool> {
+    let kv = KeyValueSender::new();
+    let key = kv.get(ctx, CONTROL_API_KEY).await?;
+    if !key.exists || key.value.is_empty() {
+        error!("Rejecting control request, no {} is set", CONTROL_API_KEY);
+        return Ok(false);
+    }
+    let token = req
+        .header
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, values)| values.first())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    Ok(token.map_or(false, |token| constant_time_eq(token.as_bytes(), key.value.as_bytes())))
+}
+
+/// Compare every byte, so the time taken doesn't give away how much of the key was guessed
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn unauthorized() -> HttpResponse {
+    let mut header = HeaderMap::new();
+    header.insert("WWW-Authenticate".to_string(), vec!["Bearer".to_string()]);
+    HttpResponse {
+        status_code: 401,
+        header,
+        body: b"Unauthorized".to_vec(),
+    }
+}
+
+async fn send_command(ctx: &Context, req: &HttpRequest) -> RpcResult<HttpResponse> {
+    info!("Received command request");
+    // The headers hold the control API key, so only the body is logged
+    debug!("Request body: {}", String::from_utf8_lossy(&req.body));
+    if !authorized(ctx, req).await? {
+        return Ok(unauthorized());
+    }
+    match serde_json::from_slice::<CommandRequest>(req.body.as_slice()) {
+        Ok(command) => {
+            let control: ControlSender<_> = ControlSender::new();
+            let resp: CommandResponse = control.send_command(ctx, &command).await?;
+            // Every command is audited, whether or not the device acknowledged it
+            let pangea_api: PangeaApiSender<_> = PangeaApiSender::to_actor(PANGEA_API_ACTOR);
+            let write_result = pangea_api.write_audit_log(ctx, &vec![command_event(&resp)]).await?;
+            if !write_result.success {
+                error!("Error writing command to audit log: {:?}", write_result.reason);
+            }
+            if resp.acknowledged && resp.error.is_none() {
+                info!("Command {} acknowledged by {}", resp.command.command, resp.command.target_id);
+            } else {
+                error!("Command {} failed for {}: {:?}", resp.command.command, resp.command.target_id, resp.error);
+            }
+            Ok(HttpResponse::json(&resp, 200)?)
+        }
+        Err(e) => {
+            error!("Failed to deserialize command request: {e:?}");
+            Ok(HttpResponse::bad_request(
+                "Failed to deserialize command request",
+            ))
+        }
+    }
+}
+
+/// The provider audits the change itself once the device reports its new configuration, so
+/// the response only needs to be passed back to the caller
+async fn push_config(ctx: &Context, req: &HttpRequest) -> RpcResult<HttpResponse> {
+    info!("Received config request");
+    // The headers hold the control API key, so only the body is logged
+    debug!("Request body: {}", String::from_utf8_lossy(&req.body));
+    if !authorized(ctx, req).await? {
+        return Ok(unauthorized());
+    }
+    match serde_json::from_slice::<ConfigRequest>(req.body.as_slice()) {
+        Ok(config) => {
+            let control: ControlSender<_> = ControlSender::new();
+            let resp = control.push_config(ctx, &config).await?;
+            if resp.applied {
+                info!("Config version {} applied by {}", resp.version, resp.target_id);
+            } else {
+                error!("Config version {} not applied by {}: {:?}", resp.version, resp.target_id, resp.error);
+            }
+            Ok(HttpResponse::json(&resp, 200)?)
+        }
+        Err(e) => {
+            error!("Failed to deserialize config request: {e:?}");
+            Ok(HttpResponse::bad_request(
+                "Failed to deserialize config request",
+            ))
+        }
+    }
+}
+
+/// Record the outcome of a command as a change from its `old` value to its `new` value. Both
+/// values are only ever what the device reported, so a command which wasn't applied doesn't look
+/// like it was.
+fn command_event(resp: &CommandResponse) -> LogEvent {
+    let status = match (&resp.error, resp.acknowledged) {
+        (Some(error), _) => error.error_type.to_owned(),
+        (None, true) => "ACKNOWLEDGED".to_string(),
+        (None, false) => "UNACKNOWLEDGED".to_string(),
+    };
+    LogEvent {
+        message: match &resp.command.value {
+            Some(value) => format!("{}: {} {}", resp.command.target_id, resp.command.command, value),
+            None => format!("{}: {}", resp.command.target_id, resp.command.command),
+        },
+        action: Some("DEVICE_COMMAND".to_string()),
+        timestamp: resp.timestamp.map(|timestamp| (timestamp / 1000).to_string()),
+        source: resp.source.to_owned(),
+        target: Some(resp.command.target_id.to_owned()),
+        status: Some(status),
+        old: resp.old.to_owned(),
+        new: resp.new.to_owned(),
+        ..Default::default()
+    }
+}
@@ -9,9 +9,18 @@ const API_KEY: &str = "PANGEA_API_KEY";
 const AUDIT_LOG_ENDPOINT: &str = "https://audit.aws.eu.pangea.cloud/v1/log";
 const AUDIT_SEARCH_ENDPOINT: &str = "https://audit.aws.eu.pangea.cloud/v1/search";
 
-pub fn build_log_request(api_token: &String, mut event: LogEvent) -> RpcResult<HttpRequest> {
+/// `traceparent` should be the W3C trace context of the reading being logged, if there is one, so
+/// the trace continues through the request to Pangea
+pub fn build_log_request(
+    api_token: &String,
+    mut event: LogEvent,
+    traceparent: Option<&String>,
+) -> RpcResult<HttpRequest> {
     event.convert_timestamps();
-    let headers = headers(api_token);
+    let mut headers = headers(api_token);
+    if let Some(traceparent) = traceparent {
+        headers.insert("traceparent".to_string(), vec![traceparent.to_owned()]);
+    }
     let body = serde_json::json!({ "event": event });
     let body = serde_json::to_vec(&body).map_err(|e| RpcError::Ser(e.to_string()))?;
     Ok(HttpRequest {
@@ -25,6 +34,7 @@ pub fn build_log_request(api_token: &String, mut event: LogEvent) -> RpcResult<H
 //TODO: use a proper search query type
 pub fn build_search_request(api_token: &String, mut query: SearchParams) -> RpcResult<HttpRequest> {
     query.convert_timestamps();
+    restrict_locations(&mut query);
     let headers = headers(api_token);
     let body = serde_json::to_vec(&query).map_err(|e| RpcError::Ser(e.to_string()))?;
     Ok(HttpRequest {
@@ -35,6 +45,35 @@ pub fn build_search_request(api_token: &String, mut query: SearchParams) -> RpcR
     })
 }
 
+/// Pangea only restricts searches to whole field values, so location restrictions are turned
+/// into query terms for the `source` of each event instead, which starts with the sensor's
+/// location. Each level is followed by either the next level or the sensor's alias, so
+/// `acme/plant_1` doesn't also match `acme/plant_10`.
+fn restrict_locations(query: &mut SearchParams) {
+    let locations = match query
+        .search_restriction
+        .as_mut()
+        .and_then(|restrictions| restrictions.location.take())
+    {
+        Some(locations) if !locations.is_empty() => locations,
+        _ => return,
+    };
+    let terms = locations
+        .iter()
+        .flat_map(|location| {
+            [
+                format!("source:\"{location}/\""),
+                format!("source:\"{location}.\""),
+            ]
+        })
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    query.query = match query.query.take() {
+        Some(existing) if !existing.is_empty() => Some(format!("{existing} ({terms})")),
+        _ => Some(format!("({terms})")),
+    };
+}
+
 fn headers(api_token: &String) -> HeaderMap {
     HeaderMap::from([
         (
@@ -110,3 +149,64 @@ impl ConvertTimestamps for LogEvent {
         self.timestamp = timestamp_to_datetime(self.timestamp.clone());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actor_interfaces::SearchRestrictionParams;
+
+    fn search(query: Option<&str>, location: Option<Vec<&str>>) -> SearchParams {
+        SearchParams {
+            query: query.map(str::to_string),
+            search_restriction: Some(SearchRestrictionParams {
+                location: location.map(|l| l.into_iter().map(str::to_string).collect()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_restrict_locations() {
+        let mut query = search(None, Some(vec!["acme/plant_1"]));
+        restrict_locations(&mut query);
+        assert_eq!(
+            query.query.as_deref(),
+            Some(r#"(source:"acme/plant_1/" OR source:"acme/plant_1.")"#)
+        );
+        // Pangea doesn't know about locations, so the restriction is removed
+        assert_eq!(query.search_restriction.unwrap().location, None);
+
+        let mut query = search(Some("action:SENSOR_READING"), Some(vec!["acme", "globex"]));
+        restrict_locations(&mut query);
+        assert_eq!(
+            query.query.as_deref(),
+            Some(concat!(
+                r#"action:SENSOR_READING (source:"acme/" OR source:"acme." OR "#,
+                r#"source:"globex/" OR source:"globex.")"#
+            ))
+        );
+
+        let mut query = search(Some(""), Some(vec!["acme"]));
+        restrict_locations(&mut query);
+        assert_eq!(
+            query.query.as_deref(),
+            Some(r#"(source:"acme/" OR source:"acme.")"#)
+        );
+    }
+
+    #[test]
+    fn test_unrestricted_locations() {
+        let mut query = search(Some("action:SENSOR_READING"), None);
+        restrict_locations(&mut query);
+        assert_eq!(query, search(Some("action:SENSOR_READING"), None));
+
+        let mut query = search(Some("action:SENSOR_READING"), Some(vec![]));
+        restrict_locations(&mut query);
+        assert_eq!(query.query.as_deref(), Some("action:SENSOR_READING"));
+
+        let mut query = SearchParams::default();
+        restrict_locations(&mut query);
+        assert_eq!(query, SearchParams::default());
+    }
+}
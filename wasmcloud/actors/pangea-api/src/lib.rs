@@ -37,7 +37,7 @@ impl PangeaApi for PangeaApiActor {
 
         for event in events {
             debug!("Event: {:?}", event);
-            let req = build_log_request(&api_token, event.to_owned());
+            let req = build_log_request(&api_token, event.to_owned(), ctx.span.as_ref());
             match req {
                 Ok(req) => match client.request(ctx, &req).await {
                     Ok(resp) => {
@@ -1,7 +1,13 @@
 use actor_interfaces::{LogEvent, PangeaApi, PangeaApiSender};
 use wasmbus_rpc::actor::prelude::*;
 use wasmcloud_interface_logging::{debug, error, info};
-use wasmcloud_interface_polling::{PollResult, PollSubscriber, PollSubscriberReceiver};
+use wasmcloud_interface_polling::reading::{
+    quality_class, QUALITY_BAD, STATUS_CONFIG_CHANGE, STATUS_OFFLINE, STATUS_QUARANTINED,
+    STATUS_RESTORED,
+};
+use wasmcloud_interface_polling::{
+    PollResult, PollSubscriber, PollSubscriberReceiver, SensorReading, TargetDescriptor,
+};
 
 const PANGEA_API_ACTOR: &str = "iiot/pangea_api";
 
@@ -20,39 +26,303 @@ impl PollSubscriber for SensorReaderActor {
                 poll_error.description.unwrap_or("n/a".to_string())
             );
         } else {
-            if let Some(poll_readings) = poll_result.data.clone() {
-                match serde_json::from_slice::<Vec<LogEvent>>(&poll_readings) {
-                    Ok(poll_readings) => {
-                        debug!("{} readings to be sent to event log", poll_readings.len());
-                        let pangea_api = PangeaApiSender::to_actor(PANGEA_API_ACTOR);
-                        info!("Sending {} readings to event log", poll_readings.len());
-                        let write_result = pangea_api.write_audit_log(ctx, &poll_readings).await;
-                        match write_result {
-                            Ok(write_result) => {
-                                if write_result.success {
-                                    info!("Successfully wrote readings to audit log");
-                                } else {
-                                    error!(
-                                        "Error writing to audit log: {}",
-                                        write_result.reason.unwrap_or("".to_string())
-                                    )
-                                }
-                            }
-                            Err(e) => {
-                                // let poll_readings = serde_json::f;
-                                error!("RPC call to pangea-api actor failed: {e:?}");
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        // let poll_readings = serde_json::f;
-                        error!("Failed to deserialize sensor readings: {e:?}\n{poll_readings:?}");
+            match poll_result.decode_data::<Vec<SensorReading>>() {
+                Ok(Some(poll_readings)) => {
+                    debug!("{} readings to be sent to event log", poll_readings.len());
+                    // Readings which carry their own trace context are written with it, so each
+                    // reading's trace continues through to the audit log
+                    for (traceparent, readings) in by_trace(&poll_readings) {
+                        let ctx = Context {
+                            span: traceparent.cloned().or_else(|| ctx.span.clone()),
+                            ..ctx.clone()
+                        };
+                        let events = readings.into_iter().map(log_event).collect();
+                        write_audit_log(&ctx, events).await;
                     }
                 }
-            } else {
-                error!("No sensor readings contained in poll result");
+                Ok(None) => {
+                    error!("No sensor readings contained in poll result");
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to deserialize sensor readings ({}): {e:?}\n{:?}",
+                        poll_result
+                            .content_type
+                            .as_deref()
+                            .unwrap_or("application/json"),
+                        poll_result.data
+                    );
+                }
             }
         }
         Ok(())
     }
+
+    async fn target_discovered(&self, ctx: &Context, target: &TargetDescriptor) -> RpcResult<()> {
+        info!("sensor_reader: target {} discovered", target.target_id);
+        write_audit_log(ctx, vec![target_event(target, "TARGET_DISCOVERED")]).await;
+        Ok(())
+    }
+
+    async fn target_changed(&self, ctx: &Context, target: &TargetDescriptor) -> RpcResult<()> {
+        info!("sensor_reader: target {} changed", target.target_id);
+        write_audit_log(ctx, vec![target_event(target, "TARGET_CHANGED")]).await;
+        Ok(())
+    }
+
+    async fn target_lost(&self, ctx: &Context, target: &TargetDescriptor) -> RpcResult<()> {
+        info!("sensor_reader: target {} lost", target.target_id);
+        write_audit_log(ctx, vec![target_event(target, "TARGET_LOST")]).await;
+        Ok(())
+    }
+}
+
+/// Group readings by their trace context, keeping the order they were read in
+fn by_trace(readings: &[SensorReading]) -> Vec<(Option<&String>, Vec<&SensorReading>)> {
+    let mut groups: Vec<(Option<&String>, Vec<&SensorReading>)> = Vec::new();
+    for reading in readings {
+        let traceparent = reading.traceparent.as_ref();
+        match groups.iter_mut().find(|(t, _)| *t == traceparent) {
+            Some((_, group)) => group.push(reading),
+            None => groups.push((traceparent, vec![reading])),
+        }
+    }
+    groups
+}
+
+async fn write_audit_log(ctx: &Context, events: Vec<LogEvent>) {
+    let pangea_api = PangeaApiSender::to_actor(PANGEA_API_ACTOR);
+    info!("Sending {} events to event log", events.len());
+    match pangea_api.write_audit_log(ctx, &events).await {
+        Ok(write_result) => {
+            if write_result.success {
+                info!("Successfully wrote events to audit log");
+            } else {
+                error!(
+                    "Error writing to audit log: {}",
+                    write_result.reason.unwrap_or("".to_string())
+                )
+            }
+        }
+        Err(e) => {
+            error!("RPC call to pangea-api actor failed: {e:?}");
+        }
+    }
+}
+
+/// Record a target being discovered, changed or lost, with its full descriptor as the new value
+/// (or the old one, once it's lost)
+fn target_event(target: &TargetDescriptor, action: &str) -> LogEvent {
+    let source = format!("{}.{}", target.location, target.alias);
+    let (old, new) = match action {
+        "TARGET_LOST" => (Some(target.details.to_owned()), None),
+        _ => (None, Some(target.details.to_owned())),
+    };
+    LogEvent {
+        timestamp: Some((target.timestamp / 1000).to_string()),
+        message: format!(
+            "{source}: {}",
+            action.trim_start_matches("TARGET_").to_lowercase()
+        ),
+        source: Some(source),
+        action: Some(action.to_string()),
+        status: Some("SUCCESS".to_string()),
+        target: Some(target.target_id.to_owned()),
+        old,
+        new,
+        ..Default::default()
+    }
+}
+
+/// Record a reading as an audit log event. Readings are timestamped in milliseconds, the audit log
+/// wants seconds.
+fn log_event(reading: &SensorReading) -> LogEvent {
+    let source = &reading.source;
+    let mut event = LogEvent {
+        timestamp: Some((reading.timestamp / 1000).to_string()),
+        source: Some(source.to_owned()),
+        status: Some("SUCCESS".to_string()),
+        target: Some(reading.sensor_id.to_owned()),
+        old: reading.previous_value.to_owned(),
+        new: reading.value.to_owned(),
+        gps: reading.gps.to_owned(),
+        ..Default::default()
+    };
+    match reading.status.as_str() {
+        STATUS_CONFIG_CHANGE => {
+            event.action = Some("CONFIG_CHANGE".to_string());
+            event.message = format!("{source}: config changed");
+        }
+        STATUS_OFFLINE => {
+            event.action = Some("SENSOR_OFFLINE".to_string());
+            event.message = format!("{source}: offline");
+        }
+        STATUS_QUARANTINED => {
+            event.action = Some("SENSOR_QUARANTINED".to_string());
+            event.message = match &reading.value {
+                Some(detail) => format!("{source}: quarantined ({detail})"),
+                None => format!("{source}: quarantined"),
+            };
+        }
+        STATUS_RESTORED => {
+            event.action = Some("SENSOR_RESTORED".to_string());
+            event.message = format!("{source}: restored");
+        }
+        // Readings are logged with their quality as well as their status, so questionable data
+        // can be filtered out when searching the audit log
+        status => {
+            event.action = Some("SENSOR_READING".to_string());
+            event.status = Some(status.to_string());
+            event.quality = Some(reading.quality.to_owned());
+            // Readings from multi-channel sensors are targeted at their channel, or every
+            // channel for composite readings, e.g. `pm1_0,pm2_5`
+            event.target = reading.channel.to_owned();
+            if quality_class(&reading.quality) == QUALITY_BAD {
+                // Failed reads are logged with why they failed rather than a value, which for
+                // values outside the sensor's range is the value itself
+                event.message = match (&reading.value, &reading.unit) {
+                    (Some(detail), Some(unit)) if status == "OUT_OF_RANGE" => {
+                        format!("{source}: {status} ({detail} {unit})")
+                    }
+                    (Some(detail), _) => format!("{source}: {status} ({detail})"),
+                    (None, _) => format!("{source}: {status}"),
+                };
+            } else {
+                let value = reading.value.as_deref().unwrap_or("N/A");
+                let mut reading_text = match &reading.unit {
+                    Some(unit) => format!("{value} {unit}"),
+                    None => value.to_string(),
+                };
+                // Calibrated values are logged with how they were calibrated, so the conversion
+                // can be reproduced from the audit log
+                if let (Some(raw), Some(calibration)) = (&reading.raw_value, &reading.calibration) {
+                    reading_text = format!("{reading_text} [raw {raw}, calibration {calibration}]");
+                    event.old = Some(raw.to_owned());
+                }
+                event.message = match status {
+                    "SUCCESS" => format!("{source}: {reading_text}"),
+                    status => format!("{source}: {reading_text} ({status})"),
+                };
+                event.new = Some(value.to_string());
+            }
+        }
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(status: &str, value: Option<&str>) -> SensorReading {
+        SensorReading {
+            unit: Some("C".to_string()),
+            ..SensorReading::new(
+                "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "sim.temp_01".to_string(),
+                status,
+                value.map(str::to_string),
+                1_684_000_000_123,
+            )
+        }
+    }
+
+    #[test]
+    fn test_reading_event() {
+        let event = log_event(&reading("SUCCESS", Some("21.5")));
+        assert_eq!(event.action.as_deref(), Some("SENSOR_READING"));
+        assert_eq!(event.message, "sim.temp_01: 21.5 C");
+        assert_eq!(event.status.as_deref(), Some("SUCCESS"));
+        assert_eq!(event.quality.as_deref(), Some("GOOD"));
+        assert_eq!(event.timestamp.as_deref(), Some("1684000000"));
+        assert_eq!(event.source.as_deref(), Some("sim.temp_01"));
+        assert_eq!(event.new.as_deref(), Some("21.5"));
+        assert_eq!(event.target, None);
+        assert_eq!(event.gps, None);
+
+        let mut stale = reading("STALE_DATA", Some("21.5"));
+        stale.channel = Some("pm1_0,pm2_5".to_string());
+        stale.gps = Some("51.5072,-0.1276".to_string());
+        let event = log_event(&stale);
+        assert_eq!(event.message, "sim.temp_01: 21.5 C (STALE_DATA)");
+        assert_eq!(event.status.as_deref(), Some("STALE_DATA"));
+        assert_eq!(event.quality.as_deref(), Some("UNCERTAIN_LAST_KNOWN_VALUE"));
+        assert_eq!(event.target.as_deref(), Some("pm1_0,pm2_5"));
+        assert_eq!(event.gps.as_deref(), Some("51.5072,-0.1276"));
+    }
+
+    #[test]
+    fn test_calibrated_reading_event() {
+        let mut calibrated = reading("SUCCESS", Some("21.5"));
+        calibrated.raw_value = Some("2150".to_string());
+        calibrated.calibration = Some("x0.01".to_string());
+        let event = log_event(&calibrated);
+        assert_eq!(
+            event.message,
+            "sim.temp_01: 21.5 C [raw 2150, calibration x0.01]"
+        );
+        assert_eq!(event.old.as_deref(), Some("2150"));
+        assert_eq!(event.new.as_deref(), Some("21.5"));
+    }
+
+    #[test]
+    fn test_failed_reading_event() {
+        let event = log_event(&reading("OUT_OF_RANGE", Some("900")));
+        assert_eq!(event.message, "sim.temp_01: OUT_OF_RANGE (900 C)");
+        assert_eq!(event.status.as_deref(), Some("OUT_OF_RANGE"));
+        assert_eq!(event.quality.as_deref(), Some("BAD_SENSOR_FAILURE"));
+
+        let event = log_event(&reading("MALFORMED_PAYLOAD", Some("expected a number")));
+        assert_eq!(
+            event.message,
+            "sim.temp_01: MALFORMED_PAYLOAD (expected a number)"
+        );
+
+        let event = log_event(&reading("TIMEOUT", None));
+        assert_eq!(event.message, "sim.temp_01: TIMEOUT");
+        assert_eq!(event.status.as_deref(), Some("TIMEOUT"));
+        assert_eq!(event.quality.as_deref(), Some("BAD_COMM_FAILURE"));
+        assert_eq!(event.new, None);
+    }
+
+    #[test]
+    fn test_status_events() {
+        let mut config_change = reading(STATUS_CONFIG_CHANGE, Some(r#"{"poll_interval":500}"#));
+        config_change.previous_value = Some(r#"{"poll_interval":1000}"#.to_string());
+        let event = log_event(&config_change);
+        assert_eq!(event.action.as_deref(), Some("CONFIG_CHANGE"));
+        assert_eq!(event.message, "sim.temp_01: config changed");
+        assert_eq!(event.old, config_change.previous_value);
+        assert_eq!(event.new, config_change.value);
+
+        let event = log_event(&reading(STATUS_OFFLINE, None));
+        assert_eq!(event.action.as_deref(), Some("SENSOR_OFFLINE"));
+        assert_eq!(event.message, "sim.temp_01: offline");
+
+        let event = log_event(&reading(STATUS_QUARANTINED, Some("5 failed reads")));
+        assert_eq!(event.action.as_deref(), Some("SENSOR_QUARANTINED"));
+        assert_eq!(event.message, "sim.temp_01: quarantined (5 failed reads)");
+        let event = log_event(&reading(STATUS_QUARANTINED, None));
+        assert_eq!(event.message, "sim.temp_01: quarantined");
+
+        let event = log_event(&reading(STATUS_RESTORED, Some("21.5")));
+        assert_eq!(event.action.as_deref(), Some("SENSOR_RESTORED"));
+        assert_eq!(event.message, "sim.temp_01: restored");
+
+        // Status events are targeted at the sensor, and aren't logged with their quality
+        for status in [
+            STATUS_CONFIG_CHANGE,
+            STATUS_OFFLINE,
+            STATUS_QUARANTINED,
+            STATUS_RESTORED,
+        ] {
+            let event = log_event(&reading(status, None));
+            assert_eq!(event.status.as_deref(), Some("SUCCESS"));
+            assert_eq!(event.quality, None);
+            assert_eq!(
+                event.target.as_deref(),
+                Some("a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3")
+            );
+        }
+    }
 }
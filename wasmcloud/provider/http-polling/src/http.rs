@@ -0,0 +1,208 @@
+//! Requesting a target's endpoint and extracting its value from the JSON response
+
+use reqwest::{Client, RequestBuilder};
+use serde_json::Value;
+use std::time::Duration;
+use tracing::warn;
+use wasmcloud_interface_polling::reading::{now, ReadingTarget};
+use wasmcloud_interface_polling::SensorReading;
+
+use crate::target::{Auth, HttpTarget};
+
+/// Request the target's endpoint and read the value at its `value_path`. Requests which fail or
+/// get an error status give a `COMM_ERROR` reading, and responses without a value at the path
+/// give a `VALUE_ERROR` one.
+pub async fn fetch(client: &Client, target: &HttpTarget, timeout: Duration) -> SensorReading {
+    let timestamp = now();
+    let value = match request(client, target, timeout).send().await {
+        Ok(response) if response.status().is_success() => response.json::<Value>().await,
+        Ok(response) => {
+            warn!(
+                "{} responded to {} with {}",
+                target.url,
+                target.alias,
+                response.status()
+            );
+            return target.reading("COMM_ERROR", None, timestamp);
+        }
+        Err(e) => {
+            warn!("Error requesting {} for {}: {e}", target.url, target.alias);
+            return target.reading("COMM_ERROR", None, timestamp);
+        }
+    };
+
+    match value
+        .map_err(|e| format!("invalid JSON: {e}"))
+        .and_then(|value| extract(target, &value))
+    {
+        Ok(value) => target.reading("SUCCESS", Some(value), timestamp),
+        Err(e) => {
+            warn!("Error reading value for {}: {e}", target.alias);
+            target.reading("VALUE_ERROR", None, timestamp)
+        }
+    }
+}
+
+fn request(client: &Client, target: &HttpTarget, timeout: Duration) -> RequestBuilder {
+    let mut request = client
+        .request(target.method.into(), &target.url)
+        .timeout(timeout);
+    for (name, value) in &target.headers {
+        request = request.header(name, value);
+    }
+    if let Some(body) = &target.body {
+        request = request.json(body);
+    }
+    match &target.auth {
+        Some(Auth::Bearer { token }) => request.bearer_auth(token),
+        Some(Auth::Basic { username, password }) => request.basic_auth(username, password.as_ref()),
+        Some(Auth::Header { name, value }) => request.header(name, value),
+        None => request,
+    }
+}
+
+/// The single value at the target's `value_path`, strings are used as they are and anything else
+/// is kept as JSON
+fn extract(target: &HttpTarget, response: &Value) -> Result<String, String> {
+    let path = target.value_path()?;
+    match path.query(response).exactly_one() {
+        Ok(Value::Null) => Err(format!("{} is null", target.value_path)),
+        Ok(Value::String(value)) => Ok(value.clone()),
+        Ok(value) => Ok(value.to_string()),
+        Err(e) => Err(format!("{} didn't match one value: {e}", target.value_path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub::Stub;
+    use crate::target::Method;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    const READINGS: &str =
+        r#"{"sensors": [{"temperature": 21.5, "state": "ok"}, {"temperature": null}]}"#;
+
+    fn target(url: String, value_path: &str) -> HttpTarget {
+        HttpTarget {
+            id: Uuid::new_v4(),
+            alias: "station_01".to_string(),
+            location: "roof".to_string(),
+            url,
+            method: Method::Get,
+            headers: HashMap::new(),
+            body: None,
+            auth: None,
+            value_path: value_path.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_stub() {
+        let stub = Stub::default()
+            .with_response("/readings", 200, READINGS)
+            .with_response("/broken", 503, "")
+            .with_response("/html", 200, "<html></html>");
+        let addr = stub.start().await;
+        let client = Client::new();
+        let timeout = Duration::from_millis(500);
+        let url = |path: &str| format!("http://{addr}{path}");
+
+        let mut temperature = target(url("/readings"), "$.sensors[0].temperature");
+        temperature
+            .headers
+            .insert("Accept".to_string(), "application/json".to_string());
+        temperature.auth = Some(Auth::Bearer {
+            token: "secret".to_string(),
+        });
+        let reading = fetch(&client, &temperature, timeout).await;
+        assert_eq!(reading.status, "SUCCESS");
+        assert_eq!(reading.value.as_deref(), Some("21.5"));
+        assert_eq!(reading.source, "roof.station_01");
+
+        let state = target(url("/readings"), "$.sensors[0].state");
+        let reading = fetch(&client, &state, timeout).await;
+        assert_eq!(reading.value.as_deref(), Some("ok"));
+
+        let cases = [
+            (
+                target(url("/readings"), "$.sensors[1].temperature"),
+                "VALUE_ERROR",
+            ),
+            (
+                target(url("/readings"), "$.sensors[*].temperature"),
+                "VALUE_ERROR",
+            ),
+            (target(url("/readings"), "$.humidity"), "VALUE_ERROR"),
+            (target(url("/html"), "$.temperature"), "VALUE_ERROR"),
+            (target(url("/broken"), "$.temperature"), "COMM_ERROR"),
+            (target(url("/missing"), "$.temperature"), "COMM_ERROR"),
+            // Nothing can listen on port 0, so the connection is refused
+            (
+                target("http://127.0.0.1:0/readings".to_string(), "$.temperature"),
+                "COMM_ERROR",
+            ),
+        ];
+        for (target, expected_status) in cases {
+            let reading = fetch(&client, &target, timeout).await;
+            assert_eq!(
+                reading.status, expected_status,
+                "{} {}",
+                target.url, target.value_path
+            );
+            assert_eq!(reading.value, None);
+        }
+
+        let request = &stub.requests()[0];
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/readings");
+        assert_eq!(
+            request.headers.get("authorization").map(String::as_str),
+            Some("Bearer secret")
+        );
+        assert_eq!(
+            request.headers.get("accept").map(String::as_str),
+            Some("application/json")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_with_auth() {
+        let stub = Stub::default().with_response("/query", 200, r#"{"value": 3}"#);
+        let addr = stub.start().await;
+        let client = Client::new();
+
+        let mut query = target(format!("http://{addr}/query"), "$.value");
+        query.method = Method::Post;
+        query.body = Some(serde_json::json!({ "register": "energy" }));
+        query.auth = Some(Auth::Basic {
+            username: "meter".to_string(),
+            password: Some("hunter2".to_string()),
+        });
+        let reading = fetch(&client, &query, Duration::from_millis(500)).await;
+        assert_eq!(reading.value.as_deref(), Some("3"));
+
+        query.auth = Some(Auth::Header {
+            name: "X-API-Key".to_string(),
+            value: "abc123".to_string(),
+        });
+        fetch(&client, &query, Duration::from_millis(500)).await;
+
+        let requests = stub.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(
+            serde_json::from_slice::<Value>(&requests[0].body).unwrap(),
+            serde_json::json!({ "register": "energy" })
+        );
+        // base64 of meter:hunter2
+        assert_eq!(
+            requests[0].headers.get("authorization").map(String::as_str),
+            Some("Basic bWV0ZXI6aHVudGVyMg==")
+        );
+        assert_eq!(
+            requests[1].headers.get("x-api-key").map(String::as_str),
+            Some("abc123")
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Minimal HTTP/1.1 server returning canned responses, for testing the provider against without a
+//! real device. Every request it receives is recorded so tests can check what was sent.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone, Default)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercased
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Default, Clone)]
+pub struct Stub {
+    responses: HashMap<String, (u16, String)>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Stub {
+    /// Respond to requests for `path` with the status and body, anything else gets a 404
+    pub fn with_response(mut self, path: &str, status: u16, body: &str) -> Self {
+        self.responses
+            .insert(path.to_string(), (status, body.to_string()));
+        self
+    }
+
+    /// Start serving on a free local port, for as long as the test runs
+    pub async fn start(&self) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stub = Arc::new(self.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stub.clone().serve(stream));
+            }
+        });
+        addr
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    async fn serve(self: Arc<Self>, mut stream: TcpStream) {
+        let request = match read_request(&mut stream).await {
+            Some(request) => request,
+            None => return,
+        };
+        let (status, body) = self
+            .responses
+            .get(&request.path)
+            .cloned()
+            .unwrap_or((404, String::new()));
+        self.requests.lock().unwrap().push(request);
+
+        let response = format!(
+            "HTTP/1.1 {status} STUB\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut buf = Vec::new();
+    let head_end = loop {
+        let mut chunk = [0u8; 1024];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let mut request = Request {
+        method: request_line.next()?.to_string(),
+        path: request_line.next()?.to_string(),
+        ..Default::default()
+    };
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            request
+                .headers
+                .insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = request
+        .headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = buf[head_end + 4..].to_vec();
+    while body.len() < length {
+        let mut chunk = [0u8; 1024];
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    request.body = body;
+    Some(request)
+}
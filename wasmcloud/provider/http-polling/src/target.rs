@@ -0,0 +1,175 @@
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json_path::JsonPath;
+use std::collections::HashMap;
+use uuid::Uuid;
+use wasmcloud_interface_polling::reading::ReadingTarget;
+
+/// An HTTP endpoint to poll, sent as the `targetData` of an `AddPollTarget` request
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct HttpTarget {
+    pub id: Uuid,
+    pub alias: String,
+    pub location: String,
+    /// `http` or `https` URL returning JSON, e.g. `http://10.0.0.12/api/v1/readings`
+    pub url: String,
+    #[serde(default)]
+    pub method: Method,
+    /// Extra headers sent with every request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Body sent with `POST` and `PUT` requests, as JSON
+    #[serde(default)]
+    pub body: Option<serde_json::Value>,
+    #[serde(default)]
+    pub auth: Option<Auth>,
+    /// JSONPath to the value in the response, e.g. `$.sensors[0].temperature`, which must match
+    /// exactly one value
+    pub value_path: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Method {
+    #[default]
+    Get,
+    Post,
+    Put,
+}
+
+impl From<Method> for reqwest::Method {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Get => reqwest::Method::GET,
+            Method::Post => reqwest::Method::POST,
+            Method::Put => reqwest::Method::PUT,
+        }
+    }
+}
+
+/// Credentials sent with every request to the target
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+    /// `Authorization: Bearer <token>`
+    Bearer { token: String },
+    /// `Authorization: Basic <base64 of username:password>`
+    Basic {
+        username: String,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    /// An API key sent in the given header, e.g. `X-API-Key`
+    Header { name: String, value: String },
+}
+
+// Targets are logged, their credentials shouldn't be
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Auth::Bearer { .. } => write!(f, "Bearer"),
+            Auth::Basic { username, .. } => write!(f, "Basic({username})"),
+            Auth::Header { name, .. } => write!(f, "Header({name})"),
+        }
+    }
+}
+
+impl HttpTarget {
+    pub fn value_path(&self) -> Result<JsonPath, String> {
+        JsonPath::parse(&self.value_path)
+            .map_err(|e| format!("invalid value path {}: {e}", self.value_path))
+    }
+
+    /// Check the target can be polled before it's added
+    pub fn validate(&self) -> Result<(), String> {
+        let url = Url::parse(&self.url).map_err(|e| format!("invalid URL {}: {e}", self.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("unsupported URL scheme: {}", url.scheme()));
+        }
+        for (name, value) in &self.headers {
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid header name: {name}"))?;
+            HeaderValue::from_str(value).map_err(|_| format!("invalid value for header {name}"))?;
+        }
+        if let Some(Auth::Header { name, value }) = &self.auth {
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid auth header name: {name}"))?;
+            // The value is a credential, so it isn't included in the error
+            HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid value for auth header {name}"))?;
+        }
+        if self.body.is_some() && self.method == Method::Get {
+            return Err("GET requests can't have a body".to_string());
+        }
+        self.value_path().map(|_| ())
+    }
+}
+
+impl ReadingTarget for HttpTarget {
+    fn target_id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn location(&self) -> &str {
+        &self.location
+    }
+
+    fn alias(&self) -> &str {
+        &self.alias
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_deserialize() {
+        let target_json = r#"
+            {
+                "id": "2f6b8c4d-1a3e-4f5b-8c7d-9e0f1a2b3c4d",
+                "alias": "meter_01",
+                "location": "plant_1",
+                "url": "https://10.0.0.12/api/v1/readings",
+                "headers": { "Accept": "application/json" },
+                "auth": { "type": "bearer", "token": "secret" },
+                "value_path": "$.sensors[0].temperature"
+            }
+            "#;
+        let target: HttpTarget = serde_json::from_str(target_json).unwrap();
+        assert_eq!(target.method, Method::Get);
+        assert_eq!(
+            target.auth,
+            Some(Auth::Bearer {
+                token: "secret".to_string()
+            })
+        );
+        assert_eq!(target.validate(), Ok(()));
+        assert!(!format!("{target:?}").contains("secret"));
+
+        let bad_path = target_json.replace("$.sensors[0].temperature", "$.sensors[");
+        let target: HttpTarget = serde_json::from_str(&bad_path).unwrap();
+        assert!(target.validate().is_err());
+
+        let bad_scheme = target_json.replace("https://", "ftp://");
+        let target: HttpTarget = serde_json::from_str(&bad_scheme).unwrap();
+        assert!(target.validate().is_err());
+
+        let get_with_body = target_json.replace(r#""headers""#, r#""body": {}, "headers""#);
+        let mut target: HttpTarget = serde_json::from_str(&get_with_body).unwrap();
+        assert!(target.validate().is_err());
+        target.method = Method::Post;
+        assert_eq!(target.validate(), Ok(()));
+
+        let mut target: HttpTarget = serde_json::from_str(target_json).unwrap();
+        target.auth = Some(Auth::Header {
+            name: "X-API-Key".to_string(),
+            value: "abc\r\n123".to_string(),
+        });
+        assert_eq!(
+            target.validate(),
+            Err("invalid value for auth header X-API-Key".to_string())
+        );
+    }
+}
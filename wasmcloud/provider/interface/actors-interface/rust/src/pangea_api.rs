@@ -484,6 +484,10 @@ pub struct LogEvent {
     /// username, first and last name, or a combination of fields.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub actor: Option<String>,
+    /// GPS coordinates of where the recorded action occurred as `latitude,longitude` or
+    /// `latitude,longitude,altitude`. Only set for sensors whose location gives them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gps: Option<String>,
     /// This field is used to record a detailed account of what action occurred. This can be recorded as free-form text
     /// or as a JSON field. If JSON is provided the log viewer will render this field as JSON.
     #[serde(default)]
@@ -496,6 +500,10 @@ pub struct LogEvent {
     /// any change made by the recorded action. If JSON is provided, the log viewer will render this field as JSON.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub old: Option<String>,
+    /// Quality of the reading being recorded, `GOOD`, `UNCERTAIN` or `BAD` with an optional reason,
+    /// e.g. `UNCERTAIN_LAST_KNOWN_VALUE`. Only set for sensor readings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
     /// The source field is for recording from where an activity occurred. This could be used to record a client's IP address,
     /// country of origin, the application used, etc.
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -526,7 +534,7 @@ pub fn encode_log_event<W: wasmbus_rpc::cbor::Write>(
 where
     <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
 {
-    e.map(10)?;
+    e.map(12)?;
     if let Some(val) = val.action.as_ref() {
         e.str("action")?;
         e.str(val)?;
@@ -539,6 +547,12 @@ where
     } else {
         e.null()?;
     }
+    if let Some(val) = val.gps.as_ref() {
+        e.str("gps")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
     e.str("message")?;
     e.str(&val.message)?;
     if let Some(val) = val.new.as_ref() {
@@ -553,6 +567,12 @@ where
     } else {
         e.null()?;
     }
+    if let Some(val) = val.quality.as_ref() {
+        e.str("quality")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
     if let Some(val) = val.source.as_ref() {
         e.str("source")?;
         e.str(val)?;
@@ -592,9 +612,11 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
     let __result = {
         let mut action: Option<Option<String>> = Some(None);
         let mut actor: Option<Option<String>> = Some(None);
+        let mut gps: Option<Option<String>> = Some(None);
         let mut message: Option<String> = None;
         let mut new: Option<Option<String>> = Some(None);
         let mut old: Option<Option<String>> = Some(None);
+        let mut quality: Option<Option<String>> = Some(None);
         let mut source: Option<Option<String>> = Some(None);
         let mut status: Option<Option<String>> = Some(None);
         let mut target: Option<Option<String>> = Some(None);
@@ -630,17 +652,17 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
                             Some(Some(d.str()?.to_string()))
                         }
                     }
-                    2 => message = Some(d.str()?.to_string()),
-                    3 => {
-                        new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                    2 => {
+                        gps = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
                             Some(Some(d.str()?.to_string()))
                         }
                     }
+                    3 => message = Some(d.str()?.to_string()),
                     4 => {
-                        old = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -648,7 +670,7 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
                         }
                     }
                     5 => {
-                        source = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        old = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -656,7 +678,7 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
                         }
                     }
                     6 => {
-                        status = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        quality = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -664,7 +686,7 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
                         }
                     }
                     7 => {
-                        target = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        source = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -672,7 +694,7 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
                         }
                     }
                     8 => {
-                        tenant_id = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        status = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -680,6 +702,22 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
                         }
                     }
                     9 => {
+                        target = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    10 => {
+                        tenant_id = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    11 => {
                         timestamp = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
@@ -711,6 +749,14 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
                             Some(Some(d.str()?.to_string()))
                         }
                     }
+                    "gps" => {
+                        gps = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
                     "message" => message = Some(d.str()?.to_string()),
                     "new" => {
                         new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
@@ -728,6 +774,14 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
                             Some(Some(d.str()?.to_string()))
                         }
                     }
+                    "quality" => {
+                        quality = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
                     "source" => {
                         source = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
@@ -775,16 +829,18 @@ pub fn decode_log_event(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<LogEve
         LogEvent {
             action: action.unwrap(),
             actor: actor.unwrap(),
+            gps: gps.unwrap(),
 
             message: if let Some(__x) = message {
                 __x
             } else {
                 return Err(RpcError::Deser(
-                    "missing field LogEvent.message (#2)".to_string(),
+                    "missing field LogEvent.message (#3)".to_string(),
                 ));
             },
             new: new.unwrap(),
             old: old.unwrap(),
+            quality: quality.unwrap(),
             source: source.unwrap(),
             status: status.unwrap(),
             target: target.unwrap(),
@@ -1735,6 +1791,10 @@ pub struct SearchRestrictionParams {
     /// A list of actors to restrict the search to
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub actor: Option<Strings>,
+    /// A list of asset locations to restrict the search to, at any level of the ISA-95
+    /// hierarchy, e.g. `acme/plant_1` for every sensor on that site
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Strings>,
     /// A list of messages to restrict the search to
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub message: Option<Strings>,
@@ -1744,6 +1804,10 @@ pub struct SearchRestrictionParams {
     /// A list of old values to restrict the search to
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub old: Option<Strings>,
+    /// A list of reading qualities to restrict the search to, e.g. `GOOD` and `GOOD_CLAMPED` to leave
+    /// out questionable data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Strings>,
     /// A list of received_at timestamps to restrict the search to. This is the
     /// timestamp provided by Pangea when logging the event.
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -1776,7 +1840,7 @@ pub fn encode_search_restriction_params<W: wasmbus_rpc::cbor::Write>(
 where
     <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
 {
-    e.map(11)?;
+    e.map(13)?;
     if let Some(val) = val.action.as_ref() {
         e.str("action")?;
         encode_strings(e, val)?;
@@ -1789,6 +1853,12 @@ where
     } else {
         e.null()?;
     }
+    if let Some(val) = val.location.as_ref() {
+        e.str("location")?;
+        encode_strings(e, val)?;
+    } else {
+        e.null()?;
+    }
     if let Some(val) = val.message.as_ref() {
         e.str("message")?;
         encode_strings(e, val)?;
@@ -1807,6 +1877,12 @@ where
     } else {
         e.null()?;
     }
+    if let Some(val) = val.quality.as_ref() {
+        e.str("quality")?;
+        encode_strings(e, val)?;
+    } else {
+        e.null()?;
+    }
     if let Some(val) = val.received_at.as_ref() {
         e.str("received_at")?;
         encode_strings(e, val)?;
@@ -1854,9 +1930,11 @@ pub fn decode_search_restriction_params(
     let __result = {
         let mut action: Option<Option<Strings>> = Some(None);
         let mut actor: Option<Option<Strings>> = Some(None);
+        let mut location: Option<Option<Strings>> = Some(None);
         let mut message: Option<Option<Strings>> = Some(None);
         let mut new: Option<Option<Strings>> = Some(None);
         let mut old: Option<Option<Strings>> = Some(None);
+        let mut quality: Option<Option<Strings>> = Some(None);
         let mut received_at: Option<Option<Strings>> = Some(None);
         let mut source: Option<Option<Strings>> = Some(None);
         let mut status: Option<Option<Strings>> = Some(None);
@@ -1904,7 +1982,7 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     2 => {
-                        message = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        location = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -1917,7 +1995,7 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     3 => {
-                        new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        message = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -1930,7 +2008,7 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     4 => {
-                        old = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -1943,7 +2021,7 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     5 => {
-                        received_at = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        old = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -1956,7 +2034,7 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     6 => {
-                        source = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        quality = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -1969,7 +2047,7 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     7 => {
-                        status = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        received_at = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -1982,7 +2060,7 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     8 => {
-                        target = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        source = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -1995,7 +2073,7 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     9 => {
-                        tenant_id = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                        status = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
                         } else {
@@ -2008,6 +2086,32 @@ pub fn decode_search_restriction_params(
                         }
                     }
                     10 => {
+                        target = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(decode_strings(d).map_err(|e| {
+                                format!(
+                                    "decoding 'jclmnop.iiot_poc.interface.pangea_api#Strings': {}",
+                                    e
+                                )
+                            })?))
+                        }
+                    }
+                    11 => {
+                        tenant_id = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(decode_strings(d).map_err(|e| {
+                                format!(
+                                    "decoding 'jclmnop.iiot_poc.interface.pangea_api#Strings': {}",
+                                    e
+                                )
+                            })?))
+                        }
+                    }
+                    12 => {
                         timestamp = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
@@ -2054,6 +2158,19 @@ pub fn decode_search_restriction_params(
                             })?))
                         }
                     }
+                    "location" => {
+                        location = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(decode_strings(d).map_err(|e| {
+                                format!(
+                                    "decoding 'jclmnop.iiot_poc.interface.pangea_api#Strings': {}",
+                                    e
+                                )
+                            })?))
+                        }
+                    }
                     "message" => {
                         message = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
@@ -2093,6 +2210,19 @@ pub fn decode_search_restriction_params(
                             })?))
                         }
                     }
+                    "quality" => {
+                        quality = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(decode_strings(d).map_err(|e| {
+                                format!(
+                                    "decoding 'jclmnop.iiot_poc.interface.pangea_api#Strings': {}",
+                                    e
+                                )
+                            })?))
+                        }
+                    }
                     "received_at" => {
                         received_at = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
@@ -2178,9 +2308,11 @@ pub fn decode_search_restriction_params(
         SearchRestrictionParams {
             action: action.unwrap(),
             actor: actor.unwrap(),
+            location: location.unwrap(),
             message: message.unwrap(),
             new: new.unwrap(),
             old: old.unwrap(),
+            quality: quality.unwrap(),
             received_at: received_at.unwrap(),
             source: source.unwrap(),
             status: status.unwrap(),
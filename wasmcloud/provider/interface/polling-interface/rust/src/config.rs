@@ -0,0 +1,116 @@
+//! Link configuration shared by the polling providers.
+//!
+//! Each provider's config is given in a link's values as JSON in `config_json`, or as base64
+//! encoded JSON in `config_b64`, and flattens [`PollingSettings`] into its own settings.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use wasmbus_rpc::error::{RpcError, RpcResult};
+
+use crate::encoding::Encoding;
+
+const DEFAULT_POLL_INTERVAL_MS: u32 = 10_000;
+
+/// Settings every polling provider has
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PollingSettings {
+    /// Poll interval for targets added without one, in ms
+    #[serde(default)]
+    pub poll_interval_ms: Option<u32>,
+    /// How readings are encoded in the `PollResult` sent to the actor, JSON if this isn't set
+    #[serde(default)]
+    pub encoding: Option<Encoding>,
+}
+
+impl PollingSettings {
+    /// Settings from `extra` override these ones, unless they aren't set
+    pub fn merge(&self, extra: &PollingSettings) -> PollingSettings {
+        PollingSettings {
+            poll_interval_ms: extra.poll_interval_ms.or(self.poll_interval_ms),
+            encoding: extra.encoding.or(self.encoding),
+        }
+    }
+
+    pub fn poll_interval(&self) -> u32 {
+        self.poll_interval_ms.unwrap_or(DEFAULT_POLL_INTERVAL_MS)
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding.unwrap_or_default()
+    }
+}
+
+/// A provider's config from a link's values, or its default config if the link doesn't have one
+pub fn from_link_values<T: DeserializeOwned + Default>(
+    values: &HashMap<String, String>,
+) -> RpcResult<T> {
+    if let Some(config_b64) = values.get("config_b64") {
+        let bytes = base64::decode(config_b64.as_bytes())
+            .map_err(|e| RpcError::InvalidParameter(format!("invalid base64 encoding: {}", e)))?;
+        serde_json::from_slice::<T>(&bytes)
+            .map_err(|e| RpcError::InvalidParameter(format!("corrupt config_b64: {}", e)))
+    } else if let Some(config) = values.get("config_json") {
+        serde_json::from_str::<T>(config)
+            .map_err(|e| RpcError::InvalidParameter(format!("corrupt config_json: {}", e)))
+    } else {
+        Ok(T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    struct ProviderConfig {
+        #[serde(flatten)]
+        polling: PollingSettings,
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+    }
+
+    #[test]
+    fn test_from_link_values() {
+        let json = r#"{"poll_interval_ms": 500, "encoding": "cbor", "timeout_ms": 100}"#;
+        let expected = ProviderConfig {
+            polling: PollingSettings {
+                poll_interval_ms: Some(500),
+                encoding: Some(Encoding::Cbor),
+            },
+            timeout_ms: Some(100),
+        };
+        let values = HashMap::from([("config_json".to_string(), json.to_string())]);
+        assert_eq!(
+            from_link_values::<ProviderConfig>(&values).unwrap(),
+            expected
+        );
+        let values = HashMap::from([("config_b64".to_string(), base64::encode(json))]);
+        assert_eq!(
+            from_link_values::<ProviderConfig>(&values).unwrap(),
+            expected
+        );
+
+        let config: ProviderConfig = from_link_values(&HashMap::new()).unwrap();
+        assert_eq!(config, ProviderConfig::default());
+        assert_eq!(config.polling.poll_interval(), DEFAULT_POLL_INTERVAL_MS);
+        assert_eq!(config.polling.encoding(), Encoding::Json);
+
+        let values = HashMap::from([("config_json".to_string(), "{".to_string())]);
+        assert!(from_link_values::<ProviderConfig>(&values).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let defaults = PollingSettings {
+            poll_interval_ms: Some(60_000),
+            encoding: Some(Encoding::Msgpack),
+        };
+        let link = PollingSettings {
+            poll_interval_ms: Some(1_000),
+            encoding: None,
+        };
+        let merged = defaults.merge(&link);
+        assert_eq!(merged.poll_interval(), 1_000);
+        assert_eq!(merged.encoding(), Encoding::Msgpack);
+    }
+}
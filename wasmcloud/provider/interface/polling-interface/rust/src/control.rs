@@ -0,0 +1,979 @@
+// This file is @generated by wasmcloud/weld-codegen 0.7.0.
+// It is not intended for manual editing.
+// namespace: org.wasmcloud.interface.control
+
+#[allow(unused_imports)]
+use async_trait::async_trait;
+#[allow(unused_imports)]
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use std::{borrow::Borrow, borrow::Cow, io::Write, string::ToString};
+#[allow(unused_imports)]
+use wasmbus_rpc::{
+    cbor::*,
+    common::{
+        deserialize, message_format, serialize, Context, Message, MessageDispatch, MessageFormat,
+        SendOpts, Transport,
+    },
+    error::{RpcError, RpcResult},
+    Timestamp,
+};
+
+#[allow(dead_code)]
+pub const SMITHY_VERSION: &str = "1.0";
+
+/// A command for a single device
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CommandRequest {
+    /// Name of the command, for example `set_position` or `stop`
+    #[serde(default)]
+    pub command: String,
+    /// Identifies the device the command is for. For a sensor based provider
+    /// this would be the sensor ID.
+    #[serde(rename = "targetId")]
+    #[serde(default)]
+    pub target_id: String,
+    /// How long to wait for the device to acknowledge the command, in
+    /// milliseconds. If this isn't set the provider's default is used.
+    #[serde(rename = "timeoutMs")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u32>,
+    /// Optional value for the command, such as a setpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+// Encode CommandRequest as CBOR and append to output stream
+#[doc(hidden)]
+#[allow(unused_mut)]
+pub fn encode_command_request<W: wasmbus_rpc::cbor::Write>(
+    mut e: &mut wasmbus_rpc::cbor::Encoder<W>,
+    val: &CommandRequest,
+) -> RpcResult<()>
+where
+    <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
+{
+    e.map(4)?;
+    e.str("command")?;
+    e.str(&val.command)?;
+    e.str("targetId")?;
+    e.str(&val.target_id)?;
+    if let Some(val) = val.timeout_ms.as_ref() {
+        e.str("timeoutMs")?;
+        e.u32(*val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.value.as_ref() {
+        e.str("value")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    Ok(())
+}
+
+// Decode CommandRequest from cbor input stream
+#[doc(hidden)]
+pub fn decode_command_request(
+    d: &mut wasmbus_rpc::cbor::Decoder<'_>,
+) -> Result<CommandRequest, RpcError> {
+    let __result = {
+        let mut command: Option<String> = None;
+        let mut target_id: Option<String> = None;
+        let mut timeout_ms: Option<Option<u32>> = Some(None);
+        let mut value: Option<Option<String>> = Some(None);
+
+        let is_array = match d.datatype()? {
+            wasmbus_rpc::cbor::Type::Array => true,
+            wasmbus_rpc::cbor::Type::Map => false,
+            _ => {
+                return Err(RpcError::Deser(
+                    "decoding struct CommandRequest, expected array or map".to_string(),
+                ))
+            }
+        };
+        if is_array {
+            let len = d.fixed_array()?;
+            for __i in 0..(len as usize) {
+                match __i {
+                    0 => command = Some(d.str()?.to_string()),
+                    1 => target_id = Some(d.str()?.to_string()),
+                    2 => {
+                        timeout_ms = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u32()?))
+                        }
+                    }
+                    3 => {
+                        value = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+
+                    _ => d.skip()?,
+                }
+            }
+        } else {
+            let len = d.fixed_map()?;
+            for __i in 0..(len as usize) {
+                match d.str()? {
+                    "command" => command = Some(d.str()?.to_string()),
+                    "targetId" => target_id = Some(d.str()?.to_string()),
+                    "timeoutMs" => {
+                        timeout_ms = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u32()?))
+                        }
+                    }
+                    "value" => {
+                        value = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    _ => d.skip()?,
+                }
+            }
+        }
+        CommandRequest {
+            command: if let Some(__x) = command {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field CommandRequest.command (#0)".to_string(),
+                ));
+            },
+
+            target_id: if let Some(__x) = target_id {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field CommandRequest.target_id (#1)".to_string(),
+                ));
+            },
+            timeout_ms: timeout_ms.unwrap(),
+            value: value.unwrap(),
+        }
+    };
+    Ok(__result)
+}
+/// The outcome of a command, which can be recorded in an audit log as a change
+/// from the `old` value to the `new` value.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CommandResponse {
+    /// True if the device acknowledged the command before the timeout
+    #[serde(default)]
+    pub acknowledged: bool,
+    /// The command that was sent
+    pub command: CommandRequest,
+    /// If this field is present then the command failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ControlError>,
+    /// The value reported by the device after it applied the command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+    /// The value reported by the device before it applied the command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    /// Where the device is, in the same form as the `source` of its readings, if the provider
+    /// knows the device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// When the command was acknowledged, or given up on, as a unix timestamp in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+}
+
+// Encode CommandResponse as CBOR and append to output stream
+#[doc(hidden)]
+#[allow(unused_mut)]
+pub fn encode_command_response<W: wasmbus_rpc::cbor::Write>(
+    mut e: &mut wasmbus_rpc::cbor::Encoder<W>,
+    val: &CommandResponse,
+) -> RpcResult<()>
+where
+    <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
+{
+    e.map(7)?;
+    e.str("acknowledged")?;
+    e.bool(val.acknowledged)?;
+    e.str("command")?;
+    encode_command_request(e, &val.command)?;
+    if let Some(val) = val.error.as_ref() {
+        e.str("error")?;
+        encode_control_error(e, val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.new.as_ref() {
+        e.str("new")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.old.as_ref() {
+        e.str("old")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.source.as_ref() {
+        e.str("source")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.timestamp.as_ref() {
+        e.str("timestamp")?;
+        e.u64(*val)?;
+    } else {
+        e.null()?;
+    }
+    Ok(())
+}
+
+// Decode CommandResponse from cbor input stream
+#[doc(hidden)]
+pub fn decode_command_response(
+    d: &mut wasmbus_rpc::cbor::Decoder<'_>,
+) -> Result<CommandResponse, RpcError> {
+    let __result = {
+        let mut acknowledged: Option<bool> = None;
+        let mut command: Option<CommandRequest> = None;
+        let mut error: Option<Option<ControlError>> = Some(None);
+        let mut new: Option<Option<String>> = Some(None);
+        let mut old: Option<Option<String>> = Some(None);
+        let mut source: Option<Option<String>> = Some(None);
+        let mut timestamp: Option<Option<u64>> = Some(None);
+
+        let is_array = match d.datatype()? {
+            wasmbus_rpc::cbor::Type::Array => true,
+            wasmbus_rpc::cbor::Type::Map => false,
+            _ => {
+                return Err(RpcError::Deser(
+                    "decoding struct CommandResponse, expected array or map".to_string(),
+                ))
+            }
+        };
+        if is_array {
+            let len = d.fixed_array()?;
+            for __i in 0..(len as usize) {
+                match __i {
+                    0 => acknowledged = Some(d.bool()?),
+                    1 => {
+                        command = Some(decode_command_request(d).map_err(|e| {
+                            format!(
+                                "decoding 'org.wasmcloud.interface.control#CommandRequest': {}",
+                                e
+                            )
+                        })?)
+                    }
+                    2 => {
+                        error = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(decode_control_error(d).map_err(|e| {
+                                format!(
+                                    "decoding 'org.wasmcloud.interface.control#ControlError': {}",
+                                    e
+                                )
+                            })?))
+                        }
+                    }
+                    3 => {
+                        new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    4 => {
+                        old = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    5 => {
+                        source = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    6 => {
+                        timestamp = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u64()?))
+                        }
+                    }
+
+                    _ => d.skip()?,
+                }
+            }
+        } else {
+            let len = d.fixed_map()?;
+            for __i in 0..(len as usize) {
+                match d.str()? {
+                    "acknowledged" => acknowledged = Some(d.bool()?),
+                    "command" => {
+                        command = Some(decode_command_request(d).map_err(|e| {
+                            format!(
+                                "decoding 'org.wasmcloud.interface.control#CommandRequest': {}",
+                                e
+                            )
+                        })?)
+                    }
+                    "error" => {
+                        error = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(decode_control_error(d).map_err(|e| {
+                                format!(
+                                    "decoding 'org.wasmcloud.interface.control#ControlError': {}",
+                                    e
+                                )
+                            })?))
+                        }
+                    }
+                    "new" => {
+                        new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "old" => {
+                        old = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "source" => {
+                        source = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "timestamp" => {
+                        timestamp = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u64()?))
+                        }
+                    }
+                    _ => d.skip()?,
+                }
+            }
+        }
+        CommandResponse {
+            acknowledged: if let Some(__x) = acknowledged {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field CommandResponse.acknowledged (#0)".to_string(),
+                ));
+            },
+
+            command: if let Some(__x) = command {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field CommandResponse.command (#1)".to_string(),
+                ));
+            },
+            error: error.unwrap(),
+            new: new.unwrap(),
+            old: old.unwrap(),
+            source: source.unwrap(),
+            timestamp: timestamp.unwrap(),
+        }
+    };
+    Ok(__result)
+}
+/// A configuration document for a single device
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ConfigRequest {
+    /// JSON document containing only the settings to change, for example
+    /// `{"poll_interval": 30000}`
+    #[serde(default)]
+    pub config: String,
+    /// Identifies the device the configuration is for. For a sensor based
+    /// provider this would be the sensor ID.
+    #[serde(rename = "targetId")]
+    #[serde(default)]
+    pub target_id: String,
+    /// How long to wait for the device to report the new configuration, in
+    /// milliseconds. If this isn't set the provider's default is used.
+    #[serde(rename = "timeoutMs")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u32>,
+}
+
+// Encode ConfigRequest as CBOR and append to output stream
+#[doc(hidden)]
+#[allow(unused_mut)]
+pub fn encode_config_request<W: wasmbus_rpc::cbor::Write>(
+    mut e: &mut wasmbus_rpc::cbor::Encoder<W>,
+    val: &ConfigRequest,
+) -> RpcResult<()>
+where
+    <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
+{
+    e.map(3)?;
+    e.str("config")?;
+    e.str(&val.config)?;
+    e.str("targetId")?;
+    e.str(&val.target_id)?;
+    if let Some(val) = val.timeout_ms.as_ref() {
+        e.str("timeoutMs")?;
+        e.u32(*val)?;
+    } else {
+        e.null()?;
+    }
+    Ok(())
+}
+
+// Decode ConfigRequest from cbor input stream
+#[doc(hidden)]
+pub fn decode_config_request(
+    d: &mut wasmbus_rpc::cbor::Decoder<'_>,
+) -> Result<ConfigRequest, RpcError> {
+    let __result = {
+        let mut config: Option<String> = None;
+        let mut target_id: Option<String> = None;
+        let mut timeout_ms: Option<Option<u32>> = Some(None);
+
+        let is_array = match d.datatype()? {
+            wasmbus_rpc::cbor::Type::Array => true,
+            wasmbus_rpc::cbor::Type::Map => false,
+            _ => {
+                return Err(RpcError::Deser(
+                    "decoding struct ConfigRequest, expected array or map".to_string(),
+                ))
+            }
+        };
+        if is_array {
+            let len = d.fixed_array()?;
+            for __i in 0..(len as usize) {
+                match __i {
+                    0 => config = Some(d.str()?.to_string()),
+                    1 => target_id = Some(d.str()?.to_string()),
+                    2 => {
+                        timeout_ms = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u32()?))
+                        }
+                    }
+
+                    _ => d.skip()?,
+                }
+            }
+        } else {
+            let len = d.fixed_map()?;
+            for __i in 0..(len as usize) {
+                match d.str()? {
+                    "config" => config = Some(d.str()?.to_string()),
+                    "targetId" => target_id = Some(d.str()?.to_string()),
+                    "timeoutMs" => {
+                        timeout_ms = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u32()?))
+                        }
+                    }
+                    _ => d.skip()?,
+                }
+            }
+        }
+        ConfigRequest {
+            config: if let Some(__x) = config {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field ConfigRequest.config (#0)".to_string(),
+                ));
+            },
+
+            target_id: if let Some(__x) = target_id {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field ConfigRequest.target_id (#1)".to_string(),
+                ));
+            },
+            timeout_ms: timeout_ms.unwrap(),
+        }
+    };
+    Ok(__result)
+}
+/// The outcome of a configuration push, which can be recorded in an audit log
+/// as a change from the `old` configuration to the `new` configuration.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ConfigResponse {
+    /// True if the device reported the new version before the timeout
+    #[serde(default)]
+    pub applied: bool,
+    /// If this field is present then the configuration wasn't applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ControlError>,
+    /// JSON document of the device's configuration after the push
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+    /// JSON document of the device's configuration before the push
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(rename = "targetId")]
+    #[serde(default)]
+    pub target_id: String,
+    /// Version assigned to the configuration document by the provider
+    #[serde(default)]
+    pub version: u64,
+}
+
+// Encode ConfigResponse as CBOR and append to output stream
+#[doc(hidden)]
+#[allow(unused_mut)]
+pub fn encode_config_response<W: wasmbus_rpc::cbor::Write>(
+    mut e: &mut wasmbus_rpc::cbor::Encoder<W>,
+    val: &ConfigResponse,
+) -> RpcResult<()>
+where
+    <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
+{
+    e.map(6)?;
+    e.str("applied")?;
+    e.bool(val.applied)?;
+    if let Some(val) = val.error.as_ref() {
+        e.str("error")?;
+        encode_control_error(e, val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.new.as_ref() {
+        e.str("new")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.old.as_ref() {
+        e.str("old")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    e.str("targetId")?;
+    e.str(&val.target_id)?;
+    e.str("version")?;
+    e.u64(val.version)?;
+    Ok(())
+}
+
+// Decode ConfigResponse from cbor input stream
+#[doc(hidden)]
+pub fn decode_config_response(
+    d: &mut wasmbus_rpc::cbor::Decoder<'_>,
+) -> Result<ConfigResponse, RpcError> {
+    let __result = {
+        let mut applied: Option<bool> = None;
+        let mut error: Option<Option<ControlError>> = Some(None);
+        let mut new: Option<Option<String>> = Some(None);
+        let mut old: Option<Option<String>> = Some(None);
+        let mut target_id: Option<String> = None;
+        let mut version: Option<u64> = None;
+
+        let is_array = match d.datatype()? {
+            wasmbus_rpc::cbor::Type::Array => true,
+            wasmbus_rpc::cbor::Type::Map => false,
+            _ => {
+                return Err(RpcError::Deser(
+                    "decoding struct ConfigResponse, expected array or map".to_string(),
+                ))
+            }
+        };
+        if is_array {
+            let len = d.fixed_array()?;
+            for __i in 0..(len as usize) {
+                match __i {
+                    0 => applied = Some(d.bool()?),
+                    1 => {
+                        error = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(decode_control_error(d).map_err(|e| {
+                                format!(
+                                    "decoding 'org.wasmcloud.interface.control#ControlError': {}",
+                                    e
+                                )
+                            })?))
+                        }
+                    }
+                    2 => {
+                        new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    3 => {
+                        old = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    4 => target_id = Some(d.str()?.to_string()),
+                    5 => version = Some(d.u64()?),
+                    _ => d.skip()?,
+                }
+            }
+        } else {
+            let len = d.fixed_map()?;
+            for __i in 0..(len as usize) {
+                match d.str()? {
+                    "applied" => applied = Some(d.bool()?),
+                    "error" => {
+                        error = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(decode_control_error(d).map_err(|e| {
+                                format!(
+                                    "decoding 'org.wasmcloud.interface.control#ControlError': {}",
+                                    e
+                                )
+                            })?))
+                        }
+                    }
+                    "new" => {
+                        new = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "old" => {
+                        old = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "targetId" => target_id = Some(d.str()?.to_string()),
+                    "version" => version = Some(d.u64()?),
+                    _ => d.skip()?,
+                }
+            }
+        }
+        ConfigResponse {
+            applied: if let Some(__x) = applied {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field ConfigResponse.applied (#0)".to_string(),
+                ));
+            },
+            error: error.unwrap(),
+            new: new.unwrap(),
+            old: old.unwrap(),
+
+            target_id: if let Some(__x) = target_id {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field ConfigResponse.target_id (#4)".to_string(),
+                ));
+            },
+
+            version: if let Some(__x) = version {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field ConfigResponse.version (#5)".to_string(),
+                ));
+            },
+        }
+    };
+    Ok(__result)
+}
+/// Contains the type or code for an error along with an optional description.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ControlError {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "errorType")]
+    #[serde(default)]
+    pub error_type: String,
+}
+
+// Encode ControlError as CBOR and append to output stream
+#[doc(hidden)]
+#[allow(unused_mut)]
+pub fn encode_control_error<W: wasmbus_rpc::cbor::Write>(
+    mut e: &mut wasmbus_rpc::cbor::Encoder<W>,
+    val: &ControlError,
+) -> RpcResult<()>
+where
+    <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
+{
+    e.map(2)?;
+    if let Some(val) = val.description.as_ref() {
+        e.str("description")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    e.str("errorType")?;
+    e.str(&val.error_type)?;
+    Ok(())
+}
+
+// Decode ControlError from cbor input stream
+#[doc(hidden)]
+pub fn decode_control_error(
+    d: &mut wasmbus_rpc::cbor::Decoder<'_>,
+) -> Result<ControlError, RpcError> {
+    let __result = {
+        let mut description: Option<Option<String>> = Some(None);
+        let mut error_type: Option<String> = None;
+
+        let is_array = match d.datatype()? {
+            wasmbus_rpc::cbor::Type::Array => true,
+            wasmbus_rpc::cbor::Type::Map => false,
+            _ => {
+                return Err(RpcError::Deser(
+                    "decoding struct ControlError, expected array or map".to_string(),
+                ))
+            }
+        };
+        if is_array {
+            let len = d.fixed_array()?;
+            for __i in 0..(len as usize) {
+                match __i {
+                    0 => {
+                        description = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    1 => error_type = Some(d.str()?.to_string()),
+                    _ => d.skip()?,
+                }
+            }
+        } else {
+            let len = d.fixed_map()?;
+            for __i in 0..(len as usize) {
+                match d.str()? {
+                    "description" => {
+                        description = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "errorType" => error_type = Some(d.str()?.to_string()),
+                    _ => d.skip()?,
+                }
+            }
+        }
+        ControlError {
+            description: description.unwrap(),
+
+            error_type: if let Some(__x) = error_type {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field ControlError.error_type (#1)".to_string(),
+                ));
+            },
+        }
+    };
+    Ok(__result)
+}
+/// The Control interface describes a service that sends commands to external
+/// hardware, such as a setpoint for a valve position or starting/stopping a
+/// motor, and waits for the hardware to acknowledge them.
+///
+/// It shares the `wasmcloud:polling` contract, so a provider which is already
+/// polling a device can also control it over the same link.
+/// wasmbus.contractId: wasmcloud:polling
+/// wasmbus.providerReceive
+#[async_trait]
+pub trait Control {
+    /// returns the capability contract id for this interface
+    fn contract_id() -> &'static str {
+        "wasmcloud:polling"
+    }
+    /// Send a command to a device and wait for the device to acknowledge it
+    async fn send_command(&self, ctx: &Context, arg: &CommandRequest)
+        -> RpcResult<CommandResponse>;
+    /// Push a new configuration document to a device and wait for the device to
+    /// report the new configuration in its next heartbeat
+    async fn push_config(&self, ctx: &Context, arg: &ConfigRequest) -> RpcResult<ConfigResponse>;
+}
+
+/// ControlReceiver receives messages defined in the Control service trait
+/// The Control interface describes a service that sends commands to external
+/// hardware, such as a setpoint for a valve position or starting/stopping a
+/// motor, and waits for the hardware to acknowledge them.
+///
+/// It shares the `wasmcloud:polling` contract, so a provider which is already
+/// polling a device can also control it over the same link.
+#[doc(hidden)]
+#[async_trait]
+pub trait ControlReceiver: MessageDispatch + Control {
+    async fn dispatch(&self, ctx: &Context, message: Message<'_>) -> Result<Vec<u8>, RpcError> {
+        match message.method {
+            "SendCommand" => {
+                let value: CommandRequest = wasmbus_rpc::common::deserialize(&message.arg)
+                    .map_err(|e| RpcError::Deser(format!("'CommandRequest': {}", e)))?;
+
+                let resp = Control::send_command(self, ctx, &value).await?;
+                let buf = wasmbus_rpc::common::serialize(&resp)?;
+
+                Ok(buf)
+            }
+            "PushConfig" => {
+                let value: ConfigRequest = wasmbus_rpc::common::deserialize(&message.arg)
+                    .map_err(|e| RpcError::Deser(format!("'ConfigRequest': {}", e)))?;
+
+                let resp = Control::push_config(self, ctx, &value).await?;
+                let buf = wasmbus_rpc::common::serialize(&resp)?;
+
+                Ok(buf)
+            }
+            _ => Err(RpcError::MethodNotHandled(format!(
+                "Control::{}",
+                message.method
+            ))),
+        }
+    }
+}
+
+/// ControlSender sends messages to a Control service
+/// The Control interface describes a service that sends commands to external
+/// hardware, such as a setpoint for a valve position or starting/stopping a
+/// motor, and waits for the hardware to acknowledge them.
+///
+/// It shares the `wasmcloud:polling` contract, so a provider which is already
+/// polling a device can also control it over the same link.
+/// client for sending Control messages
+#[derive(Clone, Debug)]
+pub struct ControlSender<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> ControlSender<T> {
+    /// Constructs a ControlSender with the specified transport
+    pub fn via(transport: T) -> Self {
+        Self { transport }
+    }
+
+    pub fn set_timeout(&self, interval: std::time::Duration) {
+        self.transport.set_timeout(interval);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl ControlSender<wasmbus_rpc::actor::prelude::WasmHost> {
+    /// Constructs a client for sending to a Control provider
+    /// implementing the 'wasmcloud:polling' capability contract, with the "default" link
+    pub fn new() -> Self {
+        let transport =
+            wasmbus_rpc::actor::prelude::WasmHost::to_provider("wasmcloud:polling", "default")
+                .unwrap();
+        Self { transport }
+    }
+
+    /// Constructs a client for sending to a Control provider
+    /// implementing the 'wasmcloud:polling' capability contract, with the specified link name
+    pub fn new_with_link(link_name: &str) -> wasmbus_rpc::error::RpcResult<Self> {
+        let transport =
+            wasmbus_rpc::actor::prelude::WasmHost::to_provider("wasmcloud:polling", link_name)?;
+        Ok(Self { transport })
+    }
+}
+#[async_trait]
+impl<T: Transport + std::marker::Sync + std::marker::Send> Control for ControlSender<T> {
+    #[allow(unused)]
+    /// Send a command to a device and wait for the device to acknowledge it
+    async fn send_command(
+        &self,
+        ctx: &Context,
+        arg: &CommandRequest,
+    ) -> RpcResult<CommandResponse> {
+        let buf = wasmbus_rpc::common::serialize(arg)?;
+
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "Control.SendCommand",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+
+        let value: CommandResponse = wasmbus_rpc::common::deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("'{}': CommandResponse", e)))?;
+        Ok(value)
+    }
+    #[allow(unused)]
+    /// Push a new configuration document to a device and wait for the device to
+    /// report the new configuration in its next heartbeat
+    async fn push_config(&self, ctx: &Context, arg: &ConfigRequest) -> RpcResult<ConfigResponse> {
+        let buf = wasmbus_rpc::common::serialize(arg)?;
+
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "Control.PushConfig",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+
+        let value: ConfigResponse = wasmbus_rpc::common::deserialize(&resp)
+            .map_err(|e| RpcError::Deser(format!("'{}': ConfigResponse", e)))?;
+        Ok(value)
+    }
+}
@@ -0,0 +1,123 @@
+//! Encodings for the `data` of a [`PollResult`], so providers and subscribers can agree on
+//! something more compact than JSON.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use wasmbus_rpc::error::{RpcError, RpcResult};
+
+use crate::PollResult;
+
+pub const CONTENT_TYPE_JSON: &str = "application/json";
+pub const CONTENT_TYPE_CBOR: &str = "application/cbor";
+pub const CONTENT_TYPE_MSGPACK: &str = "application/msgpack";
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Json,
+    Cbor,
+    Msgpack,
+}
+
+impl Encoding {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Encoding::Json => CONTENT_TYPE_JSON,
+            Encoding::Cbor => CONTENT_TYPE_CBOR,
+            Encoding::Msgpack => CONTENT_TYPE_MSGPACK,
+        }
+    }
+
+    /// Missing content types are treated as JSON, for results from providers which predate
+    /// the `contentType` field
+    pub fn from_content_type(content_type: Option<&str>) -> RpcResult<Self> {
+        match content_type {
+            None | Some(CONTENT_TYPE_JSON) => Ok(Encoding::Json),
+            Some(CONTENT_TYPE_CBOR) => Ok(Encoding::Cbor),
+            Some(CONTENT_TYPE_MSGPACK) => Ok(Encoding::Msgpack),
+            Some(other) => Err(RpcError::Deser(format!(
+                "unsupported content type: {other}"
+            ))),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> RpcResult<Vec<u8>> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value).map_err(|e| RpcError::Ser(e.to_string())),
+            Encoding::Cbor => minicbor_ser::to_vec(value).map_err(|e| RpcError::Ser(e.to_string())),
+            Encoding::Msgpack => {
+                rmp_serde::to_vec_named(value).map_err(|e| RpcError::Ser(e.to_string()))
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> RpcResult<T> {
+        match self {
+            Encoding::Json => {
+                serde_json::from_slice(bytes).map_err(|e| RpcError::Deser(e.to_string()))
+            }
+            Encoding::Cbor => {
+                minicbor_ser::from_slice(bytes).map_err(|e| RpcError::Deser(e.to_string()))
+            }
+            Encoding::Msgpack => {
+                rmp_serde::from_slice(bytes).map_err(|e| RpcError::Deser(e.to_string()))
+            }
+        }
+    }
+}
+
+impl PollResult {
+    /// Build a successful result with `value` encoded as its data
+    pub fn encoded<T: Serialize>(value: &T, encoding: Encoding) -> RpcResult<Self> {
+        Ok(PollResult {
+            content_type: Some(encoding.content_type().to_string()),
+            data: Some(encoding.encode(value)?),
+            error: None,
+        })
+    }
+
+    /// Decode the result's data using its content type, `None` if there is no data
+    pub fn decode_data<T: DeserializeOwned>(&self) -> RpcResult<Option<T>> {
+        match &self.data {
+            Some(data) => {
+                let encoding = Encoding::from_content_type(self.content_type.as_deref())?;
+                encoding.decode(data).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_round_trip() {
+        let readings = vec![HashMap::from([
+            ("message".to_string(), "sim.temp_01: 12.34".to_string()),
+            ("status".to_string(), "SUCCESS".to_string()),
+        ])];
+
+        for encoding in [Encoding::Json, Encoding::Cbor, Encoding::Msgpack] {
+            let result = PollResult::encoded(&readings, encoding).unwrap();
+            assert_eq!(
+                result.content_type.as_deref(),
+                Some(encoding.content_type())
+            );
+            let decoded: Option<Vec<HashMap<String, String>>> = result.decode_data().unwrap();
+            assert_eq!(decoded, Some(readings.clone()));
+        }
+    }
+
+    #[test]
+    fn test_missing_content_type_is_json() {
+        let result = PollResult {
+            data: Some(br#"["12.34"]"#.to_vec()),
+            ..Default::default()
+        };
+        let decoded: Option<Vec<String>> = result.decode_data().unwrap();
+        assert_eq!(decoded, Some(vec!["12.34".to_string()]));
+    }
+}
@@ -2,3 +2,12 @@
 
 mod polling;
 pub use polling::*;
+
+pub mod config;
+pub mod control;
+pub mod encoding;
+pub mod reading;
+#[cfg(feature = "provider")]
+pub mod targets;
+#[cfg(feature = "otel")]
+pub mod trace;
@@ -219,6 +219,12 @@ pub fn decode_add_poll_target_response(
 /// return an error.
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct PollRequest {
+    /// Providers which keep the last value read from each target can answer with
+    /// it instead of polling the target, as long as it was read at most this many
+    /// milliseconds ago.
+    #[serde(rename = "maxAge")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u64>,
     /// Bytes which can be serialised from any type of data structure, depending
     /// on the implementation of the provider. For an HTTP based provider it could
     /// be a hash map of API endpoints and parameters, for a NATS based provider
@@ -241,7 +247,13 @@ pub fn encode_poll_request<W: wasmbus_rpc::cbor::Write>(
 where
     <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
 {
-    e.map(1)?;
+    e.map(2)?;
+    if let Some(val) = val.max_age.as_ref() {
+        e.str("maxAge")?;
+        e.u64(*val)?;
+    } else {
+        e.null()?;
+    }
     if let Some(val) = val.request_data.as_ref() {
         e.str("request_data")?;
         e.bytes(val)?;
@@ -257,6 +269,7 @@ pub fn decode_poll_request(
     d: &mut wasmbus_rpc::cbor::Decoder<'_>,
 ) -> Result<PollRequest, RpcError> {
     let __result = {
+        let mut max_age: Option<Option<u64>> = Some(None);
         let mut request_data: Option<Option<Vec<u8>>> = Some(None);
 
         let is_array = match d.datatype()? {
@@ -273,6 +286,14 @@ pub fn decode_poll_request(
             for __i in 0..(len as usize) {
                 match __i {
                     0 => {
+                        max_age = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u64()?))
+                        }
+                    }
+                    1 => {
                         request_data = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
@@ -288,6 +309,14 @@ pub fn decode_poll_request(
             let len = d.fixed_map()?;
             for __i in 0..(len as usize) {
                 match d.str()? {
+                    "maxAge" => {
+                        max_age = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u64()?))
+                        }
+                    }
                     "request_data" => {
                         request_data = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
@@ -301,6 +330,7 @@ pub fn decode_poll_request(
             }
         }
         PollRequest {
+            max_age: max_age.unwrap(),
             request_data: request_data.unwrap(),
         }
     };
@@ -309,6 +339,11 @@ pub fn decode_poll_request(
 /// Results from either automatic or manual polling of external services/hardware
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct PollResult {
+    /// Media type of `data`, one of `application/json`, `application/cbor` or
+    /// `application/msgpack`. If this field isn't present then `data` is JSON.
+    #[serde(rename = "contentType")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
     /// Bytes serialised from a data structure suitable for the type of services
     /// being polled.
     #[serde(with = "serde_bytes")]
@@ -329,7 +364,13 @@ pub fn encode_poll_result<W: wasmbus_rpc::cbor::Write>(
 where
     <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
 {
-    e.map(2)?;
+    e.map(3)?;
+    if let Some(val) = val.content_type.as_ref() {
+        e.str("contentType")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
     if let Some(val) = val.data.as_ref() {
         e.str("data")?;
         e.bytes(val)?;
@@ -349,6 +390,7 @@ where
 #[doc(hidden)]
 pub fn decode_poll_result(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<PollResult, RpcError> {
     let __result = {
+        let mut content_type: Option<Option<String>> = Some(None);
         let mut data: Option<Option<Vec<u8>>> = Some(None);
         let mut error: Option<Option<PollingError>> = Some(None);
 
@@ -366,6 +408,14 @@ pub fn decode_poll_result(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<Poll
             for __i in 0..(len as usize) {
                 match __i {
                     0 => {
+                        content_type = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    1 => {
                         data = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
@@ -373,7 +423,7 @@ pub fn decode_poll_result(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<Poll
                             Some(Some(d.bytes()?.to_vec()))
                         }
                     }
-                    1 => {
+                    2 => {
                         error = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
                             Some(None)
@@ -394,6 +444,14 @@ pub fn decode_poll_result(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<Poll
             let len = d.fixed_map()?;
             for __i in 0..(len as usize) {
                 match d.str()? {
+                    "contentType" => {
+                        content_type = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
                     "data" => {
                         data = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
                             d.skip()?;
@@ -420,6 +478,7 @@ pub fn decode_poll_result(d: &mut wasmbus_rpc::cbor::Decoder<'_>) -> Result<Poll
             }
         }
         PollResult {
+            content_type: content_type.unwrap(),
             data: data.unwrap(),
             error: error.unwrap(),
         }
@@ -685,6 +744,629 @@ pub fn decode_remove_poll_target_response(
     };
     Ok(__result)
 }
+/// One value read from, or event about, a sensor. Providers send a list of
+/// these, as `SensorReadings`, in the `data` of each `PollResult` so actors
+/// don't need to know which provider or protocol the values came from.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SensorReading {
+    /// The calibration applied to `rawValue` to get `value`, as JSON, if the sensor is
+    /// calibrated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<String>,
+    /// Name of the channel the value was read from, for sensors with several. Composite
+    /// readings of every channel at once list them all, separated by commas, and their value
+    /// is a JSON object of each channel's value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// GPS coordinates of the sensor as `latitude,longitude` or `latitude,longitude,altitude`,
+    /// if its location gives them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gps: Option<String>,
+    /// The value before a change, for readings with a `CONFIG_CHANGE` status
+    #[serde(rename = "previousValue")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_value: Option<String>,
+    /// How far the value can be trusted, `GOOD`, `UNCERTAIN` or `BAD` on their own or
+    /// with a reason, e.g. `UNCERTAIN_LAST_KNOWN_VALUE` or `BAD_COMM_FAILURE`
+    #[serde(default)]
+    pub quality: String,
+    /// The value as the sensor reported it, before `calibration` was applied
+    #[serde(rename = "rawValue")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_value: Option<String>,
+    /// ID of the sensor or target the reading is for
+    #[serde(rename = "sensorId")]
+    #[serde(default)]
+    pub sensor_id: String,
+    /// Where the reading came from, as `{location}.{alias}`. Locations in the ISA-95
+    /// hierarchy are given as a path from the enterprise down, e.g.
+    /// `acme/plant_1/packaging/line_3`
+    #[serde(default)]
+    pub source: String,
+    /// When the sensor or device itself took the reading, if it says, in
+    /// milliseconds since the unix epoch
+    #[serde(rename = "sourceTimestamp")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_timestamp: Option<u64>,
+    /// Outcome of the read, e.g. `SUCCESS`, `COMM_ERROR` or `VALUE_ERROR`, or
+    /// `CONFIG_CHANGE`/`OFFLINE` for events which aren't readings
+    #[serde(default)]
+    pub status: String,
+    /// When the provider read or received the value, in milliseconds since the
+    /// unix epoch
+    #[serde(default)]
+    pub timestamp: u64,
+    /// W3C `traceparent` of the read, so actors can continue each reading's trace when they
+    /// pass it on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+    /// Unit of `value` as a UCUM code, e.g. `Cel`, if the sensor declares one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// The value read, not present if nothing could be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+// Encode SensorReading as CBOR and append to output stream
+#[doc(hidden)]
+#[allow(unused_mut)]
+pub fn encode_sensor_reading<W: wasmbus_rpc::cbor::Write>(
+    mut e: &mut wasmbus_rpc::cbor::Encoder<W>,
+    val: &SensorReading,
+) -> RpcResult<()>
+where
+    <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
+{
+    e.map(14)?;
+    if let Some(val) = val.calibration.as_ref() {
+        e.str("calibration")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.channel.as_ref() {
+        e.str("channel")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.gps.as_ref() {
+        e.str("gps")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.previous_value.as_ref() {
+        e.str("previousValue")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    e.str("quality")?;
+    e.str(&val.quality)?;
+    if let Some(val) = val.raw_value.as_ref() {
+        e.str("rawValue")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    e.str("sensorId")?;
+    e.str(&val.sensor_id)?;
+    e.str("source")?;
+    e.str(&val.source)?;
+    if let Some(val) = val.source_timestamp.as_ref() {
+        e.str("sourceTimestamp")?;
+        e.u64(*val)?;
+    } else {
+        e.null()?;
+    }
+    e.str("status")?;
+    e.str(&val.status)?;
+    e.str("timestamp")?;
+    e.u64(val.timestamp)?;
+    if let Some(val) = val.traceparent.as_ref() {
+        e.str("traceparent")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.unit.as_ref() {
+        e.str("unit")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    if let Some(val) = val.value.as_ref() {
+        e.str("value")?;
+        e.str(val)?;
+    } else {
+        e.null()?;
+    }
+    Ok(())
+}
+
+// Decode SensorReading from cbor input stream
+#[doc(hidden)]
+pub fn decode_sensor_reading(
+    d: &mut wasmbus_rpc::cbor::Decoder<'_>,
+) -> Result<SensorReading, RpcError> {
+    let __result = {
+        let mut calibration: Option<Option<String>> = Some(None);
+        let mut channel: Option<Option<String>> = Some(None);
+        let mut gps: Option<Option<String>> = Some(None);
+        let mut previous_value: Option<Option<String>> = Some(None);
+        let mut quality: Option<String> = None;
+        let mut raw_value: Option<Option<String>> = Some(None);
+        let mut sensor_id: Option<String> = None;
+        let mut source: Option<String> = None;
+        let mut source_timestamp: Option<Option<u64>> = Some(None);
+        let mut status: Option<String> = None;
+        let mut timestamp: Option<u64> = None;
+        let mut traceparent: Option<Option<String>> = Some(None);
+        let mut unit: Option<Option<String>> = Some(None);
+        let mut value: Option<Option<String>> = Some(None);
+
+        let is_array = match d.datatype()? {
+            wasmbus_rpc::cbor::Type::Array => true,
+            wasmbus_rpc::cbor::Type::Map => false,
+            _ => {
+                return Err(RpcError::Deser(
+                    "decoding struct SensorReading, expected array or map".to_string(),
+                ))
+            }
+        };
+        if is_array {
+            let len = d.fixed_array()?;
+            for __i in 0..(len as usize) {
+                match __i {
+                    0 => {
+                        calibration = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    1 => {
+                        channel = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    2 => {
+                        gps = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    3 => {
+                        previous_value = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    4 => quality = Some(d.str()?.to_string()),
+                    5 => {
+                        raw_value = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    6 => sensor_id = Some(d.str()?.to_string()),
+                    7 => source = Some(d.str()?.to_string()),
+                    8 => {
+                        source_timestamp = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u64()?))
+                        }
+                    }
+                    9 => status = Some(d.str()?.to_string()),
+                    10 => timestamp = Some(d.u64()?),
+                    11 => {
+                        traceparent = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    12 => {
+                        unit = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    13 => {
+                        value = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+
+                    _ => d.skip()?,
+                }
+            }
+        } else {
+            let len = d.fixed_map()?;
+            for __i in 0..(len as usize) {
+                match d.str()? {
+                    "calibration" => {
+                        calibration = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "channel" => {
+                        channel = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "previousValue" => {
+                        previous_value = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "gps" => {
+                        gps = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "quality" => quality = Some(d.str()?.to_string()),
+                    "rawValue" => {
+                        raw_value = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "sensorId" => sensor_id = Some(d.str()?.to_string()),
+                    "source" => source = Some(d.str()?.to_string()),
+                    "sourceTimestamp" => {
+                        source_timestamp = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u64()?))
+                        }
+                    }
+                    "status" => status = Some(d.str()?.to_string()),
+                    "timestamp" => timestamp = Some(d.u64()?),
+                    "traceparent" => {
+                        traceparent = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "unit" => {
+                        unit = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    "value" => {
+                        value = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.str()?.to_string()))
+                        }
+                    }
+                    _ => d.skip()?,
+                }
+            }
+        }
+        SensorReading {
+            calibration: calibration.unwrap(),
+            channel: channel.unwrap(),
+            gps: gps.unwrap(),
+            previous_value: previous_value.unwrap(),
+
+            quality: if let Some(__x) = quality {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field SensorReading.quality (#4)".to_string(),
+                ));
+            },
+
+            raw_value: raw_value.unwrap(),
+
+            sensor_id: if let Some(__x) = sensor_id {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field SensorReading.sensor_id (#6)".to_string(),
+                ));
+            },
+
+            source: if let Some(__x) = source {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field SensorReading.source (#7)".to_string(),
+                ));
+            },
+
+            source_timestamp: source_timestamp.unwrap(),
+
+            status: if let Some(__x) = status {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field SensorReading.status (#9)".to_string(),
+                ));
+            },
+
+            timestamp: if let Some(__x) = timestamp {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field SensorReading.timestamp (#10)".to_string(),
+                ));
+            },
+            traceparent: traceparent.unwrap(),
+            unit: unit.unwrap(),
+
+            value: value.unwrap(),
+        }
+    };
+    Ok(__result)
+}
+pub type SensorReadings = Vec<SensorReading>;
+
+// Encode SensorReadings as CBOR and append to output stream
+#[doc(hidden)]
+#[allow(unused_mut)]
+pub fn encode_sensor_readings<W: wasmbus_rpc::cbor::Write>(
+    mut e: &mut wasmbus_rpc::cbor::Encoder<W>,
+    val: &SensorReadings,
+) -> RpcResult<()>
+where
+    <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
+{
+    e.array(val.len() as u64)?;
+    for item in val.iter() {
+        encode_sensor_reading(e, item)?;
+    }
+    Ok(())
+}
+
+// Decode SensorReadings from cbor input stream
+#[doc(hidden)]
+pub fn decode_sensor_readings(
+    d: &mut wasmbus_rpc::cbor::Decoder<'_>,
+) -> Result<SensorReadings, RpcError> {
+    let __result = {
+        if let Some(n) = d.array()? {
+            let mut arr: Vec<SensorReading> = Vec::with_capacity(n as usize);
+            for _ in 0..(n as usize) {
+                arr.push(decode_sensor_reading(d).map_err(|e| {
+                    format!(
+                        "decoding 'org.wasmcloud.interface.polling#SensorReading': {}",
+                        e
+                    )
+                })?)
+            }
+            arr
+        } else {
+            // indefinite array
+            let mut arr: Vec<SensorReading> = Vec::new();
+            loop {
+                match d.datatype() {
+                    Err(_) => break,
+                    Ok(wasmbus_rpc::cbor::Type::Break) => break,
+                    Ok(_) => arr.push(decode_sensor_reading(d).map_err(|e| {
+                        format!(
+                            "decoding 'org.wasmcloud.interface.polling#SensorReading': {}",
+                            e
+                        )
+                    })?),
+                }
+            }
+            arr
+        }
+    };
+    Ok(__result)
+}
+/// Everything the provider knows about a target, sent to actors when the target
+/// is discovered, changes or is lost
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TargetDescriptor {
+    #[serde(default)]
+    pub alias: String,
+    /// The provider's full description of the target as JSON, e.g. a sensor's
+    /// heartbeat
+    #[serde(default)]
+    pub details: String,
+    /// Where the target is, as a path down the ISA-95 hierarchy from the enterprise,
+    /// e.g. `acme/plant_1/packaging/line_3`, or a free-form name
+    #[serde(default)]
+    pub location: String,
+    /// How often the target is polled, or expected to push readings, in
+    /// milliseconds
+    #[serde(rename = "pollInterval")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_interval: Option<u64>,
+    /// ID of the sensor or target
+    #[serde(rename = "targetId")]
+    #[serde(default)]
+    pub target_id: String,
+    /// When the target was discovered, changed or lost, in milliseconds since
+    /// the unix epoch
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+// Encode TargetDescriptor as CBOR and append to output stream
+#[doc(hidden)]
+#[allow(unused_mut)]
+pub fn encode_target_descriptor<W: wasmbus_rpc::cbor::Write>(
+    mut e: &mut wasmbus_rpc::cbor::Encoder<W>,
+    val: &TargetDescriptor,
+) -> RpcResult<()>
+where
+    <W as wasmbus_rpc::cbor::Write>::Error: std::fmt::Display,
+{
+    e.map(6)?;
+    e.str("alias")?;
+    e.str(&val.alias)?;
+    e.str("details")?;
+    e.str(&val.details)?;
+    e.str("location")?;
+    e.str(&val.location)?;
+    if let Some(val) = val.poll_interval.as_ref() {
+        e.str("pollInterval")?;
+        e.u64(*val)?;
+    } else {
+        e.null()?;
+    }
+    e.str("targetId")?;
+    e.str(&val.target_id)?;
+    e.str("timestamp")?;
+    e.u64(val.timestamp)?;
+    Ok(())
+}
+
+// Decode TargetDescriptor from cbor input stream
+#[doc(hidden)]
+pub fn decode_target_descriptor(
+    d: &mut wasmbus_rpc::cbor::Decoder<'_>,
+) -> Result<TargetDescriptor, RpcError> {
+    let __result = {
+        let mut alias: Option<String> = None;
+        let mut details: Option<String> = None;
+        let mut location: Option<String> = None;
+        let mut poll_interval: Option<Option<u64>> = Some(None);
+        let mut target_id: Option<String> = None;
+        let mut timestamp: Option<u64> = None;
+
+        let is_array = match d.datatype()? {
+            wasmbus_rpc::cbor::Type::Array => true,
+            wasmbus_rpc::cbor::Type::Map => false,
+            _ => {
+                return Err(RpcError::Deser(
+                    "decoding struct TargetDescriptor, expected array or map".to_string(),
+                ))
+            }
+        };
+        if is_array {
+            let len = d.fixed_array()?;
+            for __i in 0..(len as usize) {
+                match __i {
+                    0 => alias = Some(d.str()?.to_string()),
+                    1 => details = Some(d.str()?.to_string()),
+                    2 => location = Some(d.str()?.to_string()),
+                    3 => {
+                        poll_interval = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u64()?))
+                        }
+                    }
+                    4 => target_id = Some(d.str()?.to_string()),
+                    5 => timestamp = Some(d.u64()?),
+                    _ => d.skip()?,
+                }
+            }
+        } else {
+            let len = d.fixed_map()?;
+            for __i in 0..(len as usize) {
+                match d.str()? {
+                    "alias" => alias = Some(d.str()?.to_string()),
+                    "details" => details = Some(d.str()?.to_string()),
+                    "location" => location = Some(d.str()?.to_string()),
+                    "pollInterval" => {
+                        poll_interval = if wasmbus_rpc::cbor::Type::Null == d.datatype()? {
+                            d.skip()?;
+                            Some(None)
+                        } else {
+                            Some(Some(d.u64()?))
+                        }
+                    }
+                    "targetId" => target_id = Some(d.str()?.to_string()),
+                    "timestamp" => timestamp = Some(d.u64()?),
+                    _ => d.skip()?,
+                }
+            }
+        }
+        TargetDescriptor {
+            alias: if let Some(__x) = alias {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field TargetDescriptor.alias (#0)".to_string(),
+                ));
+            },
+
+            details: if let Some(__x) = details {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field TargetDescriptor.details (#1)".to_string(),
+                ));
+            },
+
+            location: if let Some(__x) = location {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field TargetDescriptor.location (#2)".to_string(),
+                ));
+            },
+
+            poll_interval: poll_interval.unwrap(),
+
+            target_id: if let Some(__x) = target_id {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field TargetDescriptor.target_id (#4)".to_string(),
+                ));
+            },
+
+            timestamp: if let Some(__x) = timestamp {
+                __x
+            } else {
+                return Err(RpcError::Deser(
+                    "missing field TargetDescriptor.timestamp (#5)".to_string(),
+                ));
+            },
+        }
+    };
+    Ok(__result)
+}
 /// The PollSubscriber interface described an actor interface that receives the
 /// results from automatic polling of external services/hardware at a specified
 /// interval.
@@ -699,6 +1381,14 @@ pub trait PollSubscriber {
     /// Receive results from the latest automatic poll, or simply perform some
     /// functionality without any external input
     async fn poll_rx(&self, ctx: &Context, arg: &PollResult) -> RpcResult<()>;
+    /// A target the provider found by itself, e.g. from a heartbeat, has been
+    /// registered and will be polled
+    async fn target_discovered(&self, ctx: &Context, arg: &TargetDescriptor) -> RpcResult<()>;
+    /// A registered target's descriptor has changed, e.g. it's been reconfigured
+    async fn target_changed(&self, ctx: &Context, arg: &TargetDescriptor) -> RpcResult<()>;
+    /// A target has been evicted and will no longer be polled, e.g. because its
+    /// device went offline
+    async fn target_lost(&self, ctx: &Context, arg: &TargetDescriptor) -> RpcResult<()>;
 }
 
 /// PollSubscriberReceiver receives messages defined in the PollSubscriber service trait
@@ -718,6 +1408,30 @@ pub trait PollSubscriberReceiver: MessageDispatch + PollSubscriber {
                 let buf = Vec::new();
                 Ok(buf)
             }
+            "TargetDiscovered" => {
+                let value: TargetDescriptor = wasmbus_rpc::common::deserialize(&message.arg)
+                    .map_err(|e| RpcError::Deser(format!("'TargetDescriptor': {}", e)))?;
+
+                let _resp = PollSubscriber::target_discovered(self, ctx, &value).await?;
+                let buf = Vec::new();
+                Ok(buf)
+            }
+            "TargetChanged" => {
+                let value: TargetDescriptor = wasmbus_rpc::common::deserialize(&message.arg)
+                    .map_err(|e| RpcError::Deser(format!("'TargetDescriptor': {}", e)))?;
+
+                let _resp = PollSubscriber::target_changed(self, ctx, &value).await?;
+                let buf = Vec::new();
+                Ok(buf)
+            }
+            "TargetLost" => {
+                let value: TargetDescriptor = wasmbus_rpc::common::deserialize(&message.arg)
+                    .map_err(|e| RpcError::Deser(format!("'TargetDescriptor': {}", e)))?;
+
+                let _resp = PollSubscriber::target_lost(self, ctx, &value).await?;
+                let buf = Vec::new();
+                Ok(buf)
+            }
             _ => Err(RpcError::MethodNotHandled(format!(
                 "PollSubscriber::{}",
                 message.method
@@ -790,6 +1504,62 @@ impl<T: Transport + std::marker::Sync + std::marker::Send> PollSubscriber
             .await?;
         Ok(())
     }
+    #[allow(unused)]
+    /// A target the provider found by itself, e.g. from a heartbeat, has been
+    /// registered and will be polled
+    async fn target_discovered(&self, ctx: &Context, arg: &TargetDescriptor) -> RpcResult<()> {
+        let buf = wasmbus_rpc::common::serialize(arg)?;
+
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "PollSubscriber.TargetDiscovered",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+    #[allow(unused)]
+    /// A registered target's descriptor has changed, e.g. it's been reconfigured
+    async fn target_changed(&self, ctx: &Context, arg: &TargetDescriptor) -> RpcResult<()> {
+        let buf = wasmbus_rpc::common::serialize(arg)?;
+
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "PollSubscriber.TargetChanged",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+    #[allow(unused)]
+    /// A target has been evicted and will no longer be polled, e.g. because its
+    /// device went offline
+    async fn target_lost(&self, ctx: &Context, arg: &TargetDescriptor) -> RpcResult<()> {
+        let buf = wasmbus_rpc::common::serialize(arg)?;
+
+        let resp = self
+            .transport
+            .send(
+                ctx,
+                Message {
+                    method: "PollSubscriber.TargetLost",
+                    arg: Cow::Borrowed(&buf),
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
 }
 
 /// The Polling interface describes a service that automatically polls external
@@ -0,0 +1,170 @@
+//! Helpers for building [`SensorReading`]s, so every provider derives their quality from their
+//! status in the same way.
+//!
+//! Qualities follow the Good/Uncertain/Bad semantics used by OPC and most historians. A quality
+//! is either one of those three on its own, or one of them with a reason, e.g.
+//! `UNCERTAIN_LAST_KNOWN_VALUE`, so anything which only cares how far a value can be trusted can
+//! use [`quality_class`].
+
+use std::time::UNIX_EPOCH;
+
+use crate::SensorReading;
+
+pub const QUALITY_GOOD: &str = "GOOD";
+pub const QUALITY_UNCERTAIN: &str = "UNCERTAIN";
+pub const QUALITY_BAD: &str = "BAD";
+
+/// The value is good, but was clamped to a limit of what the sensor can measure
+pub const QUALITY_GOOD_CLAMPED: &str = "GOOD_CLAMPED";
+/// No new value could be read, so this is the last one that was
+pub const QUALITY_UNCERTAIN_LAST_KNOWN_VALUE: &str = "UNCERTAIN_LAST_KNOWN_VALUE";
+/// The sensor is due for calibration, or its calibration is known to be off
+pub const QUALITY_UNCERTAIN_OUT_OF_CALIBRATION: &str = "UNCERTAIN_OUT_OF_CALIBRATION";
+/// The sensor has failed, e.g. it reported a value it can't have read
+pub const QUALITY_BAD_SENSOR_FAILURE: &str = "BAD_SENSOR_FAILURE";
+/// The device responded, but not with a reading
+pub const QUALITY_BAD_DEVICE_FAILURE: &str = "BAD_DEVICE_FAILURE";
+/// The device couldn't be reached
+pub const QUALITY_BAD_COMM_FAILURE: &str = "BAD_COMM_FAILURE";
+/// The device isn't connected
+pub const QUALITY_BAD_NOT_CONNECTED: &str = "BAD_NOT_CONNECTED";
+
+/// Every quality a reading can have
+pub const QUALITIES: [&str; 10] = [
+    QUALITY_GOOD,
+    QUALITY_GOOD_CLAMPED,
+    QUALITY_UNCERTAIN,
+    QUALITY_UNCERTAIN_LAST_KNOWN_VALUE,
+    QUALITY_UNCERTAIN_OUT_OF_CALIBRATION,
+    QUALITY_BAD,
+    QUALITY_BAD_SENSOR_FAILURE,
+    QUALITY_BAD_DEVICE_FAILURE,
+    QUALITY_BAD_COMM_FAILURE,
+    QUALITY_BAD_NOT_CONNECTED,
+];
+
+/// Status of a reading recording a change to a sensor's settings, with the settings before and
+/// after the change as its `previous_value` and `value`
+pub const STATUS_CONFIG_CHANGE: &str = "CONFIG_CHANGE";
+/// Status of a reading recording that a sensor has gone offline
+pub const STATUS_OFFLINE: &str = "OFFLINE";
+/// Status of a reading recording that a sensor has failed too many reads in a row, so its
+/// failures won't be reported again until it's restored. The value describes the failures.
+pub const STATUS_QUARANTINED: &str = "QUARANTINED";
+/// Status of a reading recording that a quarantined sensor has been read successfully again
+pub const STATUS_RESTORED: &str = "RESTORED";
+
+/// Quality of a reading with the given status
+pub fn quality(status: &str) -> &'static str {
+    match status {
+        "SUCCESS" | STATUS_CONFIG_CHANGE | STATUS_RESTORED => QUALITY_GOOD,
+        "UNCERTAIN" | "RATE_LIMITED" => QUALITY_UNCERTAIN,
+        "STALE_DATA" => QUALITY_UNCERTAIN_LAST_KNOWN_VALUE,
+        "COMM_ERROR" | "SUBSCRIBE_ERROR" | "PUBLISH_ERROR" | "TIMEOUT" => QUALITY_BAD_COMM_FAILURE,
+        "NO_RESPONDERS" | STATUS_OFFLINE => QUALITY_BAD_NOT_CONNECTED,
+        "VALUE_ERROR" | "MALFORMED_PAYLOAD" => QUALITY_BAD_DEVICE_FAILURE,
+        "OUT_OF_RANGE" => QUALITY_BAD_SENSOR_FAILURE,
+        _ => QUALITY_BAD,
+    }
+}
+
+/// Whether a reading's quality is good, uncertain or bad, without its reason. Anything which
+/// isn't a known quality is bad.
+pub fn quality_class(quality: &str) -> &'static str {
+    match quality.split('_').next() {
+        Some(QUALITY_GOOD) if QUALITIES.contains(&quality) => QUALITY_GOOD,
+        Some(QUALITY_UNCERTAIN) if QUALITIES.contains(&quality) => QUALITY_UNCERTAIN,
+        _ => QUALITY_BAD,
+    }
+}
+
+/// Current unix timestamp in milliseconds, which readings are timestamped with. Only providers
+/// can use this, actors don't have a clock.
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("If time has gone backwards then we have bigger problems than this error.")
+        .as_millis() as u64
+}
+
+/// Something a provider reads, e.g. a device, an endpoint or a node, which its readings are
+/// attributed to in the same way by every provider
+pub trait ReadingTarget {
+    /// ID of the target, which its readings are for
+    fn target_id(&self) -> String;
+    fn location(&self) -> &str;
+    fn alias(&self) -> &str;
+
+    /// Where the target's readings come from, as `{location}.{alias}`
+    fn source(&self) -> String {
+        format!("{}.{}", self.location(), self.alias())
+    }
+
+    /// A reading of the target, with its quality derived from `status`
+    fn reading(&self, status: &str, value: Option<String>, timestamp: u64) -> SensorReading {
+        SensorReading::new(self.target_id(), self.source(), status, value, timestamp)
+    }
+}
+
+impl SensorReading {
+    /// A reading from the sensor, with its quality derived from `status`
+    pub fn new(
+        sensor_id: impl ToString,
+        source: String,
+        status: &str,
+        value: Option<String>,
+        timestamp: u64,
+    ) -> Self {
+        SensorReading {
+            sensor_id: sensor_id.to_string(),
+            source,
+            value,
+            timestamp,
+            quality: quality(status).to_string(),
+            status: status.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Encoding;
+    use crate::PollResult;
+
+    #[test]
+    fn test_quality_class() {
+        assert_eq!(quality_class(QUALITY_GOOD_CLAMPED), QUALITY_GOOD);
+        assert_eq!(quality_class(QUALITY_UNCERTAIN), QUALITY_UNCERTAIN);
+        assert_eq!(quality_class(quality("STALE_DATA")), QUALITY_UNCERTAIN);
+        assert_eq!(quality_class(quality("TIMEOUT")), QUALITY_BAD);
+        assert_eq!(quality_class("GOOD_ENOUGH"), QUALITY_BAD);
+    }
+
+    #[test]
+    fn test_readings_round_trip() {
+        let readings = vec![
+            SensorReading::new(
+                "sensor-1",
+                "sim.temp_01".to_string(),
+                "SUCCESS",
+                Some("12.34".to_string()),
+                1_700_000_000_000,
+            ),
+            SensorReading {
+                source_timestamp: Some(1_699_999_999_000),
+                unit: Some("Cel".to_string()),
+                ..SensorReading::new("sensor-2", "sim.temp_02".to_string(), "COMM_ERROR", None, 0)
+            },
+        ];
+        assert_eq!(readings[0].quality, QUALITY_GOOD);
+        assert_eq!(readings[1].quality, QUALITY_BAD_COMM_FAILURE);
+
+        for encoding in [Encoding::Json, Encoding::Cbor, Encoding::Msgpack] {
+            let result = PollResult::encoded(&readings, encoding).unwrap();
+            let decoded: Option<Vec<SensorReading>> = result.decode_data().unwrap();
+            assert_eq!(decoded, Some(readings.clone()));
+        }
+    }
+}
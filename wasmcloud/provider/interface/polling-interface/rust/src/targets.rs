@@ -0,0 +1,377 @@
+//! Link handling and scheduling shared by the providers whose targets are added and removed by
+//! the actor with `AddPollTarget` and `RemovePollTarget`.
+//!
+//! Each linked actor has a [`LinkedActor`] holding its config and targets. A target is polled on
+//! its own task, started with [`poll_every`], until it's removed or the link is deleted.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info_span, instrument, warn, Instrument};
+use uuid::Uuid;
+use wasmbus_rpc::common::Context;
+use wasmbus_rpc::core::LinkDefinition;
+use wasmbus_rpc::error::{RpcError, RpcResult};
+
+use crate::config::{self, PollingSettings};
+use crate::encoding::Encoding;
+use crate::trace;
+use crate::{
+    PollRequest, PollResult, PollSubscriber, PollSubscriberSender, PollingError, SensorReading,
+};
+
+/// A provider's config, from its host data or a link's values
+pub trait ProviderConfig: Clone + Default + DeserializeOwned + Send + Sync + 'static {
+    fn polling(&self) -> &PollingSettings;
+
+    /// Settings from `extra` override these ones, unless they aren't set
+    fn merge(&self, extra: &Self) -> Self;
+
+    /// Name of a setting which differs in `new` but is only used when the link is first put
+    fn unchangeable_setting(&self, _new: &Self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Config shared with an actor's polling tasks, so the link can be reconfigured without
+/// restarting them
+pub type SharedConfig<C> = Arc<RwLock<C>>;
+
+/// Every linked actor, by actor ID
+pub type Actors<C, T, S = ()> = Arc<RwLock<HashMap<String, LinkedActor<C, T, S>>>>;
+
+/// Sent as the `targetData` of a `RemovePollTarget` request
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TargetId {
+    pub id: Uuid,
+}
+
+/// A target which is being polled, its polling task is aborted when this is dropped
+pub struct PolledTarget<T> {
+    pub target: T,
+    handle: JoinHandle<()>,
+}
+
+impl<T> PolledTarget<T> {
+    pub fn new(target: T, handle: JoinHandle<()>) -> Self {
+        Self { target, handle }
+    }
+}
+
+impl<T> Drop for PolledTarget<T> {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// An actor's link, along with the targets it has added and anything else the provider keeps
+/// for it in `state`, e.g. a client
+pub struct LinkedActor<C, T, S = ()> {
+    pub targets: HashMap<Uuid, PolledTarget<T>>,
+    pub config: SharedConfig<C>,
+    pub ld: LinkDefinition,
+    pub state: S,
+}
+
+impl<C: ProviderConfig, T, S> LinkedActor<C, T, S> {
+    pub fn new(ld: &LinkDefinition, config: C, state: S) -> Self {
+        Self {
+            targets: HashMap::new(),
+            config: Arc::new(RwLock::new(config)),
+            ld: ld.clone(),
+            state,
+        }
+    }
+
+    /// The targets with the IDs given as a JSON array in the request's `request_data`, or every
+    /// target if there isn't one. Fails with the result to send back if an ID isn't a target.
+    pub fn requested(&self, arg: &PollRequest) -> Result<Vec<&T>, PollResult> {
+        let ids = match arg.request_data.as_deref() {
+            Some(data) if !data.is_empty() => serde_json::from_slice::<Vec<Uuid>>(data)
+                .map_err(|e| failed_poll("INVALID_REQUEST", e.to_string()))?,
+            _ => self.targets.keys().copied().collect(),
+        };
+        ids.into_iter()
+            .map(|id| match self.targets.get(&id) {
+                Some(polled) => Ok(&polled.target),
+                None => Err(failed_poll("UNKNOWN_TARGET", id.to_string())),
+            })
+            .collect()
+    }
+
+    /// The interval a target should be polled at, the link's default if the request doesn't
+    /// give one
+    pub async fn poll_interval(&self, requested: Option<u32>) -> Result<Duration, PollingError> {
+        let poll_interval = match requested {
+            Some(poll_interval) => poll_interval,
+            None => self.config.read().await.polling().poll_interval(),
+        };
+        if poll_interval == 0 {
+            return Err(polling_error(
+                "INVALID_TARGET",
+                "poll interval must be greater than 0".to_string(),
+            ));
+        }
+        Ok(Duration::from_millis(poll_interval as u64))
+    }
+
+    /// Stop polling the target with the ID given as JSON in a `RemovePollTarget` request's
+    /// `target_data`, returning it
+    pub fn remove(&mut self, target_data: &[u8]) -> Result<PolledTarget<T>, PollingError> {
+        let target_id = serde_json::from_slice::<TargetId>(target_data)
+            .map_err(|e| polling_error("INVALID_TARGET", e.to_string()))?;
+        self.targets
+            .remove(&target_id.id)
+            .ok_or_else(|| polling_error("UNKNOWN_TARGET", target_id.id.to_string()))
+    }
+}
+
+/// The link's config over the provider's defaults, or `None` if its values are invalid
+pub fn link_config<C: ProviderConfig>(defaults: &C, ld: &LinkDefinition) -> Option<C> {
+    match config::from_link_values::<C>(&ld.values) {
+        Ok(config) => Some(defaults.merge(&config)),
+        Err(e) => {
+            error!("Failed to build configuration: {e:?}");
+            None
+        }
+    }
+}
+
+/// Swap the config of an actor which is already linked, so its running targets pick it up.
+/// Gives the config back if the actor isn't linked yet.
+pub async fn relink<C: ProviderConfig, T, S>(
+    actors: &Actors<C, T, S>,
+    ld: &LinkDefinition,
+    config: C,
+) -> Option<C> {
+    let read_actors = actors.read().await;
+    let actor = match read_actors.get(&ld.actor_id) {
+        Some(actor) => actor,
+        None => return Some(config),
+    };
+    let mut current = actor.config.write().await;
+    if let Some(setting) = current.unchangeable_setting(&config) {
+        warn!("The {setting} can't be changed on a live link, delete and put the link again to apply it");
+    }
+    *current = config;
+    None
+}
+
+/// Forget the actor's link, which stops polling its targets once it's dropped
+pub async fn unlink<C, T, S>(
+    actors: &Actors<C, T, S>,
+    actor_id: &str,
+) -> Option<LinkedActor<C, T, S>> {
+    let actor = actors.write().await.remove(actor_id)?;
+    debug!(
+        "Stopped polling {} targets for actor [{}]",
+        actor.targets.len(),
+        actor_id
+    );
+    Some(actor)
+}
+
+pub fn actor_id(ctx: &Context) -> RpcResult<&String> {
+    ctx.actor
+        .as_ref()
+        .ok_or_else(|| RpcError::InvalidParameter("no actor in request".to_string()))
+}
+
+pub fn not_linked(actor_id: &str) -> RpcError {
+    RpcError::InvalidParameter(format!("actor not linked: {actor_id}"))
+}
+
+pub fn polling_error(error_type: &str, description: String) -> PollingError {
+    PollingError {
+        error_type: error_type.to_string(),
+        description: Some(description),
+    }
+}
+
+fn failed_poll(error_type: &str, description: String) -> PollResult {
+    PollResult {
+        error: Some(polling_error(error_type, description)),
+        ..Default::default()
+    }
+}
+
+/// The target given as JSON in an `AddPollTarget` request's `target_data`, checked with
+/// `validate`
+pub fn parse_target<T: DeserializeOwned>(
+    target_data: &[u8],
+    validate: impl FnOnce(&T) -> Result<(), String>,
+) -> Result<T, PollingError> {
+    let target = serde_json::from_slice::<T>(target_data)
+        .map_err(|e| polling_error("INVALID_TARGET", e.to_string()))?;
+    validate(&target).map_err(|e| polling_error("INVALID_TARGET", e))?;
+    Ok(target)
+}
+
+pub fn poll_result(readings: &[SensorReading], encoding: Encoding) -> PollResult {
+    match PollResult::encoded(&readings, encoding) {
+        Ok(poll_result) => poll_result,
+        Err(e) => failed_poll("BLOB_SER", e.to_string()),
+    }
+}
+
+pub async fn send_readings(readings: Vec<SensorReading>, ld: &LinkDefinition, encoding: Encoding) {
+    let poll_result = poll_result(&readings, encoding);
+    let actor = PollSubscriberSender::for_actor(ld);
+    if let Err(e) = actor.poll_rx(&trace::actor_context(), &poll_result).await {
+        error!(
+            error = %e,
+            "Unable to send subscription"
+        );
+    };
+}
+
+/// Read the target every `poll_interval` with `read`, which is given the link's current config,
+/// and send its readings to the actor, if there are any. Each poll is traced on its own.
+#[instrument(level = "info", skip_all, fields(actor_id = %ld.actor_id, target_id = %target_id))]
+pub async fn poll_every<C, F, Fut>(
+    ld: LinkDefinition,
+    target_id: Uuid,
+    config: SharedConfig<C>,
+    poll_interval: Duration,
+    mut read: F,
+) where
+    C: ProviderConfig,
+    F: FnMut(C) -> Fut,
+    Fut: Future<Output = Vec<SensorReading>>,
+{
+    let mut poll_clock = tokio::time::interval(poll_interval);
+    loop {
+        poll_clock.tick().await;
+
+        let config = config.read().await.clone();
+        let encoding = config.polling().encoding();
+        let span = info_span!(parent: None, "poll_target", target_id = %target_id);
+        async {
+            let readings = read(config).await;
+            if !readings.is_empty() {
+                send_readings(readings, &ld, encoding).await
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+    struct TestConfig {
+        #[serde(flatten)]
+        polling: PollingSettings,
+    }
+
+    impl ProviderConfig for TestConfig {
+        fn polling(&self) -> &PollingSettings {
+            &self.polling
+        }
+
+        fn merge(&self, extra: &Self) -> Self {
+            TestConfig {
+                polling: self.polling.merge(&extra.polling),
+            }
+        }
+    }
+
+    fn actor(ids: &[Uuid]) -> LinkedActor<TestConfig, Uuid> {
+        let mut actor = LinkedActor::new(&LinkDefinition::default(), TestConfig::default(), ());
+        for id in ids {
+            let polled = PolledTarget::new(*id, tokio::spawn(async {}));
+            actor.targets.insert(*id, polled);
+        }
+        actor
+    }
+
+    fn poll_request(request_data: Option<&str>) -> PollRequest {
+        PollRequest {
+            request_data: request_data.map(|data| data.as_bytes().to_vec()),
+            ..Default::default()
+        }
+    }
+
+    fn error_type(result: PollResult) -> String {
+        result.error.unwrap().error_type
+    }
+
+    #[tokio::test]
+    async fn test_requested() {
+        let ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let actor = actor(&ids);
+
+        let mut every = actor.requested(&poll_request(None)).unwrap();
+        every.sort();
+        let mut expected: Vec<&Uuid> = ids.iter().collect();
+        expected.sort();
+        assert_eq!(every, expected);
+
+        let one = format!(r#"["{}"]"#, ids[1]);
+        assert_eq!(
+            actor.requested(&poll_request(Some(&one))).unwrap(),
+            vec![&ids[1]]
+        );
+
+        let unknown = format!(r#"["{}"]"#, Uuid::new_v4());
+        let result = actor.requested(&poll_request(Some(&unknown))).unwrap_err();
+        assert_eq!(error_type(result), "UNKNOWN_TARGET");
+        let result = actor.requested(&poll_request(Some("["))).unwrap_err();
+        assert_eq!(error_type(result), "INVALID_REQUEST");
+    }
+
+    #[tokio::test]
+    async fn test_poll_interval() {
+        let actor = actor(&[]);
+        assert_eq!(
+            actor.poll_interval(Some(500)).await,
+            Ok(Duration::from_millis(500))
+        );
+        assert_eq!(
+            actor.poll_interval(None).await,
+            Ok(Duration::from_millis(
+                PollingSettings::default().poll_interval() as u64
+            ))
+        );
+        let error = actor.poll_interval(Some(0)).await.unwrap_err();
+        assert_eq!(error.error_type, "INVALID_TARGET");
+    }
+
+    #[tokio::test]
+    async fn test_remove() {
+        let id = Uuid::new_v4();
+        let mut actor = actor(&[id]);
+        let target_data = format!(r#"{{"id": "{id}"}}"#);
+
+        assert_eq!(actor.remove(target_data.as_bytes()).unwrap().target, id);
+        let error = actor.remove(target_data.as_bytes()).err().unwrap();
+        assert_eq!(error.error_type, "UNKNOWN_TARGET");
+        let error = actor.remove(b"{}").err().unwrap();
+        assert_eq!(error.error_type, "INVALID_TARGET");
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let validate = |id: &Uuid| match id.is_nil() {
+            true => Err("nil target ID".to_string()),
+            false => Ok(()),
+        };
+        let id = Uuid::new_v4();
+        let target_data = format!(r#""{id}""#);
+        assert_eq!(parse_target(target_data.as_bytes(), validate), Ok(id));
+
+        let target_data = format!(r#""{}""#, Uuid::nil());
+        let error = parse_target(target_data.as_bytes(), validate).unwrap_err();
+        assert_eq!(error.description.as_deref(), Some("nil target ID"));
+        let error = parse_target(b"1", validate).unwrap_err();
+        assert_eq!(error.error_type, "INVALID_TARGET");
+    }
+}
@@ -0,0 +1,28 @@
+//! Helpers for providers to continue the current span's trace in the actors they send to.
+//!
+//! Traces are exported to an OTLP collector by wasmbus-rpc when a provider is started with
+//! `OTEL_TRACES_EXPORTER=otlp`, and `OTEL_EXPORTER_OTLP_ENDPOINT` pointing at the collector.
+
+use wasmbus_rpc::common::Context;
+use wasmbus_rpc::otel::OtelHeaderInjector;
+
+const TRACEPARENT: &str = "traceparent";
+
+/// W3C `traceparent` of the current span, if there is one
+pub fn traceparent() -> Option<String> {
+    OtelHeaderInjector::default_with_span()
+        .as_ref()
+        .get(TRACEPARENT)
+        .map(|value| value.to_string())
+}
+
+/// Context for sending to an actor which continues the current span's trace.
+///
+/// RPC messages already carry the trace context in their headers, this also sets `span` so
+/// actors can pass it on to anything outside the lattice (e.g. as an HTTP header).
+pub fn actor_context() -> Context {
+    Context {
+        span: traceparent(),
+        ..Default::default()
+    }
+}
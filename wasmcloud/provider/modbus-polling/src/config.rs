@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use wasmcloud_interface_polling::config::PollingSettings;
+use wasmcloud_interface_polling::targets::ProviderConfig;
+
+const DEFAULT_TIMEOUT_MS: u64 = 1_000;
+
+/// Configuration for polling Modbus devices, from the provider's config or a link's values
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModbusConfig {
+    #[serde(flatten)]
+    pub polling: PollingSettings,
+    /// How long to wait for a device to connect or respond to each request, in ms
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+impl ModbusConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS))
+    }
+}
+
+impl ProviderConfig for ModbusConfig {
+    fn polling(&self) -> &PollingSettings {
+        &self.polling
+    }
+
+    fn merge(&self, extra: &ModbusConfig) -> ModbusConfig {
+        let mut out = self.clone();
+        out.polling = self.polling.merge(&extra.polling);
+        if extra.timeout_ms.is_some() {
+            out.timeout_ms = extra.timeout_ms;
+        }
+        out
+    }
+}
@@ -0,0 +1,183 @@
+//! Implementation for wasmcloud:polling which reads register maps from Modbus TCP devices, such as
+//! PLCs and energy meters. Targets are added and removed by the actor with `AddPollTarget` and
+//! `RemovePollTarget`, and each register is sent to it as one `SensorReading`.
+mod config;
+mod modbus;
+#[cfg(test)]
+mod simulator;
+mod target;
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, instrument};
+use wasmbus_rpc::core::HostData;
+use wasmbus_rpc::{core::LinkDefinition, provider::prelude::*};
+use wasmcloud_interface_polling::targets::{self, Actors, LinkedActor, PolledTarget};
+use wasmcloud_interface_polling::{
+    AddPollTargetRequest, AddPollTargetResponse, PollRequest, PollResult, Polling, PollingReceiver,
+    RemovePollTargetRequest, RemovePollTargetResponse,
+};
+
+use crate::config::ModbusConfig;
+use crate::modbus::Device;
+use crate::target::ModbusTarget;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // handle lattice control messages and forward rpc to the provider dispatch
+    // returns when provider receives a shutdown control message
+    let host_data = load_host_data()?;
+    let provider = ModbusPollingProvider::new(host_data)?;
+
+    provider_main(provider, Some("Modbus-Polling Provider".to_string()))?;
+
+    eprintln!("Modbus-Polling provider exiting");
+    Ok(())
+}
+
+/// Implementation for wasmcloud:polling
+#[derive(Default, Clone, Provider)]
+#[services(Polling)]
+struct ModbusPollingProvider {
+    actors: Actors<ModbusConfig, Arc<Mutex<Device>>>,
+    default_config: ModbusConfig,
+}
+
+// use default implementations of provider message handlers
+impl ProviderDispatch for ModbusPollingProvider {}
+
+impl ModbusPollingProvider {
+    fn new(host_data: HostData) -> Result<Self, anyhow::Error> {
+        match &host_data.config_json {
+            Some(config) if !config.trim().is_empty() => Ok(Self {
+                default_config: serde_json::from_str(config)?,
+                ..Default::default()
+            }),
+            _ => Ok(Self::default()),
+        }
+    }
+}
+
+/// Handle provider control commands
+/// put_link (new actors link command), del_link (remove link command), and shutdown
+#[async_trait]
+impl ProviderHandler for ModbusPollingProvider {
+    /// Targets are added by the actor after the link is put, so this only needs to store the
+    /// link's config. If the link is put again its config is updated for any running targets.
+    #[instrument(level = "info", skip(self, ld), fields(actor_id = %ld.actor_id))]
+    async fn put_link(&self, ld: &LinkDefinition) -> RpcResult<bool> {
+        debug!("putting link for actors {:?}", ld);
+        let config = match targets::link_config(&self.default_config, ld) {
+            Some(config) => config,
+            None => return Ok(false),
+        };
+
+        if let Some(config) = targets::relink(&self.actors, ld, config).await {
+            self.actors
+                .write()
+                .await
+                .entry(ld.actor_id.to_string())
+                .or_insert_with(|| LinkedActor::new(ld, config, ()));
+        }
+
+        Ok(true)
+    }
+
+    /// Handle notification that a link is dropped: stop polling the actor's targets
+    #[instrument(level = "info", skip(self))]
+    async fn delete_link(&self, actor_id: &str) {
+        debug!("deleting link for actors {}", actor_id);
+        targets::unlink(&self.actors, actor_id).await;
+    }
+
+    /// Handle shutdown request with any cleanup necessary
+    async fn shutdown(&self) -> Result<(), Infallible> {
+        self.actors.write().await.clear();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Polling for ModbusPollingProvider {
+    /// Read the targets with the IDs given as a JSON array in `request_data` straight away, or
+    /// every target if there isn't one
+    async fn poll_tx(&self, ctx: &Context, arg: &PollRequest) -> RpcResult<PollResult> {
+        let actor_id = targets::actor_id(ctx)?;
+        let (devices, config) = {
+            let read_actors = self.actors.read().await;
+            let actor = read_actors
+                .get(actor_id)
+                .ok_or_else(|| targets::not_linked(actor_id))?;
+            let devices: Vec<Arc<Mutex<Device>>> = match actor.requested(arg) {
+                Ok(devices) => devices.into_iter().cloned().collect(),
+                Err(poll_result) => return Ok(poll_result),
+            };
+            let config = actor.config.read().await.clone();
+            (devices, config)
+        };
+
+        let mut readings = vec![];
+        for device in devices {
+            readings.append(&mut device.lock().await.read(config.timeout()).await);
+        }
+        Ok(targets::poll_result(&readings, config.polling.encoding()))
+    }
+
+    /// Start polling the [`ModbusTarget`] given as JSON in `target_data`, replacing any existing
+    /// target with the same ID
+    async fn add_poll_target(
+        &self,
+        ctx: &Context,
+        arg: &AddPollTargetRequest,
+    ) -> RpcResult<AddPollTargetResponse> {
+        let actor_id = targets::actor_id(ctx)?;
+        let target = match targets::parse_target(&arg.target_data, ModbusTarget::validate) {
+            Ok(target) => target,
+            Err(e) => return Ok(AddPollTargetResponse { error: Some(e) }),
+        };
+
+        let mut write_actors = self.actors.write().await;
+        let actor = write_actors
+            .get_mut(actor_id)
+            .ok_or_else(|| targets::not_linked(actor_id))?;
+        let poll_interval = match actor.poll_interval(arg.poll_interval).await {
+            Ok(poll_interval) => poll_interval,
+            Err(e) => return Ok(AddPollTargetResponse { error: Some(e) }),
+        };
+
+        let id = target.id;
+        let device = Arc::new(Mutex::new(Device::new(target)));
+        let polled = device.clone();
+        let handle = tokio::spawn(targets::poll_every(
+            actor.ld.clone(),
+            id,
+            actor.config.clone(),
+            poll_interval,
+            move |config: ModbusConfig| {
+                let device = polled.clone();
+                async move { device.lock().await.read(config.timeout()).await }
+            },
+        ));
+        // Dropping a replaced target aborts its polling task
+        actor.targets.insert(id, PolledTarget::new(device, handle));
+        debug!("Polling Modbus target {id} every {poll_interval:?}");
+
+        Ok(AddPollTargetResponse { error: None })
+    }
+
+    /// Stop polling the target with the ID given as JSON in `target_data`
+    async fn remove_poll_target(
+        &self,
+        ctx: &Context,
+        arg: &RemovePollTargetRequest,
+    ) -> RpcResult<RemovePollTargetResponse> {
+        let actor_id = targets::actor_id(ctx)?;
+        let mut write_actors = self.actors.write().await;
+        let actor = write_actors
+            .get_mut(actor_id)
+            .ok_or_else(|| targets::not_linked(actor_id))?;
+        let error = actor.remove(&arg.target_data).err();
+
+        Ok(RemovePollTargetResponse { error })
+    }
+}
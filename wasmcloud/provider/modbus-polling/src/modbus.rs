@@ -0,0 +1,229 @@
+//! Reading a target's register map from its device over Modbus TCP
+
+use std::io;
+use std::time::Duration;
+use tokio_modbus::client::{tcp, Context as ModbusContext, Reader};
+use tokio_modbus::prelude::Slave;
+use tracing::{debug, warn};
+use wasmcloud_interface_polling::reading::now;
+use wasmcloud_interface_polling::SensorReading;
+
+use crate::target::{FunctionCode, ModbusTarget, Register};
+
+/// A target along with its connection, which is kept open between polls and reopened after any
+/// error
+pub struct Device {
+    pub target: ModbusTarget,
+    ctx: Option<ModbusContext>,
+}
+
+impl Device {
+    pub fn new(target: ModbusTarget) -> Self {
+        Self { target, ctx: None }
+    }
+
+    /// Read every register in the target's map, giving one reading per register. Registers which
+    /// couldn't be read give a `COMM_ERROR` reading rather than failing the whole poll.
+    pub async fn read(&mut self, timeout: Duration) -> Vec<SensorReading> {
+        let timestamp = now();
+        let Device { target, ctx } = self;
+        let mut readings = Vec::with_capacity(target.registers.len());
+        for register in &target.registers {
+            let reading = match read_register(ctx, target, register, timeout).await {
+                Ok(Ok(value)) => {
+                    target.register_reading(register, "SUCCESS", Some(value), timestamp)
+                }
+                Ok(Err(e)) => target.register_reading(register, "VALUE_ERROR", Some(e), timestamp),
+                Err(e) => {
+                    warn!(
+                        "Error reading register {} from {}: {e}",
+                        register.name, target.alias
+                    );
+                    target.register_reading(register, "COMM_ERROR", None, timestamp)
+                }
+            };
+            readings.push(reading);
+        }
+        readings
+    }
+}
+
+/// The register's value, or a description of why it couldn't be decoded. The connection is
+/// only kept if the request succeeds.
+async fn read_register(
+    connection: &mut Option<ModbusContext>,
+    target: &ModbusTarget,
+    register: &Register,
+    timeout: Duration,
+) -> io::Result<Result<String, String>> {
+    let mut ctx = match connection.take() {
+        Some(ctx) => ctx,
+        None => with_timeout(timeout, connect(target)).await?,
+    };
+
+    let (address, count) = (register.address, register.data_type.count());
+    let value = match register.function_code {
+        FunctionCode::ReadCoils => {
+            let bits = with_timeout(timeout, ctx.read_coils(address, count)).await?;
+            decode_bits(&bits)
+        }
+        FunctionCode::ReadDiscreteInputs => {
+            let bits = with_timeout(timeout, ctx.read_discrete_inputs(address, count)).await?;
+            decode_bits(&bits)
+        }
+        FunctionCode::ReadHoldingRegisters => {
+            let words = with_timeout(timeout, ctx.read_holding_registers(address, count)).await?;
+            register.decode_words(&words).map(|v| v.to_string())
+        }
+        FunctionCode::ReadInputRegisters => {
+            let words = with_timeout(timeout, ctx.read_input_registers(address, count)).await?;
+            register.decode_words(&words).map(|v| v.to_string())
+        }
+    };
+    *connection = Some(ctx);
+    Ok(value)
+}
+
+async fn connect(target: &ModbusTarget) -> io::Result<ModbusContext> {
+    let addr = tokio::net::lookup_host(&target.host)
+        .await?
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no address for {}", target.host),
+            )
+        })?;
+    debug!("Connecting to Modbus device {} at {addr}", target.alias);
+    tcp::connect_slave(addr, Slave(target.unit_id)).await
+}
+
+async fn with_timeout<T>(
+    timeout: Duration,
+    future: impl std::future::Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Modbus request timed out"))?
+}
+
+fn decode_bits(bits: &[bool]) -> Result<String, String> {
+    bits.first()
+        .map(|bit| bit.to_string())
+        .ok_or_else(|| "no coils in response".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::Simulator;
+    use crate::target::{DataType, WordOrder};
+    use uuid::Uuid;
+
+    fn register(
+        name: &str,
+        function_code: FunctionCode,
+        address: u16,
+        data_type: DataType,
+    ) -> Register {
+        Register {
+            name: name.to_string(),
+            function_code,
+            address,
+            data_type,
+            scale: 1.0,
+            offset: 0.0,
+            word_order: WordOrder::Big,
+        }
+    }
+
+    fn target(host: String, registers: Vec<Register>) -> ModbusTarget {
+        ModbusTarget {
+            id: Uuid::new_v4(),
+            alias: "meter_01".to_string(),
+            location: "plant_1".to_string(),
+            host,
+            unit_id: 1,
+            registers,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_from_simulator() {
+        let simulator = Simulator::default()
+            .with_holding_registers(0, &[0x4248, 0x0000])
+            .with_input_registers(100, &[650])
+            .with_coils(5, &[true])
+            .with_discrete_inputs(7, &[false]);
+        let host = simulator.start().await.to_string();
+
+        let mut temperature = register(
+            "temperature",
+            FunctionCode::ReadInputRegisters,
+            100,
+            DataType::U16,
+        );
+        temperature.scale = 0.1;
+        temperature.offset = -40.0;
+        let mut device = Device::new(target(
+            host,
+            vec![
+                register(
+                    "power",
+                    FunctionCode::ReadHoldingRegisters,
+                    0,
+                    DataType::F32,
+                ),
+                temperature,
+                register("running", FunctionCode::ReadCoils, 5, DataType::Bool),
+                register("alarm", FunctionCode::ReadDiscreteInputs, 7, DataType::Bool),
+                // Nothing at this address, so the simulator responds with an exception
+                register(
+                    "missing",
+                    FunctionCode::ReadHoldingRegisters,
+                    900,
+                    DataType::U16,
+                ),
+                register(
+                    "power_again",
+                    FunctionCode::ReadHoldingRegisters,
+                    0,
+                    DataType::F32,
+                ),
+            ],
+        ));
+
+        let readings = device.read(Duration::from_millis(500)).await;
+        let values: Vec<(String, Option<String>)> =
+            readings.into_iter().map(|r| (r.status, r.value)).collect();
+        let expected = [
+            ("SUCCESS", Some("50")),
+            ("SUCCESS", Some("25")),
+            ("SUCCESS", Some("true")),
+            ("SUCCESS", Some("false")),
+            ("COMM_ERROR", None),
+            ("SUCCESS", Some("50")),
+        ];
+        assert_eq!(values.len(), expected.len());
+        for ((status, value), (expected_status, expected_value)) in values.iter().zip(expected) {
+            assert_eq!(status, expected_status);
+            assert_eq!(value.as_deref(), expected_value);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_device() {
+        // Nothing can listen on port 0, so the connection is refused
+        let mut device = Device::new(target(
+            "127.0.0.1:0".to_string(),
+            vec![register(
+                "power",
+                FunctionCode::ReadHoldingRegisters,
+                0,
+                DataType::F32,
+            )],
+        ));
+        let readings = device.read(Duration::from_millis(200)).await;
+        assert_eq!(readings[0].status, "COMM_ERROR");
+    }
+}
@@ -0,0 +1,151 @@
+//! Minimal Modbus TCP server serving fixed values, for testing the provider against without a
+//! real device. Only the read functions are supported.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const ILLEGAL_FUNCTION: u8 = 0x01;
+const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+const ILLEGAL_DATA_VALUE: u8 = 0x03;
+
+#[derive(Default, Clone)]
+pub struct Simulator {
+    coils: HashMap<u16, bool>,
+    discrete_inputs: HashMap<u16, bool>,
+    holding_registers: HashMap<u16, u16>,
+    input_registers: HashMap<u16, u16>,
+}
+
+impl Simulator {
+    pub fn with_coils(mut self, address: u16, values: &[bool]) -> Self {
+        insert(&mut self.coils, address, values);
+        self
+    }
+
+    pub fn with_discrete_inputs(mut self, address: u16, values: &[bool]) -> Self {
+        insert(&mut self.discrete_inputs, address, values);
+        self
+    }
+
+    pub fn with_holding_registers(mut self, address: u16, values: &[u16]) -> Self {
+        insert(&mut self.holding_registers, address, values);
+        self
+    }
+
+    pub fn with_input_registers(mut self, address: u16, values: &[u16]) -> Self {
+        insert(&mut self.input_registers, address, values);
+        self
+    }
+
+    /// Start serving on a free local port, for as long as the test runs
+    pub async fn start(self) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let simulator = Arc::new(self);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(simulator.clone().serve(stream));
+            }
+        });
+        addr
+    }
+
+    async fn serve(self: Arc<Self>, mut stream: TcpStream) {
+        loop {
+            // MBAP header: transaction ID, protocol ID, length, unit ID
+            let mut header = [0u8; 7];
+            if stream.read_exact(&mut header).await.is_err() {
+                break;
+            }
+            let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let mut request = vec![0u8; length.saturating_sub(1)];
+            if stream.read_exact(&mut request).await.is_err() {
+                break;
+            }
+
+            let response = self.respond(&request);
+            let mut frame = Vec::with_capacity(7 + response.len());
+            frame.extend_from_slice(&header[..4]);
+            frame.extend_from_slice(&(response.len() as u16 + 1).to_be_bytes());
+            frame.push(header[6]);
+            frame.extend_from_slice(&response);
+            if stream.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    fn respond(&self, request: &[u8]) -> Vec<u8> {
+        let function = match request.first() {
+            Some(function) => *function,
+            None => return exception(0, ILLEGAL_FUNCTION),
+        };
+        if request.len() < 5 {
+            return exception(function, ILLEGAL_DATA_VALUE);
+        }
+        let address = u16::from_be_bytes([request[1], request[2]]);
+        let count = u16::from_be_bytes([request[3], request[4]]);
+        let addresses = (0..count).map(|i| address.checked_add(i));
+
+        match function {
+            0x01 | 0x02 => {
+                let bits = if function == 0x01 {
+                    &self.coils
+                } else {
+                    &self.discrete_inputs
+                };
+                let values: Option<Vec<bool>> = addresses
+                    .map(|a| a.and_then(|a| bits.get(&a).copied()))
+                    .collect();
+                match values {
+                    Some(values) => {
+                        let mut bytes = vec![0u8; (values.len() + 7) / 8];
+                        for (i, value) in values.iter().enumerate() {
+                            if *value {
+                                bytes[i / 8] |= 1 << (i % 8);
+                            }
+                        }
+                        let mut response = vec![function, bytes.len() as u8];
+                        response.extend(bytes);
+                        response
+                    }
+                    None => exception(function, ILLEGAL_DATA_ADDRESS),
+                }
+            }
+            0x03 | 0x04 => {
+                let registers = if function == 0x03 {
+                    &self.holding_registers
+                } else {
+                    &self.input_registers
+                };
+                let values: Option<Vec<u16>> = addresses
+                    .map(|a| a.and_then(|a| registers.get(&a).copied()))
+                    .collect();
+                match values {
+                    Some(values) => {
+                        let mut response = vec![function, (values.len() * 2) as u8];
+                        for value in values {
+                            response.extend_from_slice(&value.to_be_bytes());
+                        }
+                        response
+                    }
+                    None => exception(function, ILLEGAL_DATA_ADDRESS),
+                }
+            }
+            _ => exception(function, ILLEGAL_FUNCTION),
+        }
+    }
+}
+
+fn insert<T: Copy>(map: &mut HashMap<u16, T>, address: u16, values: &[T]) {
+    for (i, value) in values.iter().enumerate() {
+        map.insert(address + i as u16, *value);
+    }
+}
+
+fn exception(function: u8, code: u8) -> Vec<u8> {
+    vec![function | 0x80, code]
+}
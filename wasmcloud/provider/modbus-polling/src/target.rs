@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use wasmcloud_interface_polling::reading::ReadingTarget;
+use wasmcloud_interface_polling::SensorReading;
+
+/// A Modbus TCP device to poll, sent as the `targetData` of an `AddPollTarget` request
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ModbusTarget {
+    pub id: Uuid,
+    pub alias: String,
+    pub location: String,
+    /// `host:port` of the device, or of the gateway in front of it
+    pub host: String,
+    /// Unit (slave) ID of the device, only needed when it's behind a gateway
+    #[serde(default = "default_unit_id")]
+    pub unit_id: u8,
+    pub registers: Vec<Register>,
+}
+
+/// A single value read from the device, which becomes one reading on every poll
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Register {
+    pub name: String,
+    pub function_code: FunctionCode,
+    /// Zero based address of the first coil or register
+    pub address: u16,
+    pub data_type: DataType,
+    /// Multiplied with the raw value before `offset` is added
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,
+    /// Order of the registers making up a 32 bit value
+    #[serde(default)]
+    pub word_order: WordOrder,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum FunctionCode {
+    ReadCoils,
+    ReadDiscreteInputs,
+    ReadHoldingRegisters,
+    ReadInputRegisters,
+}
+
+impl TryFrom<u8> for FunctionCode {
+    type Error = String;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        match code {
+            1 => Ok(FunctionCode::ReadCoils),
+            2 => Ok(FunctionCode::ReadDiscreteInputs),
+            3 => Ok(FunctionCode::ReadHoldingRegisters),
+            4 => Ok(FunctionCode::ReadInputRegisters),
+            _ => Err(format!("unsupported function code: {code}")),
+        }
+    }
+}
+
+impl From<FunctionCode> for u8 {
+    fn from(code: FunctionCode) -> Self {
+        match code {
+            FunctionCode::ReadCoils => 1,
+            FunctionCode::ReadDiscreteInputs => 2,
+            FunctionCode::ReadHoldingRegisters => 3,
+            FunctionCode::ReadInputRegisters => 4,
+        }
+    }
+}
+
+impl FunctionCode {
+    /// Whether the function reads single bits rather than 16 bit registers
+    pub fn is_bit(&self) -> bool {
+        matches!(
+            self,
+            FunctionCode::ReadCoils | FunctionCode::ReadDiscreteInputs
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DataType {
+    Bool,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+}
+
+impl DataType {
+    /// Number of coils or registers the value is made up of
+    pub fn count(&self) -> u16 {
+        match self {
+            DataType::Bool | DataType::U16 | DataType::I16 => 1,
+            DataType::U32 | DataType::I32 | DataType::F32 => 2,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WordOrder {
+    /// Most significant register first, as in the Modbus spec
+    #[default]
+    Big,
+    /// Least significant register first, which plenty of devices use anyway
+    Little,
+}
+
+fn default_unit_id() -> u8 {
+    1
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl ModbusTarget {
+    /// Check the register map makes sense before the target is polled
+    pub fn validate(&self) -> Result<(), String> {
+        if self.registers.is_empty() {
+            return Err(format!("target {} has no registers", self.id));
+        }
+        for register in &self.registers {
+            let bool_type = register.data_type == DataType::Bool;
+            if register.function_code.is_bit() != bool_type {
+                return Err(format!(
+                    "register {} can't read {:?} with function code {}",
+                    register.name,
+                    register.data_type,
+                    u8::from(register.function_code)
+                ));
+            }
+            if register
+                .address
+                .checked_add(register.data_type.count() - 1)
+                .is_none()
+            {
+                return Err(format!(
+                    "register {} runs past the end of the address space",
+                    register.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// A reading of one of the target's registers, which comes from the register within the
+    /// target
+    pub fn register_reading(
+        &self,
+        register: &Register,
+        status: &str,
+        value: Option<String>,
+        timestamp: u64,
+    ) -> SensorReading {
+        SensorReading {
+            source: format!("{}.{}", self.source(), register.name),
+            ..self.reading(status, value, timestamp)
+        }
+    }
+}
+
+impl ReadingTarget for ModbusTarget {
+    fn target_id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn location(&self) -> &str {
+        &self.location
+    }
+
+    fn alias(&self) -> &str {
+        &self.alias
+    }
+}
+
+impl Register {
+    /// Scaled value of a numeric register, from the raw registers read for it
+    pub fn decode_words(&self, words: &[u16]) -> Result<f64, String> {
+        if words.len() < self.data_type.count() as usize {
+            return Err(format!(
+                "expected {} registers, got {}",
+                self.data_type.count(),
+                words.len()
+            ));
+        }
+        let combined = || match self.word_order {
+            WordOrder::Big => (words[0] as u32) << 16 | words[1] as u32,
+            WordOrder::Little => (words[1] as u32) << 16 | words[0] as u32,
+        };
+        let raw = match self.data_type {
+            DataType::U16 => words[0] as f64,
+            DataType::I16 => words[0] as i16 as f64,
+            DataType::U32 => combined() as f64,
+            DataType::I32 => combined() as i32 as f64,
+            DataType::F32 => f32::from_bits(combined()) as f64,
+            DataType::Bool => return Err("boolean registers are read as bits".to_string()),
+        };
+        Ok(raw * self.scale + self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(data_type: DataType, word_order: WordOrder) -> Register {
+        Register {
+            name: "voltage".to_string(),
+            function_code: FunctionCode::ReadHoldingRegisters,
+            address: 0,
+            data_type,
+            scale: 1.0,
+            offset: 0.0,
+            word_order,
+        }
+    }
+
+    #[test]
+    fn test_target_deserialize() {
+        let target_json = r#"
+            {
+                "id": "5a1c2b34-2f4e-4b8e-9d6a-1f2e3d4c5b6a",
+                "alias": "meter_01",
+                "location": "plant_1",
+                "host": "10.0.0.5:502",
+                "registers": [
+                    {
+                        "name": "voltage",
+                        "function_code": 4,
+                        "address": 0,
+                        "data_type": "u16",
+                        "scale": 0.1
+                    },
+                    {
+                        "name": "running",
+                        "function_code": 1,
+                        "address": 10,
+                        "data_type": "bool"
+                    }
+                ]
+            }
+            "#;
+        let target: ModbusTarget = serde_json::from_str(target_json).unwrap();
+        assert_eq!(target.unit_id, 1);
+        assert_eq!(
+            target.registers[0].function_code,
+            FunctionCode::ReadInputRegisters
+        );
+        assert_eq!(target.registers[1].scale, 1.0);
+        assert_eq!(target.validate(), Ok(()));
+
+        let bad_code = target_json.replace(r#""function_code": 4"#, r#""function_code": 6"#);
+        assert!(serde_json::from_str::<ModbusTarget>(&bad_code).is_err());
+    }
+
+    #[test]
+    fn test_validate_data_type() {
+        let mut target = ModbusTarget {
+            id: Uuid::new_v4(),
+            alias: "meter_01".to_string(),
+            location: "plant_1".to_string(),
+            host: "127.0.0.1:502".to_string(),
+            unit_id: 1,
+            registers: vec![register(DataType::Bool, WordOrder::Big)],
+        };
+        assert!(target.validate().is_err());
+
+        target.registers[0].data_type = DataType::F32;
+        target.registers[0].address = u16::MAX;
+        assert!(target.validate().is_err());
+    }
+
+    #[test]
+    fn test_decode_words() {
+        let words = [0x4248, 0x0000]; // 50.0_f32
+        assert_eq!(
+            register(DataType::F32, WordOrder::Big).decode_words(&words),
+            Ok(50.0)
+        );
+        assert_eq!(
+            register(DataType::F32, WordOrder::Little).decode_words(&[0x0000, 0x4248]),
+            Ok(50.0)
+        );
+        assert_eq!(
+            register(DataType::I16, WordOrder::Big).decode_words(&[0xFFFE]),
+            Ok(-2.0)
+        );
+        assert_eq!(
+            register(DataType::U32, WordOrder::Big).decode_words(&[0x0001, 0x0000]),
+            Ok(65536.0)
+        );
+
+        let mut scaled = register(DataType::U16, WordOrder::Big);
+        scaled.scale = 0.1;
+        scaled.offset = -40.0;
+        let value = scaled.decode_words(&[650]).unwrap();
+        assert!((value - 25.0).abs() < 1e-9);
+
+        assert!(register(DataType::U32, WordOrder::Big)
+            .decode_words(&[1])
+            .is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! Last-value cache of every sensor's readings, so on-demand polls which can make do with a
+//! slightly old value are answered without waking the sensor. Battery-powered sensors in
+//! particular shouldn't be polled every time a dashboard refreshes.
+
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use wasmcloud_interface_polling::reading::{
+    quality_class, QUALITY_BAD, QUALITY_UNCERTAIN_LAST_KNOWN_VALUE, STATUS_CONFIG_CHANGE,
+};
+use wasmcloud_interface_polling::SensorReading;
+
+/// The latest reading with a value from each sensor, along with its timestamp and quality
+#[derive(Default)]
+pub struct LastValues {
+    readings: RwLock<HashMap<Uuid, SensorReading>>,
+}
+
+impl LastValues {
+    /// Keep every reading which carries a value that isn't bad, unless a newer one from the
+    /// same sensor is already cached
+    pub async fn record(&self, readings: &[SensorReading]) {
+        let mut cached = self.readings.write().await;
+        for reading in readings {
+            if reading.value.is_none()
+                || quality_class(&reading.quality) == QUALITY_BAD
+                || reading.status == STATUS_CONFIG_CHANGE
+            {
+                continue;
+            }
+            let sensor_id = match Uuid::parse_str(&reading.sensor_id) {
+                Ok(sensor_id) => sensor_id,
+                Err(_) => continue,
+            };
+            match cached.get(&sensor_id) {
+                Some(newer) if newer.timestamp > reading.timestamp => {}
+                _ => {
+                    cached.insert(sensor_id, reading.clone());
+                }
+            }
+        }
+    }
+
+    /// The sensor's last reading, if it was read no more than `max_age` ms before `now`
+    pub async fn fresh(&self, sensor_id: Uuid, max_age: u64, now: u64) -> Option<SensorReading> {
+        self.readings
+            .read()
+            .await
+            .get(&sensor_id)
+            .filter(|reading| now.saturating_sub(reading.timestamp) <= max_age)
+            .cloned()
+    }
+
+    /// The sensor's last reading however old it is, for sensors which can't be read on demand.
+    /// Readings older than `max_age` ms are marked as the last known value.
+    pub async fn last_known(
+        &self,
+        sensor_id: Uuid,
+        max_age: Option<u64>,
+        now: u64,
+    ) -> Option<SensorReading> {
+        let mut reading = self.readings.read().await.get(&sensor_id).cloned()?;
+        match max_age {
+            Some(max_age) if now.saturating_sub(reading.timestamp) > max_age => {
+                reading.quality = QUALITY_UNCERTAIN_LAST_KNOWN_VALUE.to_string();
+            }
+            _ => {}
+        }
+        Some(reading)
+    }
+
+    pub async fn remove(&self, sensor_id: Uuid) {
+        self.readings.write().await.remove(&sensor_id);
+    }
+
+    /// Number of sensors with a cached reading
+    pub async fn count(&self) -> usize {
+        self.readings.read().await.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmcloud_interface_polling::reading::STATUS_OFFLINE;
+
+    fn reading(
+        sensor_id: Uuid,
+        status: &str,
+        value: Option<&str>,
+        timestamp: u64,
+    ) -> SensorReading {
+        SensorReading::new(
+            sensor_id,
+            "sim.temp_01".to_string(),
+            status,
+            value.map(String::from),
+            timestamp,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_last_values() {
+        let cache = LastValues::default();
+        let sensor_id = Uuid::new_v4();
+
+        cache
+            .record(&[
+                reading(sensor_id, "SUCCESS", Some("21.5"), 10_000),
+                // Failed reads don't replace the last value
+                reading(sensor_id, "TIMEOUT", None, 11_000),
+                reading(sensor_id, "MALFORMED_PAYLOAD", Some("EOF"), 11_500),
+                reading(sensor_id, STATUS_OFFLINE, None, 12_000),
+            ])
+            .await;
+        let cached = cache.fresh(sensor_id, 5_000, 12_000).await.unwrap();
+        assert_eq!(cached.value.as_deref(), Some("21.5"));
+        assert_eq!(cached.quality, "GOOD");
+        assert!(cache.fresh(sensor_id, 1_000, 12_000).await.is_none());
+
+        // Readings arriving out of order don't replace newer ones
+        cache
+            .record(&[
+                reading(sensor_id, "SUCCESS", Some("23"), 13_000),
+                reading(sensor_id, "SUCCESS", Some("22"), 12_500),
+            ])
+            .await;
+        let cached = cache.fresh(sensor_id, 0, 13_000).await.unwrap();
+        assert_eq!(cached.value.as_deref(), Some("23"));
+
+        cache.remove(sensor_id).await;
+        assert!(cache.fresh(sensor_id, u64::MAX, 13_000).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_last_known() {
+        let cache = LastValues::default();
+        let sensor_id = Uuid::new_v4();
+        assert!(cache.last_known(sensor_id, None, 10_000).await.is_none());
+
+        cache
+            .record(&[reading(sensor_id, "SUCCESS", Some("21.5"), 10_000)])
+            .await;
+        let last = cache.last_known(sensor_id, Some(5_000), 12_000).await;
+        assert_eq!(last.unwrap().quality, "GOOD");
+        let last = cache.last_known(sensor_id, None, 60_000).await;
+        assert_eq!(last.unwrap().quality, "GOOD");
+
+        // Readings older than the max age are still returned, but as the last known value
+        let last = cache
+            .last_known(sensor_id, Some(5_000), 60_000)
+            .await
+            .unwrap();
+        assert_eq!(last.value.as_deref(), Some("21.5"));
+        assert_eq!(last.status, "SUCCESS");
+        assert_eq!(last.quality, "UNCERTAIN_LAST_KNOWN_VALUE");
+    }
+}
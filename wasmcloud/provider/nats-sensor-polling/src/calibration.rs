@@ -0,0 +1,79 @@
+//! Calibration for sensors whose raw values are a long way off the true value, e.g. the
+//! Pico W's internal temperature sensor. A sensor's calibration is given in its heartbeat, or
+//! pushed to it as part of a configuration document, and applied to every value read from it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::failure::PollFailure;
+
+/// Conversion from the value a sensor reports to the true value. A polynomial is applied if one
+/// is given, otherwise the value is multiplied by `gain` and then `offset` is added.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Calibration {
+    #[serde(default)]
+    pub offset: f64,
+    #[serde(default = "default_gain")]
+    pub gain: f64,
+    /// Coefficients of the polynomial, from the constant term up
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub polynomial: Vec<f64>,
+}
+
+fn default_gain() -> f64 {
+    1.0
+}
+
+impl Calibration {
+    /// Calibrate a value read from the sensor, which has to be a number
+    pub fn apply(&self, raw: &str) -> Result<String, PollFailure> {
+        let raw = raw.trim().parse::<f64>().map_err(|_| {
+            PollFailure::MalformedPayload(format!("can't calibrate non-numeric value: {raw}"))
+        })?;
+        let calibrated = if self.polynomial.is_empty() {
+            raw * self.gain + self.offset
+        } else {
+            self.polynomial
+                .iter()
+                .rev()
+                .fold(0.0, |total, coefficient| total * raw + coefficient)
+        };
+        if calibrated.is_finite() {
+            Ok(calibrated.to_string())
+        } else {
+            Err(PollFailure::OutOfRange(calibrated.to_string()))
+        }
+    }
+
+    /// The calibration as recorded alongside each calibrated reading
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let linear: Calibration = serde_json::from_str(r#"{"offset": -2.5}"#).unwrap();
+        assert_eq!(linear.apply("27.5").unwrap(), "25");
+        let linear: Calibration = serde_json::from_str(r#"{"gain": 2, "offset": 1}"#).unwrap();
+        assert_eq!(linear.apply(" 10 ").unwrap(), "21");
+
+        // 1 + 2x + 0.5x^2, the gain and offset are ignored
+        let polynomial: Calibration =
+            serde_json::from_str(r#"{"gain": 10, "polynomial": [1, 2, 0.5]}"#).unwrap();
+        assert_eq!(polynomial.apply("4").unwrap(), "17");
+
+        assert!(matches!(
+            linear.apply("ON"),
+            Err(PollFailure::MalformedPayload(_))
+        ));
+        assert!(matches!(
+            linear.apply("1e308"),
+            Err(PollFailure::OutOfRange(_))
+        ));
+        assert_eq!(linear.to_json(), r#"{"offset":1.0,"gain":2.0}"#);
+    }
+}
@@ -1 +1,349 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use wasmcloud_interface_polling::encoding::Encoding;
 
+use crate::events::ChannelEvents;
+
+/// Polling config shared with an actor's background tasks, so the link can be reconfigured
+/// without restarting them
+pub type SharedConfig = Arc<RwLock<PollingConfig>>;
+
+const DEFAULT_POLL_TIMEOUT_MS: u64 = 500;
+const DEFAULT_PUSH_FLUSH_INTERVAL_MS: u64 = 5_000;
+const DEFAULT_PUSH_MAX_READINGS: usize = 100;
+const DEFAULT_PUSH_STALE_INTERVALS: u64 = 3;
+const DEFAULT_COMMAND_TIMEOUT_MS: u64 = 2_000;
+const DEFAULT_CONFIG_TIMEOUT_MS: u64 = 10_000;
+const DEFAULT_SHARD_LEASE_MS: u64 = 2_000;
+const DEFAULT_SHUTDOWN_TIMEOUT_MS: u64 = 5_000;
+const DEFAULT_DISCONNECT_SUBJECT: &str = "wasmcloud.polling.disconnect";
+const DEFAULT_QUARANTINE_AFTER: u32 = 5;
+const DEFAULT_QUARANTINE_PROBE_INTERVAL_MS: u64 = 60_000;
+const DEFAULT_HEARTBEAT_QUEUE_SIZE: usize = 1_024;
+const DEFAULT_HEARTBEAT_RATE_PER_MIN: u32 = 60;
+const DEFAULT_HEARTBEAT_BURST: u32 = 10;
+const DEFAULT_HEARTBEAT_COALESCE_MS: u64 = 1_000;
+
+/// Configuration for how sensors are polled and how their readings are delivered to actors.
+///
+/// This is flattened into the [`ConnectionConfig`](crate::nats::ConnectionConfig), so these
+/// fields can be set in the same `config_json` as the connection settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PollingConfig {
+    /// How long to wait for a polled sensor to respond, in ms
+    #[serde(default)]
+    pub poll_timeout_ms: Option<u64>,
+    /// How often readings buffered from push-mode sensors are sent to the actor, in ms
+    #[serde(default)]
+    pub push_flush_interval_ms: Option<u64>,
+    /// Maximum number of readings accepted from a single push-mode sensor per flush, any
+    /// further readings are dropped until the next flush
+    #[serde(default)]
+    pub push_max_readings: Option<usize>,
+    /// Number of publish intervals a push-mode sensor can miss before its data is flagged as stale
+    #[serde(default)]
+    pub push_stale_intervals: Option<u64>,
+    /// How long to wait for a device to acknowledge a command if the request doesn't specify a
+    /// timeout, in ms
+    #[serde(default)]
+    pub command_timeout_ms: Option<u64>,
+    /// How long to wait for a device to report a pushed configuration in its heartbeat if the
+    /// request doesn't specify a timeout, in ms
+    #[serde(default)]
+    pub config_timeout_ms: Option<u64>,
+    /// Subject to answer time synchronisation requests from sensors on, time sync is disabled if
+    /// this isn't set
+    #[serde(default)]
+    pub time_sync_subject: Option<String>,
+    /// How readings are encoded in the `PollResult` sent to the actor, JSON if this isn't set
+    #[serde(default)]
+    pub encoding: Option<Encoding>,
+    /// Subject prefix used to share sensors with other provider instances linked to the same
+    /// actor, every instance polls every sensor if this isn't set
+    #[serde(default)]
+    pub shard_subject: Option<String>,
+    /// How often this instance renews its shard lease, in ms
+    #[serde(default)]
+    pub shard_lease_ms: Option<u64>,
+    /// Subject Sparkplug B messages arrive on from the MQTT bridge, e.g. `spBv1//0.>` for every
+    /// group. Sparkplug is disabled if this isn't set.
+    #[serde(default)]
+    pub sparkplug_subject: Option<String>,
+    /// How long polls in progress are given to finish when the provider shuts down, in ms
+    #[serde(default)]
+    pub shutdown_timeout_ms: Option<u64>,
+    /// Subject a notice is published on when the provider shuts down,
+    /// `wasmcloud.polling.disconnect` if this isn't set
+    #[serde(default)]
+    pub disconnect_subject: Option<String>,
+    /// Subject prefix for the endpoints of the NATS micro service the provider registers as, so
+    /// it can be inspected with `nats micro`. The service isn't registered if this isn't set.
+    #[serde(default)]
+    pub service_subject: Option<String>,
+    /// Number of failed reads in a row before a polled sensor is quarantined, 0 to never
+    /// quarantine sensors
+    #[serde(default)]
+    pub quarantine_after: Option<u32>,
+    /// How often a quarantined sensor is polled to check whether it has recovered, in ms
+    #[serde(default)]
+    pub quarantine_probe_interval_ms: Option<u64>,
+    /// Maximum number of heartbeats waiting to be handled, any further heartbeats are dropped.
+    /// Only applied when the actor is first linked.
+    #[serde(default)]
+    pub heartbeat_queue_size: Option<usize>,
+    /// Number of heartbeats handled from a single sensor per minute once it has used up its burst
+    #[serde(default)]
+    pub heartbeat_rate_per_min: Option<u32>,
+    /// Number of heartbeats a single sensor can send at once before it's rate limited
+    #[serde(default)]
+    pub heartbeat_burst: Option<u32>,
+    /// How long a heartbeat identical to the last one handled from the same sensor is dropped
+    /// for, in ms
+    #[serde(default)]
+    pub heartbeat_coalesce_ms: Option<u64>,
+    /// Whether readings from multi-channel sensors are sent as a reading for each channel
+    /// (`per_channel`) or a single reading with every channel's value (`composite`), a reading
+    /// for each channel if this isn't set
+    #[serde(default)]
+    pub channel_events: Option<ChannelEvents>,
+}
+
+impl PollingConfig {
+    /// Check for settings which would stop the provider's tasks from running, e.g. an interval
+    /// of 0
+    pub fn validate(&self) -> Result<(), String> {
+        let non_zero = [
+            ("push_flush_interval_ms", self.push_flush_interval_ms),
+            ("shard_lease_ms", self.shard_lease_ms),
+            (
+                "heartbeat_queue_size",
+                self.heartbeat_queue_size.map(|size| size as u64),
+            ),
+        ];
+        match non_zero.iter().find(|(_, value)| *value == Some(0)) {
+            Some((name, _)) => Err(format!("{name} must be greater than 0")),
+            None => Ok(()),
+        }
+    }
+
+    pub fn merge(&self, extra: &PollingConfig) -> PollingConfig {
+        let mut out = self.clone();
+        if extra.poll_timeout_ms.is_some() {
+            out.poll_timeout_ms = extra.poll_timeout_ms;
+        }
+        if extra.push_flush_interval_ms.is_some() {
+            out.push_flush_interval_ms = extra.push_flush_interval_ms;
+        }
+        if extra.push_max_readings.is_some() {
+            out.push_max_readings = extra.push_max_readings;
+        }
+        if extra.push_stale_intervals.is_some() {
+            out.push_stale_intervals = extra.push_stale_intervals;
+        }
+        if extra.command_timeout_ms.is_some() {
+            out.command_timeout_ms = extra.command_timeout_ms;
+        }
+        if extra.config_timeout_ms.is_some() {
+            out.config_timeout_ms = extra.config_timeout_ms;
+        }
+        if extra.time_sync_subject.is_some() {
+            out.time_sync_subject = extra.time_sync_subject.clone();
+        }
+        if extra.encoding.is_some() {
+            out.encoding = extra.encoding;
+        }
+        if extra.shard_subject.is_some() {
+            out.shard_subject = extra.shard_subject.clone();
+        }
+        if extra.shard_lease_ms.is_some() {
+            out.shard_lease_ms = extra.shard_lease_ms;
+        }
+        if extra.sparkplug_subject.is_some() {
+            out.sparkplug_subject = extra.sparkplug_subject.clone();
+        }
+        if extra.shutdown_timeout_ms.is_some() {
+            out.shutdown_timeout_ms = extra.shutdown_timeout_ms;
+        }
+        if extra.disconnect_subject.is_some() {
+            out.disconnect_subject = extra.disconnect_subject.clone();
+        }
+        if extra.service_subject.is_some() {
+            out.service_subject = extra.service_subject.clone();
+        }
+        if extra.quarantine_after.is_some() {
+            out.quarantine_after = extra.quarantine_after;
+        }
+        if extra.quarantine_probe_interval_ms.is_some() {
+            out.quarantine_probe_interval_ms = extra.quarantine_probe_interval_ms;
+        }
+        if extra.heartbeat_queue_size.is_some() {
+            out.heartbeat_queue_size = extra.heartbeat_queue_size;
+        }
+        if extra.heartbeat_rate_per_min.is_some() {
+            out.heartbeat_rate_per_min = extra.heartbeat_rate_per_min;
+        }
+        if extra.heartbeat_burst.is_some() {
+            out.heartbeat_burst = extra.heartbeat_burst;
+        }
+        if extra.heartbeat_coalesce_ms.is_some() {
+            out.heartbeat_coalesce_ms = extra.heartbeat_coalesce_ms;
+        }
+        if extra.channel_events.is_some() {
+            out.channel_events = extra.channel_events;
+        }
+        out
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding.unwrap_or_default()
+    }
+
+    pub fn channel_events(&self) -> ChannelEvents {
+        self.channel_events.unwrap_or_default()
+    }
+
+    pub fn poll_timeout(&self) -> Duration {
+        Duration::from_millis(self.poll_timeout_ms.unwrap_or(DEFAULT_POLL_TIMEOUT_MS))
+    }
+
+    pub fn push_flush_interval(&self) -> Duration {
+        Duration::from_millis(
+            self.push_flush_interval_ms
+                .unwrap_or(DEFAULT_PUSH_FLUSH_INTERVAL_MS),
+        )
+    }
+
+    pub fn push_max_readings(&self) -> usize {
+        self.push_max_readings.unwrap_or(DEFAULT_PUSH_MAX_READINGS)
+    }
+
+    pub fn push_stale_intervals(&self) -> u64 {
+        self.push_stale_intervals
+            .unwrap_or(DEFAULT_PUSH_STALE_INTERVALS)
+    }
+
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_millis(
+            self.command_timeout_ms
+                .unwrap_or(DEFAULT_COMMAND_TIMEOUT_MS),
+        )
+    }
+
+    pub fn config_timeout(&self) -> Duration {
+        Duration::from_millis(self.config_timeout_ms.unwrap_or(DEFAULT_CONFIG_TIMEOUT_MS))
+    }
+
+    pub fn shard_lease(&self) -> Duration {
+        Duration::from_millis(self.shard_lease_ms.unwrap_or(DEFAULT_SHARD_LEASE_MS))
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_millis(
+            self.shutdown_timeout_ms
+                .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_MS),
+        )
+    }
+
+    pub fn quarantine_after(&self) -> u32 {
+        self.quarantine_after.unwrap_or(DEFAULT_QUARANTINE_AFTER)
+    }
+
+    pub fn quarantine_probe_interval(&self) -> Duration {
+        Duration::from_millis(
+            self.quarantine_probe_interval_ms
+                .unwrap_or(DEFAULT_QUARANTINE_PROBE_INTERVAL_MS),
+        )
+    }
+
+    pub fn heartbeat_queue_size(&self) -> usize {
+        self.heartbeat_queue_size
+            .unwrap_or(DEFAULT_HEARTBEAT_QUEUE_SIZE)
+    }
+
+    pub fn heartbeat_rate_per_min(&self) -> u32 {
+        self.heartbeat_rate_per_min
+            .unwrap_or(DEFAULT_HEARTBEAT_RATE_PER_MIN)
+    }
+
+    pub fn heartbeat_burst(&self) -> u32 {
+        self.heartbeat_burst.unwrap_or(DEFAULT_HEARTBEAT_BURST)
+    }
+
+    pub fn heartbeat_coalesce_window(&self) -> Duration {
+        Duration::from_millis(
+            self.heartbeat_coalesce_ms
+                .unwrap_or(DEFAULT_HEARTBEAT_COALESCE_MS),
+        )
+    }
+
+    pub fn disconnect_subject(&self) -> &str {
+        self.disconnect_subject
+            .as_deref()
+            .unwrap_or(DEFAULT_DISCONNECT_SUBJECT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let default_config = PollingConfig {
+            push_flush_interval_ms: Some(1_000),
+            push_max_readings: Some(10),
+            push_stale_intervals: None,
+            ..Default::default()
+        };
+        let link_config = PollingConfig {
+            push_flush_interval_ms: None,
+            push_max_readings: Some(50),
+            push_stale_intervals: Some(5),
+            ..Default::default()
+        };
+
+        let merged = default_config.merge(&link_config);
+        assert_eq!(merged.push_flush_interval(), Duration::from_millis(1_000));
+        assert_eq!(merged.push_max_readings(), 50);
+        assert_eq!(merged.push_stale_intervals(), 5);
+        assert_eq!(merged.encoding(), Encoding::Json);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(PollingConfig::default().validate(), Ok(()));
+        let config = PollingConfig {
+            push_flush_interval_ms: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err("push_flush_interval_ms must be greater than 0".to_string())
+        );
+        // Every lease would expire as soon as it was renewed
+        let config = PollingConfig {
+            shard_lease_ms: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err("shard_lease_ms must be greater than 0".to_string())
+        );
+        let config = PollingConfig {
+            heartbeat_queue_size: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err("heartbeat_queue_size must be greater than 0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_encoding_deserialize() {
+        let config: PollingConfig = serde_json::from_str(r#"{"encoding": "msgpack"}"#).unwrap();
+        assert_eq!(config.encoding(), Encoding::Msgpack);
+    }
+}
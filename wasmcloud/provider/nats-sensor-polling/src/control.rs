@@ -0,0 +1,206 @@
+//! Commands for devices which advertise a `command_topic` in their heartbeat.
+//!
+//! Commands are published to the device's command topic, and the device acknowledges them on its
+//! `ack_topic`. Devices which don't have an ack topic (i.e. anything that isn't limited to
+//! MQTT v3.*) can simply reply to the command message instead.
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{debug, error};
+use uuid::Uuid;
+use wasmcloud_interface_polling::control::{CommandRequest, CommandResponse, ControlError};
+
+use crate::events;
+use crate::nats::NatsClient;
+use crate::sensor::Sensor;
+
+/// Payload published to a device's command topic
+#[derive(Deserialize, Serialize)]
+pub struct Command {
+    /// Unique to each command so the acknowledgement can be matched to it
+    pub id: Uuid,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// Payload published by a device once it has processed a command
+#[derive(Deserialize, Serialize)]
+pub struct CommandAck {
+    /// ID of the command being acknowledged
+    pub id: Uuid,
+    /// Whether the device applied the command
+    pub ok: bool,
+    #[serde(default)]
+    pub old: Option<String>,
+    #[serde(default)]
+    pub new: Option<String>,
+    /// Reason the device rejected the command
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Build a response for a command which wasn't acknowledged
+pub fn failed(req: &CommandRequest, error_type: &str, description: String) -> CommandResponse {
+    CommandResponse {
+        command: req.clone(),
+        acknowledged: false,
+        error: Some(ControlError {
+            error_type: error_type.to_string(),
+            description: Some(description),
+        }),
+        timestamp: Some(events::now()),
+        ..Default::default()
+    }
+}
+
+/// Send a command to the sensor and wait for it to be acknowledged, or for the timeout to elapse.
+/// The response has the sensor's source, so it can be audited alongside the sensor's readings.
+pub async fn send_command(
+    client: &NatsClient,
+    sensor: &Sensor,
+    req: &CommandRequest,
+    default_timeout: Duration,
+) -> CommandResponse {
+    CommandResponse {
+        source: Some(events::source(sensor)),
+        ..deliver_command(client, sensor, req, default_timeout).await
+    }
+}
+
+async fn deliver_command(
+    client: &NatsClient,
+    sensor: &Sensor,
+    req: &CommandRequest,
+    default_timeout: Duration,
+) -> CommandResponse {
+    let command_topic = match &sensor.command_topic {
+        Some(topic) => topic.to_owned(),
+        None => {
+            return failed(
+                req,
+                "NOT_CONTROLLABLE",
+                format!("sensor {} doesn't accept commands", sensor.id),
+            )
+        }
+    };
+    let ack_topic = sensor
+        .ack_topic
+        .to_owned()
+        .unwrap_or_else(|| client.new_inbox());
+    let timeout = req
+        .timeout_ms
+        .map(|ms| Duration::from_millis(ms as u64))
+        .unwrap_or(default_timeout);
+
+    let command = Command {
+        id: Uuid::new_v4(),
+        command: req.command.to_owned(),
+        value: req.value.to_owned(),
+    };
+    let payload = match serde_json::to_vec(&command) {
+        Ok(payload) => payload,
+        Err(e) => return failed(req, "SER_ERROR", e.to_string()),
+    };
+
+    let mut subscriber = match client.subscribe(ack_topic.to_owned()).await {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Error subscribing to command acks for topic {ack_topic}: {e:?}");
+            return failed(req, "SUBSCRIBE_ERROR", e.to_string());
+        }
+    };
+
+    let published = if sensor.ack_topic.is_some() {
+        client.publish(command_topic, payload.into()).await
+    } else {
+        client
+            .publish_with_reply(command_topic, ack_topic, payload.into())
+            .await
+    };
+    if let Err(e) = published {
+        error!("Error sending command to sensor: {e:?}");
+        return failed(req, "PUBLISH_ERROR", e.to_string());
+    }
+
+    let ack = tokio::time::timeout(timeout, async {
+        // Other commands could be acknowledged on the same topic, so wait for the matching ID
+        while let Some(message) = subscriber.next().await {
+            match serde_json::from_slice::<CommandAck>(&message.payload) {
+                Ok(ack) if ack.id == command.id => return Some(ack),
+                Ok(_) => continue,
+                Err(e) => debug!("Ignoring malformed command ack: {e:?}"),
+            }
+        }
+        None
+    })
+    .await
+    .unwrap_or(None);
+
+    match ack {
+        Some(ack) => CommandResponse {
+            command: req.clone(),
+            acknowledged: true,
+            old: ack.old,
+            new: ack.new,
+            error: if ack.ok {
+                None
+            } else {
+                Some(ControlError {
+                    error_type: "REJECTED".to_string(),
+                    description: ack.error,
+                })
+            },
+            source: None,
+            timestamp: Some(events::now()),
+        },
+        None => failed(
+            req,
+            "TIMEOUT",
+            format!(
+                "no acknowledgement from sensor {} after {timeout:?}",
+                sensor.id
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_ack_deserialize() {
+        let ack_json = r#"
+            {
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "ok": true,
+                "old": "25",
+                "new": "40"
+            }
+            "#;
+        let ack: CommandAck = serde_json::from_str(ack_json).unwrap();
+        assert_eq!(
+            ack.id,
+            Uuid::parse_str("a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3").unwrap()
+        );
+        assert!(ack.ok);
+        assert_eq!(ack.old.as_deref(), Some("25"));
+        assert_eq!(ack.new.as_deref(), Some("40"));
+        assert_eq!(ack.error, None);
+    }
+
+    #[test]
+    fn test_command_serialize() {
+        let command = Command {
+            id: Uuid::parse_str("a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3").unwrap(),
+            command: "stop".to_string(),
+            value: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&command).unwrap(),
+            r#"{"id":"a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3","command":"stop"}"#
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Graceful shutdown. Once the provider starts shutting down no new polls are started, and
+//! the ones already in progress are given until a deadline to finish, so their readings still
+//! reach the actor.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Tracks the polls in progress for an actor, so shutdown can wait for them
+pub struct Drain {
+    /// Cloned into every poll in progress, and dropped once shutdown starts
+    in_flight: Mutex<Option<mpsc::Sender<()>>>,
+    /// Closed once every poll in progress has finished after shutdown started
+    finished: tokio::sync::Mutex<mpsc::Receiver<()>>,
+}
+
+/// Held for as long as a poll is in progress
+pub struct InFlight {
+    _tx: mpsc::Sender<()>,
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel(1);
+        Self {
+            in_flight: Mutex::new(Some(tx)),
+            finished: tokio::sync::Mutex::new(rx),
+        }
+    }
+}
+
+impl Drain {
+    /// Start a poll, unless shutdown has already started
+    pub fn start(&self) -> Option<InFlight> {
+        self.in_flight
+            .lock()
+            .expect("drain lock poisoned")
+            .as_ref()
+            .map(|tx| InFlight { _tx: tx.clone() })
+    }
+
+    /// Stop any new polls from starting, and wait up to `deadline` for the ones in progress to
+    /// finish. Returns false if some were still in progress at the deadline.
+    pub async fn drain(&self, deadline: Duration) -> bool {
+        self.in_flight.lock().expect("drain lock poisoned").take();
+        let mut finished = self.finished.lock().await;
+        // Nothing is ever sent, so this only returns once every sender has been dropped
+        tokio::time::timeout(deadline, finished.recv())
+            .await
+            .is_ok()
+    }
+}
+
+/// Published on the disconnect subject when the provider shuts down, so devices and other
+/// instances know this instance has stopped polling the actor's sensors
+#[derive(Debug, Serialize)]
+pub struct DisconnectNotice {
+    pub actor_id: String,
+    /// This instance's ID in the link's shard, if sensors are shared with other instances
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<Uuid>,
+    /// Every sensor this instance was polling or receiving readings from
+    pub sensor_ids: Vec<Uuid>,
+    /// Whether every poll in progress finished before the shutdown deadline
+    pub drained: bool,
+    pub timestamp: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain() {
+        let drain = Drain::default();
+        let poll = drain.start().unwrap();
+
+        // The poll in progress holds up shutdown until it finishes
+        assert!(!drain.drain(Duration::from_millis(10)).await);
+        assert!(drain.start().is_none());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            drop(poll);
+        });
+        assert!(drain.drain(Duration::from_secs(1)).await);
+    }
+}
@@ -0,0 +1,494 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+pub use wasmcloud_interface_polling::reading::now;
+use wasmcloud_interface_polling::reading::{
+    QUALITIES, STATUS_CONFIG_CHANGE, STATUS_QUARANTINED, STATUS_RESTORED,
+};
+use wasmcloud_interface_polling::{SensorReading, TargetDescriptor};
+
+use crate::failure::PollFailure;
+use crate::sensor::{Channel, Sensor, SensorSettings, ValueRange};
+
+/// What a sensor responds with, either just its value or its value along with the quality the
+/// device gives it, e.g. `UNCERTAIN_OUT_OF_CALIBRATION` if it knows it's due for calibration
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Payload {
+    Value(String),
+    Reading {
+        value: String,
+        #[serde(default)]
+        quality: Option<String>,
+    },
+}
+
+/// Build a reading from the payload a sensor responded with, or a failed reading if it doesn't
+/// hold a value or its value is outside the sensor's range. Values from calibrated sensors are
+/// recorded both as they were read and calibrated, along with the calibration, so the
+/// conversion can be checked.
+pub fn sensor_reading(sensor: &Sensor, payload: &[u8], timestamp: u64) -> SensorReading {
+    if !sensor.channels.is_empty() {
+        return channel_reading(sensor, payload, timestamp);
+    }
+    let (value, quality) = match parse_payload(payload) {
+        Ok(parsed) => parsed,
+        Err(failure) => return failed_reading(sensor, &failure, timestamp),
+    };
+    let reading = match &sensor.calibration {
+        Some(calibration) => match calibration.apply(&value) {
+            Ok(calibrated) => SensorReading {
+                raw_value: Some(value),
+                calibration: Some(calibration.to_json()),
+                ..sensor_event(sensor, "SUCCESS", Some(calibrated), timestamp)
+            },
+            Err(failure) => return failed_reading(sensor, &failure, timestamp),
+        },
+        None => sensor_event(sensor, "SUCCESS", Some(value), timestamp),
+    };
+    let value = reading.value.as_deref().unwrap_or_default();
+    if let Err(failure) = check_range(value, sensor.range) {
+        return SensorReading {
+            raw_value: reading.raw_value,
+            calibration: reading.calibration,
+            ..failed_reading(sensor, &failure, timestamp)
+        };
+    }
+    match quality {
+        Some(quality) => SensorReading { quality, ..reading },
+        None => reading,
+    }
+}
+
+/// Parse the value, and the quality if the device gave one, from a sensor's payload
+fn parse_payload(payload: &[u8]) -> Result<(String, Option<String>), PollFailure> {
+    let (value, quality) = match serde_json::from_slice(payload) {
+        Ok(Payload::Value(value)) => (value, None),
+        Ok(Payload::Reading { value, quality }) => (value, quality),
+        Err(e) => return Err(PollFailure::MalformedPayload(e.to_string())),
+    };
+    if let Some(quality) = &quality {
+        if !QUALITIES.contains(&quality.as_str()) {
+            return Err(PollFailure::MalformedPayload(format!(
+                "unknown quality: {quality}"
+            )));
+        }
+    }
+    match value.trim().parse::<f64>() {
+        Ok(number) if !number.is_finite() => Err(PollFailure::OutOfRange(value)),
+        _ => Ok((value, quality)),
+    }
+}
+
+/// How readings from multi-channel sensors are sent to the actor
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelEvents {
+    /// A reading for each channel
+    #[default]
+    PerChannel,
+    /// A single reading with every channel's value
+    Composite,
+}
+
+/// One channel's value in a composite reading
+#[derive(Deserialize, Serialize, Debug)]
+struct ChannelValue {
+    status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unit: Option<String>,
+}
+
+/// Build a composite reading of every channel from a multi-channel sensor's payload, which is
+/// either an object of each channel's value or an array of them in the order the channels are
+/// declared. The reading is `UNCERTAIN` if only some of the channels could be read.
+fn channel_reading(sensor: &Sensor, payload: &[u8], timestamp: u64) -> SensorReading {
+    let values = match channel_values(&sensor.channels, payload) {
+        Ok(values) => values,
+        Err(failure) => return failed_reading(sensor, &failure, timestamp),
+    };
+
+    let mut channels = BTreeMap::new();
+    let mut failures = vec![];
+    for (channel, value) in sensor.channels.iter().zip(values) {
+        let value = match channel_value(channel, value) {
+            Ok(value) => ChannelValue {
+                status: "SUCCESS".to_string(),
+                value: Some(value),
+                unit: channel.unit.to_owned(),
+            },
+            Err(failure) => {
+                failures.push(failure.status());
+                ChannelValue {
+                    status: failure.status().to_string(),
+                    value: failure.value(),
+                    unit: channel.unit.to_owned(),
+                }
+            }
+        };
+        channels.insert(channel.name.as_str(), value);
+    }
+    let status = match failures.first() {
+        None => "SUCCESS",
+        Some(&status) if failures.len() == sensor.channels.len() => status,
+        Some(_) => "UNCERTAIN",
+    };
+    let names: Vec<&str> = sensor.channels.iter().map(|c| c.name.as_str()).collect();
+    SensorReading {
+        channel: Some(names.join(",")),
+        ..sensor_event(
+            sensor,
+            status,
+            serde_json::to_string(&channels).ok(),
+            timestamp,
+        )
+    }
+}
+
+/// Each channel's value from the payload, in the order the channels are declared
+fn channel_values(channels: &[Channel], payload: &[u8]) -> Result<Vec<Option<Value>>, PollFailure> {
+    match serde_json::from_slice(payload) {
+        Ok(Value::Object(mut values)) => Ok(channels
+            .iter()
+            .map(|channel| values.remove(&channel.name))
+            .collect()),
+        Ok(Value::Array(values)) => {
+            let mut values = values.into_iter();
+            Ok(channels.iter().map(|_| values.next()).collect())
+        }
+        Ok(other) => Err(PollFailure::MalformedPayload(format!(
+            "expected channel values: {other}"
+        ))),
+        Err(e) => Err(PollFailure::MalformedPayload(e.to_string())),
+    }
+}
+
+/// Check a single channel's value, which can be a number or a string
+fn channel_value(channel: &Channel, value: Option<Value>) -> Result<String, PollFailure> {
+    let value = match value {
+        Some(Value::String(value)) => value,
+        Some(Value::Number(value)) => value.to_string(),
+        None | Some(Value::Null) => return Err(PollFailure::MalformedPayload("no value".into())),
+        Some(other) => return Err(PollFailure::MalformedPayload(other.to_string())),
+    };
+    check_range(&value, channel.range)?;
+    Ok(value)
+}
+
+/// Check a value is within the range a sensor declared, which it has to be a number for if the
+/// sensor declared one
+fn check_range(value: &str, range: Option<ValueRange>) -> Result<(), PollFailure> {
+    match (value.trim().parse::<f64>(), range) {
+        (Ok(number), _) if !number.is_finite() => Err(PollFailure::OutOfRange(value.to_string())),
+        (Ok(number), Some(range)) if !range.contains(number) => {
+            Err(PollFailure::OutOfRange(value.to_string()))
+        }
+        (Err(_), Some(_)) => Err(PollFailure::MalformedPayload(format!(
+            "not a number: {value}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Split composite readings from multi-channel sensors into a reading for each channel, named
+/// in its `channel`, unless the link wants composite readings
+pub fn channel_events(readings: Vec<SensorReading>, mode: ChannelEvents) -> Vec<SensorReading> {
+    match mode {
+        ChannelEvents::Composite => readings,
+        ChannelEvents::PerChannel => readings.into_iter().flat_map(split_channels).collect(),
+    }
+}
+
+fn split_channels(reading: SensorReading) -> Vec<SensorReading> {
+    let mut channels: BTreeMap<String, ChannelValue> = match (&reading.channel, &reading.value) {
+        (Some(_), Some(value)) => match serde_json::from_str(value) {
+            Ok(channels) => channels,
+            Err(_) => return vec![reading],
+        },
+        _ => return vec![reading],
+    };
+    let names = reading.channel.as_deref().unwrap_or_default();
+    names
+        .split(',')
+        .filter_map(|name| {
+            let value = channels.remove(name)?;
+            Some(SensorReading {
+                channel: Some(name.to_string()),
+                unit: value.unit,
+                source_timestamp: reading.source_timestamp,
+                gps: reading.gps.to_owned(),
+                traceparent: reading.traceparent.to_owned(),
+                ..SensorReading::new(
+                    &reading.sensor_id,
+                    reading.source.to_owned(),
+                    &value.status,
+                    value.value,
+                    reading.timestamp,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Build a reading recording why the sensor couldn't be read
+pub fn failed_reading(sensor: &Sensor, failure: &PollFailure, timestamp: u64) -> SensorReading {
+    sensor_event(sensor, failure.status(), failure.value(), timestamp)
+}
+
+/// Build a reading for the sensor with the given status and value
+pub fn sensor_event(
+    sensor: &Sensor,
+    status: &str,
+    value: Option<String>,
+    timestamp: u64,
+) -> SensorReading {
+    // TODO: - add value_type field which desers to an enum, to handle floats/ints etc without needing
+    //         to convert to a string
+    //       - use timestamp from sensor after configuring RTC on pico-w
+    SensorReading {
+        unit: sensor.unit.to_owned(),
+        gps: sensor.location.gps().map(|gps| gps.to_string()),
+        ..SensorReading::new(sensor.id, source(sensor), status, value, timestamp)
+    }
+}
+
+/// Build an `OFFLINE` reading for a sensor whose device has disconnected
+pub fn sensor_offline(sensor: &Sensor, timestamp: u64) -> SensorReading {
+    failed_reading(sensor, &PollFailure::Offline, timestamp)
+}
+
+/// Build a `QUARANTINED` reading for a sensor which has failed `failures` reads in a row, the
+/// last with `status`
+pub fn sensor_quarantined(
+    sensor: &Sensor,
+    failures: u32,
+    status: &str,
+    timestamp: u64,
+) -> SensorReading {
+    let value = format!("{failures} failed reads in a row, last {status}");
+    sensor_event(sensor, STATUS_QUARANTINED, Some(value), timestamp)
+}
+
+/// Build a `RESTORED` reading for a quarantined sensor which has been read successfully again
+pub fn sensor_restored(sensor: &Sensor, timestamp: u64) -> SensorReading {
+    sensor_event(sensor, STATUS_RESTORED, None, timestamp)
+}
+
+/// Build a `CONFIG_CHANGE` reading recording the sensor's settings before and after a change
+pub fn config_change(previous: &Sensor, current: &Sensor, timestamp: u64) -> SensorReading {
+    SensorReading {
+        previous_value: Some(settings_json(&previous.settings())),
+        ..sensor_event(
+            current,
+            STATUS_CONFIG_CHANGE,
+            Some(settings_json(&current.settings())),
+            timestamp,
+        )
+    }
+}
+
+/// Changes to the registered sensors which the actor is told about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetEvent {
+    Discovered,
+    Changed,
+    Lost,
+}
+
+/// Describe the sensor for discovery notifications, with its whole heartbeat as the details
+pub fn descriptor(sensor: &Sensor, timestamp: u64) -> TargetDescriptor {
+    TargetDescriptor {
+        target_id: sensor.id.to_string(),
+        alias: sensor.alias.to_owned(),
+        location: sensor.location.to_string(),
+        poll_interval: Some(sensor.poll_interval),
+        details: serde_json::to_string(sensor).unwrap_or_else(|e| e.to_string()),
+        timestamp,
+    }
+}
+
+pub fn settings_json(settings: &SensorSettings) -> String {
+    serde_json::to_string(settings).unwrap_or_else(|e| e.to_string())
+}
+
+/// Where a sensor's readings come from, e.g. `acme/plant_1.temp_01`
+pub fn source(sensor: &Sensor) -> String {
+    format!("{}.{}", sensor.location, sensor.alias)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sensor() -> Sensor {
+        serde_json::from_str(
+            r#"
+            {
+                "alias": "temp_01",
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "poll_interval": 1000,
+                "poll_topic": "temp_01/poll",
+                "read_topic": "temp_01/read",
+                "disconnect_topic": "temp_01/disconnect",
+                "ip_addr": "192.168.1.1",
+                "mac_addr": [0, 1, 2, 3, 4, 255],
+                "location": "sim"
+            }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sensor_reading() {
+        let sensor = sensor();
+
+        let reading = sensor_reading(&sensor, br#""21.5""#, 0);
+        assert_eq!(reading.status, "SUCCESS");
+        assert_eq!(reading.value.as_deref(), Some("21.5"));
+
+        let reading = sensor_reading(&sensor, b"21.5,", 0);
+        assert_eq!(reading.status, "MALFORMED_PAYLOAD");
+        assert_eq!(reading.quality, "BAD_DEVICE_FAILURE");
+
+        // Devices can give their own quality
+        let payload = br#"{"value": "21.5", "quality": "UNCERTAIN_OUT_OF_CALIBRATION"}"#;
+        let reading = sensor_reading(&sensor, payload, 0);
+        assert_eq!(reading.status, "SUCCESS");
+        assert_eq!(reading.quality, "UNCERTAIN_OUT_OF_CALIBRATION");
+        let reading = sensor_reading(&sensor, br#"{"value": "21.5", "quality": "FINE"}"#, 0);
+        assert_eq!(reading.status, "MALFORMED_PAYLOAD");
+
+        let reading = sensor_reading(&sensor, br#""NaN""#, 0);
+        assert_eq!(reading.status, "OUT_OF_RANGE");
+        assert_eq!(reading.value.as_deref(), Some("NaN"));
+
+        let reading = failed_reading(&sensor, &PollFailure::NoResponders, 0);
+        assert_eq!(reading.status, "NO_RESPONDERS");
+        assert_eq!(reading.quality, "BAD_NOT_CONNECTED");
+        assert_eq!(reading.value, None);
+        assert_eq!(sensor_offline(&sensor, 0).status, "OFFLINE");
+    }
+
+    #[test]
+    fn test_calibrated_reading() {
+        let mut sensor = sensor();
+        sensor.calibration = serde_json::from_str(r#"{"offset": -2.5}"#).unwrap();
+
+        let reading = sensor_reading(&sensor, br#""27.5""#, 0);
+        assert_eq!(reading.value.as_deref(), Some("25"));
+        assert_eq!(reading.raw_value.as_deref(), Some("27.5"));
+        assert_eq!(
+            reading.calibration.as_deref(),
+            Some(r#"{"offset":-2.5,"gain":1.0}"#)
+        );
+
+        let reading = sensor_reading(&sensor, br#""OFF""#, 0);
+        assert_eq!(reading.status, "MALFORMED_PAYLOAD");
+        assert_eq!(reading.raw_value, None);
+    }
+
+    #[test]
+    fn test_ranged_reading() {
+        let mut sensor = sensor();
+        sensor.unit = Some("Cel".to_string());
+        sensor.range = serde_json::from_str(r#"{"min": 0, "max": 50}"#).unwrap();
+
+        let reading = sensor_reading(&sensor, br#""21.5""#, 0);
+        assert_eq!(reading.status, "SUCCESS");
+        assert_eq!(reading.unit.as_deref(), Some("Cel"));
+
+        let reading = sensor_reading(&sensor, br#""50.01""#, 0);
+        assert_eq!(reading.status, "OUT_OF_RANGE");
+        assert_eq!(reading.quality, "BAD_SENSOR_FAILURE");
+        assert_eq!(reading.value.as_deref(), Some("50.01"));
+        assert_eq!(reading.unit.as_deref(), Some("Cel"));
+        let reading = sensor_reading(&sensor, br#""ON""#, 0);
+        assert_eq!(reading.status, "MALFORMED_PAYLOAD");
+
+        // The range is for calibrated values
+        sensor.calibration = serde_json::from_str(r#"{"offset": -10}"#).unwrap();
+        let reading = sensor_reading(&sensor, br#""55""#, 0);
+        assert_eq!(reading.status, "SUCCESS");
+        let reading = sensor_reading(&sensor, br#""5""#, 0);
+        assert_eq!(reading.status, "OUT_OF_RANGE");
+        assert_eq!(reading.value.as_deref(), Some("-5"));
+        assert_eq!(reading.raw_value.as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn test_channel_reading() {
+        let mut sensor = sensor();
+        sensor.channels = serde_json::from_str(
+            r#"[
+                {"name": "pm1_0", "unit": "ug/m3", "range": {"min": 0, "max": 1000}},
+                {"name": "pm2_5", "unit": "ug/m3"}
+            ]"#,
+        )
+        .unwrap();
+
+        let reading = sensor_reading(&sensor, br#"{"pm1_0": 12, "pm2_5": "18.5"}"#, 0);
+        assert_eq!(reading.status, "SUCCESS");
+        assert_eq!(reading.channel.as_deref(), Some("pm1_0,pm2_5"));
+        let composite = channel_events(vec![reading.clone()], ChannelEvents::Composite);
+        assert_eq!(composite, vec![reading.clone()]);
+
+        let readings = channel_events(vec![reading], ChannelEvents::PerChannel);
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].channel.as_deref(), Some("pm1_0"));
+        assert_eq!(readings[0].value.as_deref(), Some("12"));
+        assert_eq!(readings[0].unit.as_deref(), Some("ug/m3"));
+        assert_eq!(readings[1].channel.as_deref(), Some("pm2_5"));
+        assert_eq!(readings[1].value.as_deref(), Some("18.5"));
+
+        // Arrays are in the order the channels are declared, and channels are checked separately
+        let reading = sensor_reading(&sensor, b"[1200, 18.5]", 0);
+        assert_eq!(reading.status, "UNCERTAIN");
+        let readings = channel_events(vec![reading], ChannelEvents::PerChannel);
+        assert_eq!(readings[0].status, "OUT_OF_RANGE");
+        assert_eq!(readings[0].value.as_deref(), Some("1200"));
+        assert_eq!(readings[1].status, "SUCCESS");
+
+        let reading = sensor_reading(&sensor, b"[]", 0);
+        assert_eq!(reading.status, "MALFORMED_PAYLOAD");
+        let reading = sensor_reading(&sensor, br#""12""#, 0);
+        assert_eq!(reading.status, "MALFORMED_PAYLOAD");
+        assert_eq!(reading.channel, None);
+        assert_eq!(
+            channel_events(vec![reading], ChannelEvents::PerChannel).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_reading_location() {
+        let mut sensor = sensor();
+        let reading = sensor_reading(&sensor, br#""21.5""#, 0);
+        assert_eq!(reading.source, "sim.temp_01");
+        assert_eq!(reading.gps, None);
+
+        sensor.location = serde_json::from_str(
+            r#"{
+                "enterprise": "acme",
+                "site": "plant_1",
+                "gps": {"latitude": 51.5072, "longitude": -0.1276, "altitude": 11}
+            }"#,
+        )
+        .unwrap();
+        let reading = sensor_reading(&sensor, br#""21.5""#, 0);
+        assert_eq!(reading.source, "acme/plant_1.temp_01");
+        assert_eq!(reading.gps.as_deref(), Some("51.5072,-0.1276,11"));
+        // Every event for the sensor carries its location
+        for event in [
+            failed_reading(&sensor, &PollFailure::Offline, 0),
+            sensor_quarantined(&sensor, 5, "TIMEOUT", 0),
+            sensor_restored(&sensor, 0),
+            config_change(&sensor, &sensor, 0),
+        ] {
+            assert_eq!(event.source, "acme/plant_1.temp_01");
+            assert_eq!(event.gps.as_deref(), Some("51.5072,-0.1276,11"));
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Why a sensor couldn't be read. Each failure has its own reading status, so dashboards can
+//! tell problems with the network between the provider and the sensor (`SUBSCRIBE_ERROR`,
+//! `PUBLISH_ERROR` and `TIMEOUT`) apart from faults in the device itself (`NO_RESPONDERS`,
+//! `MALFORMED_PAYLOAD`, `OUT_OF_RANGE` and `OFFLINE`).
+
+use std::fmt;
+use wasmcloud_interface_polling::reading::STATUS_OFFLINE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PollFailure {
+    /// The provider couldn't subscribe to the sensor's read topic
+    Subscribe(String),
+    /// The provider couldn't publish the poll to the sensor's poll topic
+    Publish(String),
+    /// The sensor didn't respond before the poll timeout
+    Timeout,
+    /// Nothing is subscribed to the sensor's poll topic, so the device isn't connected
+    NoResponders,
+    /// The sensor responded with a payload which isn't a reading
+    MalformedPayload(String),
+    /// The sensor responded with a value it can't have read, e.g. `NaN`
+    OutOfRange(String),
+    /// The sensor's device has announced that it's gone offline
+    Offline,
+}
+
+impl PollFailure {
+    /// Status of the reading recording this failure
+    pub fn status(&self) -> &'static str {
+        match self {
+            PollFailure::Subscribe(_) => "SUBSCRIBE_ERROR",
+            PollFailure::Publish(_) => "PUBLISH_ERROR",
+            PollFailure::Timeout => "TIMEOUT",
+            PollFailure::NoResponders => "NO_RESPONDERS",
+            PollFailure::MalformedPayload(_) => "MALFORMED_PAYLOAD",
+            PollFailure::OutOfRange(_) => "OUT_OF_RANGE",
+            PollFailure::Offline => STATUS_OFFLINE,
+        }
+    }
+
+    /// Value of the reading recording this failure. Only failures where the sensor did respond
+    /// have one, which is what it responded with or why it couldn't be parsed.
+    pub fn value(&self) -> Option<String> {
+        match self {
+            PollFailure::MalformedPayload(detail) | PollFailure::OutOfRange(detail) => {
+                Some(detail.to_owned())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PollFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollFailure::Subscribe(e) => write!(f, "failed to subscribe to read topic: {e}"),
+            PollFailure::Publish(e) => write!(f, "failed to publish poll: {e}"),
+            PollFailure::Timeout => write!(f, "sensor didn't respond before the poll timeout"),
+            PollFailure::NoResponders => write!(f, "nothing is subscribed to the poll topic"),
+            PollFailure::MalformedPayload(e) => write!(f, "malformed payload: {e}"),
+            PollFailure::OutOfRange(value) => write!(f, "value out of range: {value}"),
+            PollFailure::Offline => write!(f, "sensor is offline"),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Protection against devices which publish heartbeats far more often than they should, e.g.
+//! because of a bug which publishes them in a tight loop. Each sensor has a token bucket which
+//! allows `heartbeat_burst` heartbeats at once, refilled at `heartbeat_rate_per_min`, and
+//! identical heartbeats within `heartbeat_coalesce_ms` of the last one accepted are coalesced
+//! into it.
+//!
+//! Heartbeats are limited as they're received, before they're queued or deserialized, so a
+//! flood can't crowd out other sensors' heartbeats. Buckets which have been idle long enough to
+//! refill are forgotten, and at most [`MAX_SOURCES`] are kept, so heartbeats with made up IDs
+//! can't grow the limiter without bound.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::config::PollingConfig;
+
+/// Most sensors the limiter keeps a bucket for, once it's reached the bucket which has been idle
+/// the longest is evicted to make room
+pub const MAX_SOURCES: usize = 10_000;
+/// How often idle buckets are looked for
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What to do with a heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// The heartbeat should be handled
+    Accept,
+    /// The heartbeat is the same as one handled recently, so it can be dropped
+    Coalesced,
+    /// The sensor has exceeded its rate limit, so the heartbeat should be dropped. `first` is
+    /// set for the first heartbeat dropped since the sensor was last within its limit, so a
+    /// single warning is raised for each flood.
+    Limited { first: bool },
+}
+
+#[derive(Debug)]
+struct Source {
+    tokens: f64,
+    refilled_at: Instant,
+    /// Hash of the last heartbeat accepted, and when it was accepted
+    last_heartbeat: Option<(u64, Instant)>,
+    /// Whether a warning has been raised since the bucket was last full
+    limited: bool,
+}
+
+/// Token buckets for every sensor a heartbeat has been received from recently
+#[derive(Debug, Default)]
+pub struct HeartbeatLimiter {
+    sources: HashMap<Uuid, Source>,
+    pruned_at: Option<Instant>,
+}
+
+impl HeartbeatLimiter {
+    /// Decide whether to handle a heartbeat from the sensor
+    pub fn admit(
+        &mut self,
+        sensor_id: Uuid,
+        payload: &[u8],
+        config: &PollingConfig,
+        now: Instant,
+    ) -> Admission {
+        let burst = f64::from(config.heartbeat_burst());
+        if !self.sources.contains_key(&sensor_id) {
+            self.make_room(config, now);
+        }
+        let source = self.sources.entry(sensor_id).or_insert_with(|| Source {
+            tokens: burst,
+            refilled_at: now,
+            last_heartbeat: None,
+            limited: false,
+        });
+
+        let elapsed = now.saturating_duration_since(source.refilled_at);
+        let refill = elapsed.as_secs_f64() * f64::from(config.heartbeat_rate_per_min()) / 60.0;
+        source.tokens = (source.tokens + refill).min(burst);
+        source.refilled_at = now;
+        if source.tokens >= burst {
+            source.limited = false;
+        }
+
+        if source.tokens < 1.0 {
+            let first = !source.limited;
+            source.limited = true;
+            return Admission::Limited { first };
+        }
+        source.tokens -= 1.0;
+
+        let mut hasher = DefaultHasher::new();
+        payload.hash(&mut hasher);
+        let hash = hasher.finish();
+        match source.last_heartbeat {
+            Some((last_hash, accepted_at))
+                if last_hash == hash
+                    && now.saturating_duration_since(accepted_at)
+                        < config.heartbeat_coalesce_window() =>
+            {
+                Admission::Coalesced
+            }
+            _ => {
+                source.last_heartbeat = Some((hash, now));
+                Admission::Accept
+            }
+        }
+    }
+
+    /// Forget idle buckets every [`PRUNE_INTERVAL`], then evict the longest idle bucket if
+    /// there's still no room for another
+    fn make_room(&mut self, config: &PollingConfig, now: Instant) {
+        let due = self.pruned_at.map_or(true, |pruned_at| {
+            now.saturating_duration_since(pruned_at) >= PRUNE_INTERVAL
+        });
+        if due {
+            self.prune(config, now);
+            self.pruned_at = Some(now);
+        }
+        if self.sources.len() < MAX_SOURCES {
+            return;
+        }
+        let idlest = self
+            .sources
+            .iter()
+            .min_by_key(|(_, source)| source.refilled_at)
+            .map(|(id, _)| *id);
+        if let Some(id) = idlest {
+            self.sources.remove(&id);
+        }
+    }
+
+    /// Forget every bucket which would be the same as a new one, because it's been idle long
+    /// enough to refill and for its last heartbeat to be outside the coalesce window
+    fn prune(&mut self, config: &PollingConfig, now: Instant) {
+        let rate_per_min = config.heartbeat_rate_per_min();
+        if rate_per_min == 0 {
+            // Buckets never refill, so forgetting them would lift the limit
+            return;
+        }
+        let refill = Duration::from_secs_f64(
+            f64::from(config.heartbeat_burst()) * 60.0 / f64::from(rate_per_min),
+        );
+        let idle = refill.max(config.heartbeat_coalesce_window());
+        self.sources
+            .retain(|_, source| now.saturating_duration_since(source.refilled_at) < idle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admit() {
+        let config = PollingConfig {
+            heartbeat_burst: Some(3),
+            heartbeat_rate_per_min: Some(60),
+            heartbeat_coalesce_ms: Some(1_000),
+            ..Default::default()
+        };
+        let mut limiter = HeartbeatLimiter::default();
+        let id = Uuid::new_v4();
+        let now = Instant::now();
+
+        assert_eq!(limiter.admit(id, b"a", &config, now), Admission::Accept);
+        assert_eq!(limiter.admit(id, b"a", &config, now), Admission::Coalesced);
+        assert_eq!(limiter.admit(id, b"b", &config, now), Admission::Accept);
+        assert_eq!(
+            limiter.admit(id, b"c", &config, now),
+            Admission::Limited { first: true }
+        );
+        assert_eq!(
+            limiter.admit(id, b"c", &config, now),
+            Admission::Limited { first: false }
+        );
+
+        // One token a second, but the flood isn't over until the bucket is full again
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.admit(id, b"c", &config, later), Admission::Accept);
+        assert_eq!(
+            limiter.admit(id, b"c", &config, later),
+            Admission::Limited { first: false }
+        );
+        let quiet = later + Duration::from_secs(3);
+        assert_eq!(limiter.admit(id, b"c", &config, quiet), Admission::Accept);
+        assert_eq!(limiter.admit(id, b"d", &config, quiet), Admission::Accept);
+        assert_eq!(limiter.admit(id, b"e", &config, quiet), Admission::Accept);
+        assert_eq!(
+            limiter.admit(id, b"f", &config, quiet),
+            Admission::Limited { first: true }
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let config = PollingConfig {
+            heartbeat_burst: Some(3),
+            heartbeat_rate_per_min: Some(60),
+            heartbeat_coalesce_ms: Some(1_000),
+            ..Default::default()
+        };
+        let mut limiter = HeartbeatLimiter::default();
+        let (idle, busy) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Instant::now();
+        limiter.admit(idle, b"a", &config, now);
+        limiter.admit(busy, b"a", &config, now);
+
+        // Buckets refill in 3s, so only the busy sensor's is kept once the limiter is pruned
+        let later = now + PRUNE_INTERVAL;
+        limiter.admit(busy, b"b", &config, later - Duration::from_secs(1));
+        limiter.admit(Uuid::new_v4(), b"a", &config, later);
+        assert_eq!(limiter.sources.len(), 2);
+        assert!(limiter.sources.contains_key(&busy));
+        assert!(!limiter.sources.contains_key(&idle));
+    }
+
+    #[test]
+    fn test_max_sources() {
+        let config = PollingConfig::default();
+        let mut limiter = HeartbeatLimiter::default();
+        let now = Instant::now();
+        let first = Uuid::new_v4();
+        limiter.admit(first, b"a", &config, now);
+        for i in 1..MAX_SOURCES {
+            let at = now + Duration::from_millis(i as u64);
+            limiter.admit(Uuid::new_v4(), b"a", &config, at);
+        }
+        assert_eq!(limiter.sources.len(), MAX_SOURCES);
+
+        // The longest idle bucket makes room for the next sensor
+        let next = Uuid::new_v4();
+        let at = now + Duration::from_secs(10);
+        assert_eq!(limiter.admit(next, b"a", &config, at), Admission::Accept);
+        assert_eq!(limiter.sources.len(), MAX_SOURCES);
+        assert!(!limiter.sources.contains_key(&first));
+        assert!(limiter.sources.contains_key(&next));
+    }
+}
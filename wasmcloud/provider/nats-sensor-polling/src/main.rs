@@ -2,31 +2,66 @@
 //! At the moment, I've only included functionality necessary for my PoC, but in the future I'll
 //! probably change the architecture of my PoC entirely and the functionality of this provider will
 //! be dramatically simplified, with more of the business logic taking place inside actors instead.
+mod cache;
+mod calibration;
 mod config;
+mod control;
+mod drain;
+mod events;
+mod failure;
+mod flood;
+mod micro;
 mod nats;
+mod push;
+mod quarantine;
+mod remote_config;
 mod sensor;
+mod shard;
+mod sparkplug;
+mod time_sync;
+mod trace;
 
-use actor_interfaces::pangea_api::LogEvent;
+use async_nats::service::Service;
+use async_nats::StatusCode;
 use futures::StreamExt;
 use regex::Regex;
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::sync::Arc;
-use std::time::{Duration, UNIX_EPOCH};
-use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
-use tokio::sync::{OwnedSemaphorePermit, RwLock};
-use tracing::{debug, error, instrument};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+use tracing::{debug, error, info_span, instrument, warn, Instrument};
 use uuid::Uuid;
-use wasmbus_rpc::core::HostData;
+use wasmbus_rpc::core::{HealthCheckRequest, HealthCheckResponse, HostData};
 use wasmbus_rpc::{core::LinkDefinition, provider::prelude::*};
+use wasmcloud_interface_polling::control::{
+    CommandRequest, CommandResponse, ConfigRequest, ConfigResponse, Control, ControlReceiver,
+};
+use wasmcloud_interface_polling::encoding::Encoding;
 use wasmcloud_interface_polling::{
     AddPollTargetRequest, AddPollTargetResponse, PollRequest, PollResult, PollSubscriber,
     PollSubscriberSender, Polling, PollingError, PollingReceiver, RemovePollTargetRequest,
-    RemovePollTargetResponse,
+    RemovePollTargetResponse, SensorReading,
 };
 
-use crate::nats::{ConnectionConfig, HeartbeatRx, HeartbeatTx, NatsClient, NatsClientBundle};
-use crate::sensor::{PollInterval, Sensor};
+use crate::cache::LastValues;
+use crate::config::{PollingConfig, SharedConfig};
+use crate::drain::{DisconnectNotice, Drain};
+use crate::events::TargetEvent;
+use crate::failure::PollFailure;
+use crate::micro::ServiceState;
+use crate::nats::{
+    heartbeat_channel, ConnectionConfig, HeartbeatRx, HeartbeatTx, NatsClient, NatsClientBundle,
+};
+use crate::push::PushIngest;
+use crate::quarantine::Quarantine;
+use crate::remote_config::PendingConfigs;
+use crate::sensor::{PollInterval, Sensor, SensorMode};
+use crate::shard::{Route, Shard};
+use crate::sparkplug::{EdgeNodes, Topic};
+use crate::time_sync::ClockStatuses;
 
 type Sensors = Arc<RwLock<HashMap<Uuid, Sensor>>>;
 type Schedule = Arc<RwLock<HashMap<PollInterval, HashSet<Uuid>>>>;
@@ -45,12 +80,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 // The heartbeat_sender needs to be stored here in case extra heartbeat subscriptions
 // are added later. sensors and schedule need to be stored here so they can be cleared
-// when ActorState is dropped, which ensures any scheduled polling tasks are aborted
+// when the actor is closed, and the background tasks are aborted when ActorState is dropped
 struct ActorState {
     client: NatsClientBundle,
     heartbeat_sender: HeartbeatTx,
     sensors: Sensors,   //TODO: does this need to be stored here?
     schedule: Schedule, //TODO: does this need to be stored here?
+    push: Arc<PushIngest>,
+    pending_configs: Arc<PendingConfigs>,
+    last_values: Arc<LastValues>,
+    drain: Arc<Drain>,
+    quarantine: Arc<Quarantine>,
+    clocks: ClockStatuses,
+    /// This instance's membership of the link's shard, if sensors are shared with other instances
+    shard: Option<Arc<Shard>>,
+    /// The link's config, kept so it can be compared against when the link is put again
+    connection: ConnectionConfig,
+    config: SharedConfig,
+    time_sync: Option<tokio::task::JoinHandle<()>>,
+    /// The link's NATS micro service, if it's registered as one
+    service: Option<Service>,
     handles: Vec<tokio::task::JoinHandle<()>>,
     ld: LinkDefinition, //TODO: does this need to be stored here?
 }
@@ -61,20 +110,18 @@ impl Drop for ActorState {
         for handle in &self.handles {
             handle.abort();
         }
-
-        // TODO: will using blocking_write() here cause issues if other actors are still running?
-        let mut write_sensors = self.sensors.blocking_write();
-        write_sensors.clear();
-        drop(write_sensors);
-
-        let mut write_schedule = self.schedule.blocking_write();
-        write_schedule.clear();
+        if let Some(handle) = &self.time_sync {
+            handle.abort();
+        }
+        if let Some(service) = self.service.take() {
+            tokio::task::spawn(service.stop());
+        }
     }
 }
 
 /// Implementation for wasmcloud:polling
 #[derive(Default, Clone, Provider)]
-#[services(Polling)]
+#[services(Polling, Control)]
 struct NatsSensorPollingProvider {
     actors: Arc<RwLock<HashMap<String, ActorState>>>,
     default_config: ConnectionConfig,
@@ -90,6 +137,7 @@ impl NatsSensorPollingProvider {
                 Ok(Self::default())
             } else {
                 let config: ConnectionConfig = serde_json::from_str(config)?;
+                config.polling.validate().map_err(anyhow::Error::msg)?;
                 Ok(Self {
                     default_config: config,
                     ..Default::default()
@@ -104,23 +152,71 @@ impl NatsSensorPollingProvider {
     /// and regularly polling sensors so results can be sent to the actors given in the ld.
     ///
     /// Returns the handles for these background tasks.
+    #[allow(clippy::too_many_arguments)]
     #[instrument(level = "info", skip_all, fields(actor_id = %ld.actor_id))]
     async fn run(
         ld: LinkDefinition,
         sensors: Sensors,
         schedule: Schedule,
+        push: Arc<PushIngest>,
+        pending_configs: Arc<PendingConfigs>,
+        last_values: Arc<LastValues>,
+        drain: Arc<Drain>,
+        quarantine: Arc<Quarantine>,
         heartbeats: HeartbeatRx,
         client: NatsClient,
+        config: SharedConfig,
+        shard: Option<Arc<Shard>>,
     ) -> Vec<tokio::task::JoinHandle<()>> {
         let mut handles = vec![];
 
+        if let Some(shard) = &shard {
+            handles.push(tokio::task::spawn(shard.clone().run(client.clone())));
+            handles.push(tokio::task::spawn(Self::release_unowned_sensors(
+                ld.clone(),
+                sensors.clone(),
+                schedule.clone(),
+                push.clone(),
+                shard.clone(),
+            )));
+        }
+
+        let sparkplug_subject = config.read().await.sparkplug_subject.clone();
+        if let Some(subject) = sparkplug_subject {
+            handles.push(tokio::task::spawn(Self::listen_sparkplug(
+                ld.clone(),
+                sensors.clone(),
+                last_values.clone(),
+                client.clone(),
+                config.clone(),
+                shard.clone(),
+                subject,
+            )));
+        }
+
         //TODO: listen for heartbeats
         handles.push(tokio::task::spawn(Self::listen_heartbeats(
             ld.clone(),
             sensors.clone(),
             schedule.clone(),
+            push.clone(),
+            pending_configs,
+            last_values.clone(),
+            drain.clone(),
+            quarantine,
             heartbeats,
             client,
+            config.clone(),
+            shard,
+        )));
+
+        handles.push(tokio::task::spawn(Self::flush_push_readings(
+            ld,
+            sensors,
+            push,
+            last_values,
+            drain,
+            config,
         )));
 
         // //TODO: scheduled polling
@@ -134,13 +230,21 @@ impl NatsSensorPollingProvider {
         handles
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[instrument(level = "info", skip_all, fields(actor_id = %ld.actor_id))]
     async fn listen_heartbeats(
         ld: LinkDefinition,
         sensors: Sensors,
         schedule: Schedule,
+        push: Arc<PushIngest>,
+        pending_configs: Arc<PendingConfigs>,
+        last_values: Arc<LastValues>,
+        drain: Arc<Drain>,
+        quarantine: Arc<Quarantine>,
         mut heartbeats: HeartbeatRx,
         client: NatsClient,
+        config: SharedConfig,
+        shard: Option<Arc<Shard>>,
     ) {
         loop {
             let (_, msg, _permit) = if let Some(heartbeat) = heartbeats.recv().await {
@@ -156,66 +260,344 @@ impl NatsSensorPollingProvider {
                 }
             };
 
+            if let Some(shard) = &shard {
+                match shard.route(sensor_info.id).await {
+                    Route::Local => {}
+                    Route::Forward(owner) => {
+                        // Heartbeats are only forwarded once, so instances which briefly disagree
+                        // about the owner can't bounce them back and forth
+                        if !shard.is_forwarded(&msg.subject) {
+                            shard.forward(&client, owner, msg.payload.to_vec()).await;
+                        }
+                        let released =
+                            Self::release_sensor(&sensors, &schedule, &push, sensor_info.id).await;
+                        if let Some(sensor) = released {
+                            Self::send_target_event(TargetEvent::Lost, &sensor, &ld).await;
+                        }
+                        continue;
+                    }
+                    // The sensor will be picked up from its next heartbeat
+                    Route::Wait => continue,
+                }
+            }
+
             sensor_info.poll_topic = mqtt_to_nats(sensor_info.poll_topic);
             sensor_info.read_topic = mqtt_to_nats(sensor_info.read_topic);
             sensor_info.disconnect_topic = mqtt_to_nats(sensor_info.disconnect_topic);
+            sensor_info.command_topic = sensor_info.command_topic.map(mqtt_to_nats);
+            sensor_info.ack_topic = sensor_info.ack_topic.map(mqtt_to_nats);
+            sensor_info.config_topic = sensor_info.config_topic.map(mqtt_to_nats);
 
-            let read_sensors = sensors.read().await;
-            if !read_sensors.contains_key(&sensor_info.id) {
-                drop(read_sensors);
+            let id = sensor_info.id;
+            let previous = sensors.read().await.get(&id).cloned();
+            match previous {
+                None => {
+                    sensors.write().await.insert(id, sensor_info.clone());
+                    let started = Self::start_reading(
+                        &ld,
+                        &sensors,
+                        &schedule,
+                        &push,
+                        &last_values,
+                        &drain,
+                        &quarantine,
+                        &client,
+                        &config,
+                        &sensor_info,
+                    )
+                    .await;
+                    if let Err(e) = started {
+                        error!("Failed to subscribe to push-mode sensor {id}: {e:?}");
+                        sensors.write().await.remove(&id);
+                        continue;
+                    }
+                    Self::send_target_event(TargetEvent::Discovered, &sensor_info, &ld).await;
 
-                let id = sensor_info.id.clone();
-                let poll_interval = sensor_info.poll_interval;
+                    // TODO: listen for disconnect message (not needed for my PoC)
+                }
+                Some(previous) => {
+                    if !sensor_info.changed_from(&previous) {
+                        continue;
+                    }
 
-                let mut write_sensors = sensors.write().await;
-                write_sensors.insert(id, sensor_info.clone());
-                drop(write_sensors);
+                    // The sensor has been reconfigured, either remotely or by reflashing it, so
+                    // it's moved to its new schedule or subscription if it's read differently
+                    sensors.write().await.insert(id, sensor_info.clone());
+                    if sensor_info.needs_restart(&previous) {
+                        Self::stop_reading(&schedule, &push, &previous).await;
+                        let started = Self::start_reading(
+                            &ld,
+                            &sensors,
+                            &schedule,
+                            &push,
+                            &last_values,
+                            &drain,
+                            &quarantine,
+                            &client,
+                            &config,
+                            &sensor_info,
+                        )
+                        .await;
+                        if let Err(e) = started {
+                            error!("Failed to subscribe to push-mode sensor {id}: {e:?}");
+                        }
+                    }
 
-                let mut write_schedule = schedule.write().await;
-                if let Some(sensor_ids) = write_schedule.get_mut(&poll_interval) {
-                    if sensor_ids.is_empty() {
-                        sensor_ids.insert(id);
-                        tokio::task::spawn(Self::scheduled_polling(
-                            ld.clone(),
-                            sensors.clone(),
-                            schedule.clone(),
-                            client.clone(),
-                            poll_interval,
-                        ));
-                    } else {
-                        sensor_ids.insert(id);
+                    let change = events::config_change(&previous, &sensor_info, events::now());
+                    let encoding = config.read().await.encoding();
+                    Self::send_readings(vec![change], &ld, encoding).await;
+                    Self::send_target_event(TargetEvent::Changed, &sensor_info, &ld).await;
+                    pending_configs.notify(&sensor_info).await;
+                }
+            }
+        }
+    }
+
+    /// Register the metrics of Sparkplug B edge nodes as sensors when they're born, and send
+    /// their data and deaths to the actor. Every instance in a shard follows every node's births,
+    /// so it can resolve the metrics of any sensors it takes over.
+    #[instrument(level = "info", skip_all, fields(actor_id = %ld.actor_id, subject = %subject))]
+    async fn listen_sparkplug(
+        ld: LinkDefinition,
+        sensors: Sensors,
+        last_values: Arc<LastValues>,
+        client: NatsClient,
+        config: SharedConfig,
+        shard: Option<Arc<Shard>>,
+        subject: String,
+    ) {
+        let mut subscriber = match client.subscribe(subject.clone()).await {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                error!("Error subscribing to Sparkplug B subject {subject}: {e:?}");
+                return;
+            }
+        };
+
+        let mut edge_nodes = EdgeNodes::default();
+        while let Some(msg) = subscriber.next().await {
+            let topic = match Topic::parse(&msg.subject) {
+                Some(topic) => topic,
+                None => continue,
+            };
+            let update = match edge_nodes.handle(&topic, &msg.payload) {
+                Ok(update) => update,
+                Err(e) => {
+                    warn!("Ignoring Sparkplug B message on {}: {e}", msg.subject);
+                    continue;
+                }
+            };
+
+            if let Some((rebirth_subject, payload)) = update.rebirth {
+                if owns(&shard, topic.node_id()).await {
+                    debug!("Requesting rebirth from Sparkplug B node {}", topic.node);
+                    if let Err(e) = client.publish(rebirth_subject, payload.into()).await {
+                        error!("Error requesting Sparkplug B rebirth: {e:?}");
                     }
-                } else {
-                    let mut sensor_ids = HashSet::new();
-                    sensor_ids.insert(id);
-                    write_schedule.insert(poll_interval, sensor_ids);
-                    tokio::task::spawn(Self::scheduled_polling(
-                        ld.clone(),
-                        sensors.clone(),
-                        schedule.clone(),
-                        client.clone(),
-                        poll_interval,
-                    ));
                 }
-                drop(write_schedule);
+            }
 
-                // TODO: listen for disconnect message (not needed for my PoC)
+            for sensor in update.online {
+                if !owns(&shard, sensor.id).await {
+                    continue;
+                }
+                let discovered = match sensors.write().await.entry(sensor.id) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(sensor.clone());
+                        true
+                    }
+                    Entry::Occupied(_) => false,
+                };
+                if discovered {
+                    Self::send_target_event(TargetEvent::Discovered, &sensor, &ld).await;
+                }
+            }
+            for sensor in update.offline {
+                last_values.remove(sensor.id).await;
+                if sensors.write().await.remove(&sensor.id).is_some() {
+                    Self::send_target_event(TargetEvent::Lost, &sensor, &ld).await;
+                }
+            }
+
+            let mut events = Vec::with_capacity(update.events.len());
+            for (sensor_id, event) in update.events {
+                if owns(&shard, sensor_id).await {
+                    events.push(event);
+                }
+            }
+            if !events.is_empty() {
+                last_values.record(&events).await;
+                let encoding = config.read().await.encoding();
+                Self::send_readings(events, &ld, encoding).await;
+            }
+        }
+    }
+
+    /// Add the sensor to the schedule for its poll interval, starting a polling task for that
+    /// interval if there isn't one already
+    #[allow(clippy::too_many_arguments)]
+    async fn schedule_sensor(
+        ld: &LinkDefinition,
+        sensors: &Sensors,
+        schedule: &Schedule,
+        last_values: &Arc<LastValues>,
+        drain: &Arc<Drain>,
+        quarantine: &Arc<Quarantine>,
+        client: &NatsClient,
+        config: &SharedConfig,
+        id: Uuid,
+        poll_interval: PollInterval,
+    ) {
+        let mut write_schedule = schedule.write().await;
+        let sensor_ids = write_schedule.entry(poll_interval).or_default();
+        if sensor_ids.is_empty() {
+            tokio::task::spawn(Self::scheduled_polling(
+                ld.clone(),
+                sensors.clone(),
+                schedule.clone(),
+                last_values.clone(),
+                drain.clone(),
+                quarantine.clone(),
+                client.clone(),
+                config.clone(),
+                poll_interval,
+            ));
+        }
+        sensor_ids.insert(id);
+    }
+
+    /// Remove the sensor from the schedule for its poll interval. The polling task for that
+    /// interval ends on its next tick if there are no sensors left.
+    async fn unschedule_sensor(schedule: &Schedule, id: Uuid, poll_interval: PollInterval) {
+        let mut write_schedule = schedule.write().await;
+        if let Some(sensor_ids) = write_schedule.get_mut(&poll_interval) {
+            sensor_ids.remove(&id);
+            if sensor_ids.is_empty() {
+                write_schedule.remove(&poll_interval);
             }
         }
     }
 
+    /// Start reading a sensor, either by scheduling it to be polled or, since push-mode sensors
+    /// publish readings on their own, by subscribing to its read topic
+    #[allow(clippy::too_many_arguments)]
+    async fn start_reading(
+        ld: &LinkDefinition,
+        sensors: &Sensors,
+        schedule: &Schedule,
+        push: &Arc<PushIngest>,
+        last_values: &Arc<LastValues>,
+        drain: &Arc<Drain>,
+        quarantine: &Arc<Quarantine>,
+        client: &NatsClient,
+        config: &SharedConfig,
+        sensor: &Sensor,
+    ) -> RpcResult<()> {
+        match sensor.mode {
+            SensorMode::Poll => {
+                Self::schedule_sensor(
+                    ld,
+                    sensors,
+                    schedule,
+                    last_values,
+                    drain,
+                    quarantine,
+                    client,
+                    config,
+                    sensor.id,
+                    sensor.poll_interval,
+                )
+                .await;
+                Ok(())
+            }
+            SensorMode::Push => push.subscribe(sensor, client, config.clone()).await,
+        }
+    }
+
+    /// Stop reading a sensor, undoing [`start_reading`](Self::start_reading)
+    async fn stop_reading(schedule: &Schedule, push: &PushIngest, sensor: &Sensor) {
+        match sensor.mode {
+            SensorMode::Poll => {
+                Self::unschedule_sensor(schedule, sensor.id, sensor.poll_interval).await
+            }
+            SensorMode::Push => push.unsubscribe(sensor.id).await,
+        }
+    }
+
+    /// Stop polling a sensor which is now owned by another instance, returning it if this instance
+    /// was polling it so the actor can be told it's been lost. The instance which owns it now
+    /// tells the actor it's been discovered once it takes it over.
+    async fn release_sensor(
+        sensors: &Sensors,
+        schedule: &Schedule,
+        push: &PushIngest,
+        id: Uuid,
+    ) -> Option<Sensor> {
+        let released = sensors.write().await.remove(&id)?;
+        debug!("Released sensor {id} to another instance");
+        Self::stop_reading(schedule, push, &released).await;
+        Some(released)
+    }
+
+    /// Release every sensor this instance no longer owns whenever an instance joins or leaves
+    /// the shard. Sensors gained from an instance which left are picked up from their next
+    /// heartbeat.
+    #[instrument(level = "info", skip_all, fields(instance_id = %shard.instance_id))]
+    async fn release_unowned_sensors(
+        ld: LinkDefinition,
+        sensors: Sensors,
+        schedule: Schedule,
+        push: Arc<PushIngest>,
+        shard: Arc<Shard>,
+    ) {
+        let mut changes = shard.changes();
+        while changes.changed().await.is_ok() {
+            for sensor in Self::release_unowned(&sensors, &schedule, &push, &shard).await {
+                Self::send_target_event(TargetEvent::Lost, &sensor, &ld).await;
+            }
+        }
+    }
+
+    /// Release every sensor the shard says this instance doesn't own, returning them
+    async fn release_unowned(
+        sensors: &Sensors,
+        schedule: &Schedule,
+        push: &PushIngest,
+        shard: &Shard,
+    ) -> Vec<Sensor> {
+        let sensor_ids: Vec<Uuid> = sensors.read().await.keys().copied().collect();
+        let mut released = Vec::new();
+        for id in sensor_ids {
+            if !shard.owns(id).await {
+                released.extend(Self::release_sensor(sensors, schedule, push, id).await);
+            }
+        }
+        released
+    }
+
     #[instrument(level = "info", skip_all, fields(actor_id = %ld.actor_id))]
     async fn scheduled_polling(
         ld: LinkDefinition,
         sensors: Sensors,
         schedule: Schedule,
+        last_values: Arc<LastValues>,
+        drain: Arc<Drain>,
+        quarantine: Arc<Quarantine>,
         client: NatsClient,
+        config: SharedConfig,
         poll_interval: PollInterval,
     ) {
         let mut poll_clock = tokio::time::interval(Duration::from_millis(poll_interval));
         loop {
             poll_clock.tick().await;
 
+            // No new cycles are started once the provider is shutting down, but a cycle which
+            // has already started is finished so its readings still reach the actor
+            let _in_flight = match drain.start() {
+                Some(in_flight) => in_flight,
+                None => break,
+            };
+
             // If there is no schedule for this poll interval anymore, end the task
             let sensor_ids = {
                 let read_schedule = schedule.read().await;
@@ -245,105 +627,252 @@ impl NatsSensorPollingProvider {
                     .collect::<Vec<Sensor>>()
             };
 
-            // TODO: handle the collapse of the spacetime continuum
-            let timestamp = std::time::SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("If time has gone backwards then we have bigger problems than this error.")
-                .as_secs();
+            // Quarantined sensors are only polled when their next probe is due
+            let now = Instant::now();
+            let mut due = Vec::with_capacity(sensors.len());
+            for sensor in sensors {
+                if quarantine.due(sensor.id, now).await {
+                    due.push(sensor);
+                }
+            }
+            let sensors = due;
+
+            let timestamp = events::now();
+            // Read for every cycle so changes to the link's config apply to running schedules
+            let (timeout, encoding, channel_events, quarantine_after, probe_interval) = {
+                let config = config.read().await;
+                (
+                    config.poll_timeout(),
+                    config.encoding(),
+                    config.channel_events(),
+                    config.quarantine_after(),
+                    config.quarantine_probe_interval(),
+                )
+            };
+            let cycle = info_span!("poll_cycle", poll_interval, sensors = sensors.len());
 
+            // Each reading gets its own trace, from the poll through to the audit log write. The
+            // readings are still sent to the actor as one batch, so each carries its own trace
+            // context for the actor to continue.
             let readings = futures::stream::iter(sensors)
-                .map(|s| async { Self::get_sensor_reading(s, &client, timestamp).await })
+                .map(|s| {
+                    let span = info_span!(
+                        parent: None,
+                        "sensor_reading",
+                        sensor_id = %s.id,
+                        alias = %s.alias
+                    );
+                    span.follows_from(&cycle);
+                    let (client, last_values, quarantine) = (&client, &last_values, &quarantine);
+                    async move {
+                        let reading =
+                            Self::get_sensor_reading(s.clone(), client, timestamp, timeout).await;
+                        last_values.record(std::slice::from_ref(&reading)).await;
+                        let readings = quarantine
+                            .record(
+                                &s,
+                                reading,
+                                quarantine_after,
+                                probe_interval,
+                                Instant::now(),
+                            )
+                            .await;
+                        let traceparent = trace::traceparent();
+                        readings
+                            .into_iter()
+                            .map(|reading| SensorReading {
+                                traceparent: traceparent.clone(),
+                                ..reading
+                            })
+                            .collect::<Vec<SensorReading>>()
+                    }
+                    .instrument(span)
+                })
                 .buffered(20)
-                .collect::<Vec<LogEvent>>()
+                .concat()
+                .instrument(cycle.clone())
                 .await;
 
-            Self::send_readings(readings, &ld).await
+            if !readings.is_empty() {
+                let readings = events::channel_events(readings, channel_events);
+                Self::send_readings(readings, &ld, encoding)
+                    .instrument(cycle)
+                    .await
+            }
         }
     }
 
-    async fn get_sensor_reading(sensor: Sensor, client: &NatsClient, timestamp: u64) -> LogEvent {
-        let mut status = "SUCCESS";
-        let reading: String = match Self::poll_sensor(sensor.clone(), client).await {
-            Some(bytes) => match serde_json::from_slice(&bytes) {
-                Ok(reading) => reading,
-                Err(e) => {
-                    status = "VALUE_ERROR";
-                    e.to_string()
+    async fn get_sensor_reading(
+        sensor: Sensor,
+        client: &NatsClient,
+        timestamp: u64,
+        timeout: Duration,
+    ) -> SensorReading {
+        match Self::poll_sensor(sensor.clone(), client, timeout).await {
+            Ok(payload) => events::sensor_reading(&sensor, &payload, timestamp),
+            Err(failure) => {
+                debug!(sensor_id = %sensor.id, "Poll failed: {failure}");
+                events::failed_reading(&sensor, &failure, timestamp)
+            }
+        }
+    }
+
+    /// The sensors with the given IDs, or every sensor if there aren't any. Fails with the first
+    /// ID which isn't a registered sensor.
+    async fn select_sensors(
+        sensors: &Sensors,
+        ids: Option<Vec<Uuid>>,
+    ) -> Result<Vec<Sensor>, Uuid> {
+        let read_sensors = sensors.read().await;
+        match ids {
+            Some(ids) => ids
+                .into_iter()
+                .map(|id| read_sensors.get(&id).cloned().ok_or(id))
+                .collect(),
+            None => Ok(read_sensors.values().cloned().collect()),
+        }
+    }
+
+    /// Read each sensor on demand, or answer from its cached reading if that's no older than
+    /// `max_age`. Push-mode sensors can't be polled, so they answer with their last reading,
+    /// marked as the last known value if it's older than `max_age`.
+    async fn read_sensors(
+        sensors: Vec<Sensor>,
+        client: &NatsClient,
+        last_values: &LastValues,
+        timeout: Duration,
+        max_age: Option<u64>,
+    ) -> Vec<SensorReading> {
+        let timestamp = events::now();
+        futures::future::join_all(sensors.into_iter().map(|sensor| async move {
+            let cached = match (sensor.mode, max_age) {
+                (SensorMode::Poll, Some(max_age)) => {
+                    last_values.fresh(sensor.id, max_age, timestamp).await
+                }
+                (SensorMode::Poll, None) => None,
+                // The last reading is the best there is for a push-mode sensor
+                (SensorMode::Push, _) => {
+                    last_values.last_known(sensor.id, max_age, timestamp).await
+                }
+            };
+            match (cached, sensor.mode) {
+                (Some(reading), _) => reading,
+                (None, SensorMode::Push) => {
+                    events::sensor_event(&sensor, "STALE_DATA", None, timestamp)
+                }
+                (None, SensorMode::Poll) => {
+                    let reading =
+                        Self::get_sensor_reading(sensor, client, timestamp, timeout).await;
+                    last_values.record(std::slice::from_ref(&reading)).await;
+                    reading
                 }
-            },
-            None => {
-                status = "COMM_ERROR";
-                "N/A".to_string()
             }
-        };
+        }))
+        .await
+    }
+
+    /// Regularly send any readings buffered from push-mode sensors to the actor
+    #[instrument(level = "info", skip_all, fields(actor_id = %ld.actor_id))]
+    async fn flush_push_readings(
+        ld: LinkDefinition,
+        sensors: Sensors,
+        push: Arc<PushIngest>,
+        last_values: Arc<LastValues>,
+        drain: Arc<Drain>,
+        config: SharedConfig,
+    ) {
+        let mut flush_interval = config.read().await.push_flush_interval();
+        let mut flush_clock = tokio::time::interval(flush_interval);
+        loop {
+            flush_clock.tick().await;
+
+            // The last readings are flushed by the shutdown itself
+            let _in_flight = match drain.start() {
+                Some(in_flight) => in_flight,
+                None => break,
+            };
 
-        // TODO: - add value_type field which desers to an enum, to handle floats/ints etc without needing
-        //         to convert to a string
-        //       - use timestamp from sensor after configuring RTC on pico-w
-        let source = format!("{}.{}", sensor.location, sensor.alias);
+            let (interval, stale_intervals, encoding, channel_events) = {
+                let config = config.read().await;
+                (
+                    config.push_flush_interval(),
+                    config.push_stale_intervals(),
+                    config.encoding(),
+                    config.channel_events(),
+                )
+            };
+            if interval != flush_interval {
+                // The link has been reconfigured, so restart the clock with the new interval
+                flush_interval = interval;
+                flush_clock =
+                    tokio::time::interval_at(Instant::now() + flush_interval, flush_interval);
+            }
 
-        LogEvent {
-            timestamp: Some(timestamp.to_string()),
-            message: format!("{}: {}", source, reading),
-            source: Some(source.clone()),
-            status: Some(status.to_string()),
-            action: Some("SENSOR_READING".to_string()),
-            new: Some(reading),
-            ..Default::default()
+            let readings = push.drain(&sensors, stale_intervals).await;
+            if !readings.is_empty() {
+                last_values.record(&readings).await;
+                let readings = events::channel_events(readings, channel_events);
+                Self::send_readings(readings, &ld, encoding).await
+            }
         }
     }
 
-    async fn poll_sensor(sensor: Sensor, client: &NatsClient) -> Option<Vec<u8>> {
-        const TIMEOUT_MS: u64 = 500;
+    /// Poll the sensor and wait for its response, which is the reading's payload
+    async fn poll_sensor(
+        sensor: Sensor,
+        client: &NatsClient,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, PollFailure> {
         let poll_topic = sensor.poll_topic.to_owned();
         let read_topic = sensor.read_topic.to_owned();
-        let mut subscriber = match client.subscribe(read_topic.to_owned()).await {
-            Ok(s) => s,
-            Err(e) => {
-                error!("Error subscribing to poll response for topic {read_topic}: {e:?}");
-                return None;
-            }
-        };
+        let mut subscriber = client.subscribe(read_topic.to_owned()).await.map_err(|e| {
+            error!("Error subscribing to poll response for topic {read_topic}: {e:?}");
+            PollFailure::Subscribe(e.to_string())
+        })?;
 
-        if let Err(e) = subscriber.unsubscribe_after(1).await {
+        subscriber.unsubscribe_after(1).await.map_err(|e| {
             error!("Error unsubscribing from read topic: {e:?}");
-            return None;
-        }
-        if let Err(e) = client.publish(poll_topic, "poll".into()).await {
-            error!("Error polling sensor: {e:?}");
-            return None;
-        }
+            PollFailure::Subscribe(e.to_string())
+        })?;
+        // Sensors which support headers can continue the reading's trace. The read topic is
+        // also the reply subject, so the server tells us there if nothing is listening for polls.
+        client
+            .publish_with_reply_and_headers(
+                poll_topic,
+                read_topic,
+                trace::span_headers(),
+                "poll".into(),
+            )
+            .await
+            .map_err(|e| {
+                error!("Error polling sensor: {e:?}");
+                PollFailure::Publish(e.to_string())
+            })?;
 
-        tokio::time::timeout(Duration::from_millis(TIMEOUT_MS), async move {
-            if let Some(message) = subscriber.next().await {
-                Some(message.payload.to_vec())
-            } else {
-                None
+        match tokio::time::timeout(timeout, subscriber.next()).await {
+            Ok(Some(message)) if message.status == Some(StatusCode::NO_RESPONDERS) => {
+                Err(PollFailure::NoResponders)
             }
-        })
-        .await
-        .unwrap_or(None)
+            Ok(Some(message)) => Ok(message.payload.to_vec()),
+            Ok(None) | Err(_) => Err(PollFailure::Timeout),
+        }
     }
 
-    async fn send_readings(readings: Vec<LogEvent>, ld: &LinkDefinition) {
+    async fn send_readings(readings: Vec<SensorReading>, ld: &LinkDefinition, encoding: Encoding) {
         // TODO: proper error handling
-        let poll_result =
-            match serde_json::to_vec(&readings).map_err(|e| RpcError::Ser(e.to_string())) {
-                Ok(blob) => PollResult {
-                    data: Some(blob),
-                    error: None,
-                },
-                Err(e) => PollResult {
-                    data: None,
-                    error: Some(PollingError {
-                        description: Some(e.to_string()),
-                        error_type: "BLOB_SER".to_string(),
-                    }),
-                },
-            };
+        let poll_result = match PollResult::encoded(&readings, encoding) {
+            Ok(poll_result) => poll_result,
+            Err(e) => PollResult {
+                error: Some(PollingError {
+                    description: Some(e.to_string()),
+                    error_type: "BLOB_SER".to_string(),
+                }),
+                ..Default::default()
+            },
+        };
 
         let actor = PollSubscriberSender::for_actor(ld);
-        if let Err(e) = actor.poll_rx(&Context::default(), &poll_result).await {
+        if let Err(e) = actor.poll_rx(&trace::actor_context(), &poll_result).await {
             error!(
                 error = %e,
                 "Unable to send subscription"
@@ -351,22 +880,228 @@ impl NatsSensorPollingProvider {
         };
     }
 
+    /// Tell the actor a sensor has been discovered, changed or lost
+    async fn send_target_event(event: TargetEvent, sensor: &Sensor, ld: &LinkDefinition) {
+        let descriptor = events::descriptor(sensor, events::now());
+        let actor = PollSubscriberSender::for_actor(ld);
+        let ctx = trace::actor_context();
+        let result = match event {
+            TargetEvent::Discovered => actor.target_discovered(&ctx, &descriptor).await,
+            TargetEvent::Changed => actor.target_changed(&ctx, &descriptor).await,
+            TargetEvent::Lost => actor.target_lost(&ctx, &descriptor).await,
+        };
+        if let Err(e) = result {
+            error!(error = %e, sensor_id = %sensor.id, "Unable to send {event:?} to actor");
+        }
+    }
+
+    /// Answer time sync requests on the subject from the actor's config, if it has one
+    fn start_time_sync(actor: &ActorState) -> Option<tokio::task::JoinHandle<()>> {
+        let subject = actor.connection.polling.time_sync_subject.clone()?;
+        Some(tokio::task::spawn(time_sync::serve(
+            subject,
+            actor.client.client.clone(),
+            actor.sensors.clone(),
+            actor.clocks.clone(),
+        )))
+    }
+
+    /// Register the link as a NATS micro service on the subject from its config, if it has one
+    async fn start_service(actor: &mut ActorState) {
+        let subject = match actor.connection.polling.service_subject.clone() {
+            Some(subject) => subject,
+            None => return,
+        };
+        let state = ServiceState {
+            sensors: actor.sensors.clone(),
+            schedule: actor.schedule.clone(),
+            last_values: actor.last_values.clone(),
+            drain: actor.drain.clone(),
+            client: actor.client.client.clone(),
+            config: actor.config.clone(),
+            shard: actor.shard.clone(),
+        };
+        match micro::start(&subject, &actor.ld.actor_id, state).await {
+            Ok((service, handles)) => {
+                actor.service = Some(service);
+                actor.handles.extend(handles);
+            }
+            Err(e) => error!("Failed to register NATS service on {subject}: {e:?}"),
+        }
+    }
+
+    /// Apply updated link values to an actor which is already linked. Heartbeat subscriptions are
+    /// added or closed as needed, and the polling config is swapped for the running tasks, so
+    /// no sensors or schedules are lost. Values which change settings that are only used when the
+    /// link is first put are rejected, returning false, so the link never claims settings it
+    /// isn't using.
+    #[instrument(level = "info", skip_all, fields(actor_id = %ld.actor_id))]
+    async fn reconfigure(&self, config: ConnectionConfig, ld: &LinkDefinition) -> RpcResult<bool> {
+        if let Err(e) = config.polling.validate() {
+            error!("Rejecting invalid link values: {e}");
+            return Ok(false);
+        }
+        // The actors lock isn't held while subscribing, so other links aren't held up by NATS
+        let (client, heartbeat_tx, subscriptions, missing) = {
+            let actors = self.actors.read().await;
+            let actor = match actors.get(&ld.actor_id) {
+                Some(actor) => actor,
+                None => return Ok(false),
+            };
+            if let Some(setting) = unchangeable_setting(&actor.connection, &config) {
+                error!(
+                    "Rejecting link values which change {setting}, it can't be changed on a live \
+                    link, delete and put the link again to change it"
+                );
+                return Ok(false);
+            }
+            let subscriptions = Self::heartbeat_subscriptions(&config, actor.shard.as_deref());
+            let missing = actor.client.missing_subscriptions(&subscriptions);
+            (
+                actor.client.client.clone(),
+                actor.heartbeat_sender.clone(),
+                subscriptions,
+                missing,
+            )
+        };
+        let added = NatsClientBundle::subscribe_all(&client, ld, &missing, &heartbeat_tx).await?;
+
+        let mut actors = self.actors.write().await;
+        let actor = match actors.get_mut(&ld.actor_id) {
+            Some(actor) => actor,
+            None => {
+                debug!(
+                    "Link for actor {} was deleted while reconfiguring",
+                    ld.actor_id
+                );
+                for (_, handle) in added {
+                    handle.abort();
+                }
+                return Ok(false);
+            }
+        };
+        actor.client.replace_subscriptions(&subscriptions, added);
+        *actor.config.write().await = config.polling.clone();
+
+        let time_sync_changed =
+            actor.connection.polling.time_sync_subject != config.polling.time_sync_subject;
+        actor.connection = config;
+        if time_sync_changed {
+            if let Some(handle) = actor.time_sync.take() {
+                handle.abort();
+            }
+            actor.time_sync = Self::start_time_sync(actor);
+        }
+
+        debug!("Reconfigured link for actor {}", ld.actor_id);
+        Ok(true)
+    }
+
+    /// Heartbeat subscriptions for the config, along with the subject other instances forward
+    /// heartbeats to if sensors are sharded
+    fn heartbeat_subscriptions(config: &ConnectionConfig, shard: Option<&Shard>) -> Vec<String> {
+        let mut subscriptions = config.subscriptions().to_vec();
+        if let Some(shard) = shard {
+            subscriptions.push(shard.forward_subject(shard.instance_id));
+        }
+        subscriptions
+    }
+
+    /// Connect to NATS for the link and subscribe to its heartbeats, returning the actor's state
+    /// and the queue its heartbeats are received on
     async fn connect(
         &self,
         cfg: ConnectionConfig,
         ld: &LinkDefinition,
-        heartbeat_tx: HeartbeatTx,
-    ) -> Result<ActorState, RpcError> {
-        let nats_client_bundle = NatsClientBundle::connect(cfg, ld, heartbeat_tx.clone()).await?;
+    ) -> Result<(ActorState, HeartbeatRx), RpcError> {
+        let connection = cfg.clone();
+        let config = Arc::new(RwLock::new(cfg.polling.clone()));
+        let (heartbeat_tx, heartbeat_rx) =
+            heartbeat_channel(cfg.polling.heartbeat_queue_size(), config.clone());
+        let shard_lease = cfg.polling.shard_lease();
+        let shard = cfg
+            .polling
+            .shard_subject
+            .as_ref()
+            .map(|subject| Arc::new(Shard::new(subject, &ld.actor_id, shard_lease)));
+        let subscriptions = Self::heartbeat_subscriptions(&cfg, shard.as_deref());
+        let mut nats_client_bundle =
+            NatsClientBundle::connect(cfg, ld, heartbeat_tx.clone()).await?;
+        // Also listen for heartbeats forwarded by the other instances in the shard
+        nats_client_bundle
+            .update_subscriptions(ld, &subscriptions, &heartbeat_tx)
+            .await?;
 
-        Ok(ActorState {
+        let actor = ActorState {
             client: nats_client_bundle,
             heartbeat_sender: heartbeat_tx,
             ld: ld.clone(),
             sensors: Default::default(),
             schedule: Default::default(),
+            push: Default::default(),
+            pending_configs: Default::default(),
+            last_values: Default::default(),
+            drain: Default::default(),
+            quarantine: Default::default(),
+            clocks: Default::default(),
+            shard,
+            connection,
+            config,
+            time_sync: None,
+            service: None,
             handles: Default::default(),
-        })
+        };
+        Ok((actor, heartbeat_rx))
+    }
+
+    /// Forget the actor's sensors and schedule, then stop its background tasks. This is done here
+    /// rather than when the actor is dropped, since the locks can't be waited for in `Drop`.
+    async fn close_actor(actor: ActorState) {
+        actor.sensors.write().await.clear();
+        actor.schedule.write().await.clear();
+        drop(actor);
+    }
+
+    /// Let the actor's polls in progress finish, send it any readings still buffered from
+    /// push-mode sensors, and publish a notice that this instance has disconnected
+    #[instrument(level = "info", skip_all, fields(actor_id = %actor.ld.actor_id))]
+    async fn drain_actor(actor: &ActorState) {
+        let config = actor.config.read().await.clone();
+        let drained = actor.drain.drain(config.shutdown_timeout()).await;
+        if !drained {
+            warn!("Shutdown timeout passed with polls still in progress");
+        }
+
+        let readings = actor
+            .push
+            .drain(&actor.sensors, config.push_stale_intervals())
+            .await;
+        if !readings.is_empty() {
+            let readings = events::channel_events(readings, config.channel_events());
+            Self::send_readings(readings, &actor.ld, config.encoding()).await;
+        }
+
+        let notice = DisconnectNotice {
+            actor_id: actor.ld.actor_id.clone(),
+            instance_id: actor.shard.as_ref().map(|shard| shard.instance_id),
+            sensor_ids: actor.sensors.read().await.keys().copied().collect(),
+            drained,
+            timestamp: events::now(),
+        };
+        let payload = match serde_json::to_vec(&notice) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to serialize disconnect notice: {e:?}");
+                return;
+            }
+        };
+        let client = &actor.client.client;
+        let subject = config.disconnect_subject().to_string();
+        if let Err(e) = client.publish(subject, payload.into()).await {
+            error!("Failed to publish disconnect notice: {e:?}");
+        } else if let Err(e) = client.flush().await {
+            error!("Failed to flush disconnect notice: {e:?}");
+        }
     }
 }
 
@@ -379,7 +1114,6 @@ impl ProviderHandler for NatsSensorPollingProvider {
     /// If the link is allowed, return true, otherwise return false to deny the link.
     #[instrument(level = "info", skip(self, ld), fields(actor_id = %ld.actor_id))]
     async fn put_link(&self, ld: &LinkDefinition) -> RpcResult<bool> {
-        let (heartbeat_tx, heartbeat_rx) = unbounded_channel();
         debug!("putting link for actors {:?}", ld);
         let config = if ld.values.is_empty() {
             self.default_config.clone()
@@ -393,12 +1127,40 @@ impl ProviderHandler for NatsSensorPollingProvider {
             }
         };
 
-        let mut actor = self.connect(config, ld, heartbeat_tx).await?;
+        // Reconfigure the existing link in place, so the actor doesn't lose its sensors
+        if self.actors.read().await.contains_key(&ld.actor_id) {
+            return self.reconfigure(config, ld).await;
+        }
+
+        let (mut actor, heartbeat_rx) = self.connect(config, ld).await?;
         let schedule = actor.schedule.clone();
         let sensors = actor.sensors.clone();
+        let push = actor.push.clone();
+        let pending_configs = actor.pending_configs.clone();
+        let last_values = actor.last_values.clone();
+        let drain = actor.drain.clone();
+        let quarantine = actor.quarantine.clone();
         let client = actor.client.client.clone();
+        let polling_config = actor.config.clone();
+        let shard = actor.shard.clone();
         // Run the background tasks
-        actor.handles = Self::run(ld.clone(), sensors, schedule, heartbeat_rx, client).await;
+        actor.handles = Self::run(
+            ld.clone(),
+            sensors,
+            schedule,
+            push,
+            pending_configs,
+            last_values,
+            drain,
+            quarantine,
+            heartbeat_rx,
+            client,
+            polling_config,
+            shard,
+        )
+        .await;
+        actor.time_sync = Self::start_time_sync(&actor);
+        Self::start_service(&mut actor).await;
 
         {
             let mut write_actors = self.actors.write().await;
@@ -413,28 +1175,117 @@ impl ProviderHandler for NatsSensorPollingProvider {
     #[instrument(level = "info", skip(self))]
     async fn delete_link(&self, actor_id: &str) {
         debug!("deleting link for actors {}", actor_id);
-        let mut write_actors = self.actors.write().await;
+        let removed = self.actors.write().await.remove(actor_id);
 
-        if let Some(actor) = write_actors.remove(actor_id) {
+        if let Some(actor) = removed {
             debug!(
                 "Closing [{}] NATS heartbeat subscriptions for actors [{}]...",
                 &actor.client.heartbeat_sub_handles.len(),
                 actor_id,
             );
-            drop(actor);
+            Self::close_actor(actor).await;
         }
 
         debug!("Finished processing delete link for actors [{actor_id}]");
     }
 
-    /// Handle shutdown request with any cleanup necessary
+    /// Report the clock offset of every sensor which has synchronised its time with the provider,
+    /// the live instances of every sharded link, and every quarantined sensor
+    async fn health_request(&self, _arg: &HealthCheckRequest) -> RpcResult<HealthCheckResponse> {
+        let mut clocks = HashMap::new();
+        let mut shards = HashMap::new();
+        let mut quarantined = HashMap::new();
+        for (actor_id, actor) in self.actors.read().await.iter() {
+            let mut actor_clocks = actor.clocks.write().await;
+            // Sensors removed since the last time request shouldn't be reported
+            let sensors = actor.sensors.read().await;
+            actor_clocks.retain(|id, _| sensors.contains_key(id));
+            if !actor_clocks.is_empty() {
+                clocks.insert(actor_id.to_owned(), actor_clocks.clone());
+            }
+            let actor_quarantined = actor.quarantine.quarantined().await;
+            if !actor_quarantined.is_empty() {
+                quarantined.insert(actor_id.to_owned(), actor_quarantined);
+            }
+            if let Some(shard) = &actor.shard {
+                shards.insert(
+                    actor_id.to_owned(),
+                    serde_json::json!({
+                        "instance_id": shard.instance_id,
+                        "members": shard.members().await,
+                    }),
+                );
+            }
+        }
+
+        let mut status = serde_json::Map::new();
+        if !clocks.is_empty() {
+            status.insert("clocks".to_string(), serde_json::json!(clocks));
+        }
+        if !shards.is_empty() {
+            status.insert("shards".to_string(), serde_json::json!(shards));
+        }
+        if !quarantined.is_empty() {
+            status.insert("quarantined".to_string(), serde_json::json!(quarantined));
+        }
+        let message = if status.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&status).ok()
+        };
+        Ok(HealthCheckResponse {
+            healthy: true,
+            message,
+        })
+    }
+
+    /// Handle shutdown request by stopping new polls, giving the polls in progress until the
+    /// link's shutdown timeout to finish, and flushing any buffered readings to each actor before
+    /// publishing a disconnect notice
+    #[instrument(level = "info", skip(self))]
     async fn shutdown(&self) -> Result<(), Infallible> {
-        let mut write_actors = self.actors.write().await;
-        write_actors.clear();
+        let actors: Vec<ActorState> = {
+            let mut write_actors = self.actors.write().await;
+            write_actors.drain().map(|(_, actor)| actor).collect()
+        };
+        futures::future::join_all(actors.iter().map(Self::drain_actor)).await;
+        for actor in actors {
+            Self::close_actor(actor).await;
+        }
         Ok(())
     }
 }
 
+/// Whether this instance handles the sensor, which it always does if the link isn't sharded
+async fn owns(shard: &Option<Arc<Shard>>, sensor_id: Uuid) -> bool {
+    match shard {
+        Some(shard) => shard.owns(sensor_id).await,
+        None => true,
+    }
+}
+
+/// The first setting which differs between the configs but is only used when a link is first put,
+/// so can't be applied to a live link
+fn unchangeable_setting(
+    current: &ConnectionConfig,
+    new: &ConnectionConfig,
+) -> Option<&'static str> {
+    let (current_polling, new_polling) = (&current.polling, &new.polling);
+    if !current.same_connection(new) {
+        Some("the connection settings")
+    } else if current_polling.shard_subject != new_polling.shard_subject
+        || current_polling.shard_lease_ms != new_polling.shard_lease_ms
+    {
+        Some("the sharding settings")
+    } else if current_polling.sparkplug_subject != new_polling.sparkplug_subject {
+        Some("the Sparkplug B subject")
+    } else if current_polling.service_subject != new_polling.service_subject {
+        Some("the service subject")
+    } else {
+        None
+    }
+}
+
 // TODO: make this function return Result<String>
 /// Convert an MQTT topic to a NATS subject
 fn mqtt_to_nats(input_string: String) -> String {
@@ -477,8 +1328,59 @@ fn mqtt_to_nats(input_string: String) -> String {
 
 #[async_trait]
 impl Polling for NatsSensorPollingProvider {
+    /// Poll the sensors given as a JSON array of IDs in `request_data`, or every sensor if it's
+    /// empty. Sensors with a cached reading no older than `max_age` aren't polled, and push-mode
+    /// sensors (which can't be polled) answer with their last reading, which is marked as the
+    /// last known value if it's older than `max_age`.
+    #[instrument(level = "debug", skip(self, ctx, arg))]
     async fn poll_tx(&self, ctx: &Context, arg: &PollRequest) -> RpcResult<PollResult> {
-        todo!()
+        let actor_id = ctx
+            .actor
+            .as_ref()
+            .ok_or_else(|| RpcError::InvalidParameter("no actor in request".to_string()))?;
+        let requested = match arg.request_data.as_deref() {
+            Some(data) if !data.is_empty() => match serde_json::from_slice::<Vec<Uuid>>(data) {
+                Ok(ids) => Some(ids),
+                Err(e) => return Ok(failed_poll("INVALID_REQUEST", e.to_string())),
+            },
+            _ => None,
+        };
+
+        let (sensors, client, last_values, config, _in_flight) = {
+            let read_actors = self.actors.read().await;
+            let actor = read_actors.get(actor_id).ok_or_else(|| {
+                RpcError::InvalidParameter(format!("actor not linked: {actor_id}"))
+            })?;
+            let in_flight = match actor.drain.start() {
+                Some(in_flight) => in_flight,
+                None => {
+                    let description = "provider is shutting down".to_string();
+                    return Ok(failed_poll("SHUTTING_DOWN", description));
+                }
+            };
+            let sensors = match Self::select_sensors(&actor.sensors, requested).await {
+                Ok(sensors) => sensors,
+                Err(id) => return Ok(failed_poll("UNKNOWN_TARGET", id.to_string())),
+            };
+            let config = actor.config.read().await.clone();
+            (
+                sensors,
+                actor.client.client.clone(),
+                actor.last_values.clone(),
+                config,
+                in_flight,
+            )
+        };
+
+        let timeout = config.poll_timeout();
+        let readings =
+            Self::read_sensors(sensors, &client, &last_values, timeout, arg.max_age).await;
+        let readings = events::channel_events(readings, config.channel_events());
+
+        Ok(match PollResult::encoded(&readings, config.encoding()) {
+            Ok(poll_result) => poll_result,
+            Err(e) => failed_poll("BLOB_SER", e.to_string()),
+        })
     }
 
     async fn add_poll_target(
@@ -498,6 +1400,102 @@ impl Polling for NatsSensorPollingProvider {
     }
 }
 
+#[async_trait]
+impl Control for NatsSensorPollingProvider {
+    #[instrument(level = "debug", skip(self, ctx, arg), fields(target_id = %arg.target_id))]
+    async fn send_command(
+        &self,
+        ctx: &Context,
+        arg: &CommandRequest,
+    ) -> RpcResult<CommandResponse> {
+        let target = self.control_target(ctx, &arg.target_id).await?;
+        let sensor = match target.sensor {
+            Some(sensor) => sensor,
+            None => {
+                return Ok(control::failed(
+                    arg,
+                    "UNKNOWN_TARGET",
+                    format!("no sensor with id {}", arg.target_id),
+                ))
+            }
+        };
+
+        let timeout = target.config.command_timeout();
+        Ok(control::send_command(&target.client, &sensor, arg, timeout).await)
+    }
+
+    #[instrument(level = "debug", skip(self, ctx, arg), fields(target_id = %arg.target_id))]
+    async fn push_config(&self, ctx: &Context, arg: &ConfigRequest) -> RpcResult<ConfigResponse> {
+        let target = self.control_target(ctx, &arg.target_id).await?;
+        let sensor = match target.sensor {
+            Some(sensor) => sensor,
+            None => {
+                return Ok(remote_config::failed(
+                    arg,
+                    0,
+                    "UNKNOWN_TARGET",
+                    format!("no sensor with id {}", arg.target_id),
+                ))
+            }
+        };
+
+        Ok(remote_config::push_config(
+            &target.client,
+            &target.pending_configs,
+            &sensor,
+            arg,
+            target.config.config_timeout(),
+        )
+        .await)
+    }
+}
+
+fn failed_poll(error_type: &str, description: String) -> PollResult {
+    PollResult {
+        error: Some(PollingError {
+            error_type: error_type.to_string(),
+            description: Some(description),
+        }),
+        ..Default::default()
+    }
+}
+
+/// State needed to control a sensor on behalf of an actor
+struct ControlTarget {
+    client: NatsClient,
+    /// `None` if the actor hasn't discovered a sensor with the requested ID
+    sensor: Option<Sensor>,
+    config: PollingConfig,
+    pending_configs: Arc<PendingConfigs>,
+}
+
+impl NatsSensorPollingProvider {
+    async fn control_target(&self, ctx: &Context, target_id: &str) -> RpcResult<ControlTarget> {
+        let actor_id = ctx
+            .actor
+            .as_ref()
+            .ok_or_else(|| RpcError::InvalidParameter("no actor in request".to_string()))?;
+
+        let read_actors = self.actors.read().await;
+        let actor = read_actors
+            .get(actor_id)
+            .ok_or_else(|| RpcError::InvalidParameter(format!("actor not linked: {actor_id}")))?;
+
+        let sensor = match Uuid::parse_str(target_id) {
+            Ok(id) => actor.sensors.read().await.get(&id).cloned(),
+            Err(_) => None,
+        };
+
+        let config = actor.config.read().await.clone();
+        Ok(ControlTarget {
+            client: actor.client.client.clone(),
+            sensor,
+            config,
+            pending_configs: actor.pending_configs.clone(),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -524,5 +1522,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unchangeable_setting() {
+        let current = ConnectionConfig::default();
+        let linked = |json: &str| {
+            let values = HashMap::from([("config_json".to_string(), json.to_string())]);
+            current.merge(&ConnectionConfig::new_from(&values).unwrap())
+        };
+
+        // A changed URL can't be applied to the live connection, so it mustn't be stored as if
+        // it was
+        let reconnected = linked(r#"{"cluster_uris": ["nats://10.0.0.1:4222"]}"#);
+        assert_eq!(
+            unchangeable_setting(&current, &reconnected),
+            Some("the connection settings")
+        );
 
+        let sharded = linked(r#"{"shard_subject": "sensors.shard"}"#);
+        assert_eq!(
+            unchangeable_setting(&current, &sharded),
+            Some("the sharding settings")
+        );
+
+        // Subscriptions and polling settings are applied in place
+        let resubscribed =
+            linked(r#"{"subscriptions": ["sim.heartbeat"], "poll_timeout_ms": 500}"#);
+        assert_eq!(unchangeable_setting(&current, &resubscribed), None);
+    }
 }
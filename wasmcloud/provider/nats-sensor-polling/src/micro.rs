@@ -0,0 +1,309 @@
+//! Registers the provider as a NATS micro service, so operators can find it with
+//! `nats micro ls` and inspect or drive it from anywhere in the lattice without deploying an
+//! actor. `$SRV.PING`, `$SRV.INFO` and `$SRV.STATS` are answered by async-nats, and the
+//! endpoints are under the link's `service_subject`:
+//!
+//! - `{service_subject}.sensors` lists every registered sensor
+//! - `{service_subject}.poll` reads the sensors given as `{"sensor_ids": [...], "max_age": ms}`,
+//!   or every sensor if the request is empty, and responds with the readings
+//! - `{service_subject}.stats` counts the sensors, schedules and cached readings
+
+use async_nats::service::error::Error as ServiceError;
+use async_nats::service::{Endpoint, Service, ServiceExt};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::cache::LastValues;
+use crate::config::SharedConfig;
+use crate::drain::Drain;
+use crate::events;
+use crate::nats::NatsClient;
+use crate::sensor::{PollInterval, SensorMode};
+use crate::shard::Shard;
+use crate::{NatsSensorPollingProvider, Schedule, Sensors};
+
+pub const SERVICE_NAME: &str = "nats-sensor-polling";
+
+/// Everything from the actor's link which the endpoints need
+#[derive(Clone)]
+pub struct ServiceState {
+    pub sensors: Sensors,
+    pub schedule: Schedule,
+    pub last_values: Arc<LastValues>,
+    pub drain: Arc<Drain>,
+    pub client: NatsClient,
+    pub config: SharedConfig,
+    pub shard: Option<Arc<Shard>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Operation {
+    Sensors,
+    Poll,
+    Stats,
+}
+
+impl Operation {
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Sensors => "sensors",
+            Operation::Poll => "poll",
+            Operation::Stats => "stats",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct PollRequest {
+    /// Every sensor is read if this isn't set
+    #[serde(default)]
+    sensor_ids: Option<Vec<Uuid>>,
+    /// Sensors with a cached reading no older than this many ms aren't polled
+    #[serde(default)]
+    max_age: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+struct Stats {
+    sensors: usize,
+    poll_sensors: usize,
+    push_sensors: usize,
+    /// Number of sensors polled at each poll interval
+    schedules: BTreeMap<PollInterval, usize>,
+    cached_readings: usize,
+    /// This instance's ID in the link's shard, if sensors are shared with other instances
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shard_members: Option<Vec<Uuid>>,
+}
+
+/// Register the service and start answering requests on its endpoints. The service is
+/// deregistered when it's stopped, and the returned tasks end once it has been.
+pub async fn start(
+    subject: &str,
+    actor_id: &str,
+    state: ServiceState,
+) -> Result<(Service, Vec<JoinHandle<()>>), async_nats::Error> {
+    let service = state
+        .client
+        .service_builder()
+        .description(format!("Sensor polling for actor {actor_id}"))
+        .start(SERVICE_NAME, env!("CARGO_PKG_VERSION"))
+        .await?;
+
+    let mut handles = vec![];
+    for operation in [Operation::Sensors, Operation::Poll, Operation::Stats] {
+        let endpoint = service
+            .endpoint(format!("{subject}.{}", operation.name()))
+            .await?;
+        handles.push(tokio::task::spawn(serve(
+            operation,
+            endpoint,
+            state.clone(),
+        )));
+    }
+    Ok((service, handles))
+}
+
+#[instrument(level = "info", skip(endpoint, state))]
+async fn serve(operation: Operation, mut endpoint: Endpoint, state: ServiceState) {
+    while let Some(request) = endpoint.next().await {
+        // Requests without a reply subject can't be answered
+        if request.message.reply.is_none() {
+            continue;
+        }
+        let response = match operation {
+            Operation::Sensors => sensors(&state).await,
+            Operation::Poll => poll(&state, &request.message.payload).await,
+            Operation::Stats => stats(&state).await,
+        };
+        if let Err(e) = request.respond(response.map(Into::into)).await {
+            error!("Failed to respond to service request: {e:?}");
+        }
+    }
+}
+
+async fn sensors(state: &ServiceState) -> Result<Vec<u8>, ServiceError> {
+    let sensors: Vec<_> = state.sensors.read().await.values().cloned().collect();
+    serde_json::to_vec(&sensors).map_err(|e| service_error(500, e))
+}
+
+/// An empty request reads every sensor
+fn poll_request(payload: &[u8]) -> Result<PollRequest, ServiceError> {
+    if payload.is_empty() {
+        Ok(PollRequest::default())
+    } else {
+        serde_json::from_slice::<PollRequest>(payload).map_err(|e| service_error(400, e))
+    }
+}
+
+async fn poll(state: &ServiceState, payload: &[u8]) -> Result<Vec<u8>, ServiceError> {
+    let request = poll_request(payload)?;
+    let _in_flight = state
+        .drain
+        .start()
+        .ok_or_else(|| service_error(503, "provider is shutting down"))?;
+    let sensors = NatsSensorPollingProvider::select_sensors(&state.sensors, request.sensor_ids)
+        .await
+        .map_err(|id| service_error(404, format!("unknown sensor: {id}")))?;
+
+    let (timeout, channel_events) = {
+        let config = state.config.read().await;
+        (config.poll_timeout(), config.channel_events())
+    };
+    let readings = NatsSensorPollingProvider::read_sensors(
+        sensors,
+        &state.client,
+        &state.last_values,
+        timeout,
+        request.max_age,
+    )
+    .await;
+    let readings = events::channel_events(readings, channel_events);
+    serde_json::to_vec(&readings).map_err(|e| service_error(500, e))
+}
+
+async fn stats(state: &ServiceState) -> Result<Vec<u8>, ServiceError> {
+    let stats = collect_stats(
+        &state.sensors,
+        &state.schedule,
+        &state.last_values,
+        state.shard.as_deref(),
+    )
+    .await;
+    serde_json::to_vec(&stats).map_err(|e| service_error(500, e))
+}
+
+async fn collect_stats(
+    sensors: &Sensors,
+    schedule: &Schedule,
+    last_values: &LastValues,
+    shard: Option<&Shard>,
+) -> Stats {
+    let (sensors, poll_sensors) = {
+        let read_sensors = sensors.read().await;
+        let poll_sensors = read_sensors
+            .values()
+            .filter(|sensor| sensor.mode == SensorMode::Poll)
+            .count();
+        (read_sensors.len(), poll_sensors)
+    };
+    let schedules = schedule
+        .read()
+        .await
+        .iter()
+        .map(|(poll_interval, sensor_ids)| (*poll_interval, sensor_ids.len()))
+        .collect();
+    let shard_members = match shard {
+        Some(shard) => Some(shard.members().await),
+        None => None,
+    };
+
+    Stats {
+        sensors,
+        poll_sensors,
+        push_sensors: sensors - poll_sensors,
+        schedules,
+        cached_readings: last_values.count().await,
+        instance_id: shard.map(|shard| shard.instance_id),
+        shard_members,
+    }
+}
+
+fn service_error(code: usize, status: impl ToString) -> ServiceError {
+    ServiceError {
+        status: status.to_string(),
+        code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensor::Sensor;
+    use serde_json::json;
+    use std::collections::{HashMap, HashSet};
+    use tokio::sync::RwLock;
+    use wasmcloud_interface_polling::SensorReading;
+
+    fn sensor(id: &str, mode: &str) -> Sensor {
+        serde_json::from_value(json!({
+            "alias": "temp_01",
+            "id": id,
+            "poll_interval": 1000,
+            "poll_topic": "temp_01/poll",
+            "read_topic": "temp_01/read",
+            "disconnect_topic": "temp_01/disconnect",
+            "ip_addr": "192.168.1.1",
+            "mac_addr": [0, 1, 2, 3, 4, 255],
+            "location": "sim",
+            "mode": mode
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_poll_request() {
+        let request = poll_request(b"").unwrap();
+        assert_eq!(request.sensor_ids, None);
+        assert_eq!(request.max_age, None);
+
+        let id = Uuid::parse_str("a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3").unwrap();
+        let request =
+            poll_request(format!(r#"{{"sensor_ids": ["{id}"], "max_age": 5000}}"#).as_bytes())
+                .unwrap();
+        assert_eq!(request.sensor_ids, Some(vec![id]));
+        assert_eq!(request.max_age, Some(5000));
+
+        let request = poll_request(b"{}").unwrap();
+        assert_eq!(request.sensor_ids, None);
+
+        for invalid in [&b"not json"[..], &br#"{"sensor_ids": ["temp_01"]}"#[..]] {
+            assert_eq!(poll_request(invalid).unwrap_err().code, 400);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect_stats() {
+        let poll_1 = sensor("a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3", "poll");
+        let poll_2 = sensor("b3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3", "poll");
+        let push = sensor("c3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3", "push");
+        let sensors: Sensors = Arc::new(RwLock::new(HashMap::from([
+            (poll_1.id, poll_1.clone()),
+            (poll_2.id, poll_2.clone()),
+            (push.id, push.clone()),
+        ])));
+        let schedule: Schedule = Arc::new(RwLock::new(HashMap::from([
+            (1000, HashSet::from([poll_1.id])),
+            (60000, HashSet::from([poll_2.id])),
+        ])));
+        let last_values = LastValues::default();
+        last_values
+            .record(&[SensorReading::new(
+                push.id,
+                "sim.temp_01".to_string(),
+                "SUCCESS",
+                Some("21.5".to_string()),
+                1_000,
+            )])
+            .await;
+
+        let stats = collect_stats(&sensors, &schedule, &last_values, None).await;
+        assert_eq!(stats.sensors, 3);
+        assert_eq!(stats.poll_sensors, 2);
+        assert_eq!(stats.push_sensors, 1);
+        assert_eq!(stats.schedules, BTreeMap::from([(1000, 1), (60000, 1)]));
+        assert_eq!(stats.cached_readings, 1);
+
+        // Links which aren't sharded don't report a shard
+        let stats = serde_json::to_value(&stats).unwrap();
+        assert_eq!(stats.get("instance_id"), None);
+        assert_eq!(stats.get("shard_members"), None);
+    }
+}
@@ -4,19 +4,54 @@
 use async_nats::Message;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinHandle;
-use tracing::{debug, error, instrument, warn, Instrument};
+use tokio::time::Instant;
+use tracing::{debug, error, instrument, warn};
+use uuid::Uuid;
 use wascap::prelude::KeyPair;
 use wasmbus_rpc::core::LinkDefinition;
 use wasmbus_rpc::error::{RpcError, RpcResult};
 
+use crate::config::{PollingConfig, SharedConfig};
+use crate::events;
+use crate::flood::{Admission, HeartbeatLimiter};
+use crate::sensor::Sensor;
+use crate::NatsSensorPollingProvider;
+
 pub type NatsClient = async_nats::Client;
-pub type HeartbeatRx = UnboundedReceiver<(LinkDefinition, Message, OwnedSemaphorePermit)>;
-pub type HeartbeatTx = UnboundedSender<(LinkDefinition, Message, OwnedSemaphorePermit)>;
+pub type HeartbeatRx = Receiver<(LinkDefinition, Message, OwnedSemaphorePermit)>;
+
+/// Sends the heartbeats received on every subscription of a link to be handled, once they've got
+/// past the link's rate limiter
+#[derive(Clone)]
+pub struct HeartbeatTx {
+    queue: Sender<(LinkDefinition, Message, OwnedSemaphorePermit)>,
+    limiter: Arc<Mutex<HeartbeatLimiter>>,
+    config: SharedConfig,
+}
+
+/// Queue of up to `size` heartbeats for a link, which are rate limited with the link's current
+/// config
+pub fn heartbeat_channel(size: usize, config: SharedConfig) -> (HeartbeatTx, HeartbeatRx) {
+    let (queue, heartbeats) = channel(size);
+    let heartbeat_tx = HeartbeatTx {
+        queue,
+        limiter: Default::default(),
+        config,
+    };
+    (heartbeat_tx, heartbeats)
+}
+
+/// Just enough of a heartbeat to rate limit it by, without deserializing the rest of it
+#[derive(Deserialize)]
+struct HeartbeatSource {
+    id: Uuid,
+}
 
 const DEFAULT_NATS_URI: &str = "0.0.0.0:4222";
 const ENV_NATS_SUBSCRIPTION: &str = "SUBSCRIPTION";
@@ -41,6 +76,10 @@ pub struct ConnectionConfig {
     /// ping interval in seconds
     #[serde(default)]
     ping_interval_sec: Option<u16>,
+
+    /// settings for polling sensors and delivering their readings
+    #[serde(flatten)]
+    pub polling: PollingConfig,
 }
 
 impl ConnectionConfig {
@@ -64,9 +103,23 @@ impl ConnectionConfig {
         if extra.ping_interval_sec.is_some() {
             out.ping_interval_sec = extra.ping_interval_sec.clone()
         }
+        out.polling = self.polling.merge(&extra.polling);
         out
     }
 
+    /// Whether both configs connect to NATS in the same way, i.e. whether an existing client can
+    /// be reused for `other`
+    pub fn same_connection(&self, other: &ConnectionConfig) -> bool {
+        self.cluster_uris == other.cluster_uris
+            && self.auth_jwt == other.auth_jwt
+            && self.auth_seed == other.auth_seed
+            && self.ping_interval_sec == other.ping_interval_sec
+    }
+
+    pub fn subscriptions(&self) -> &[String] {
+        &self.subscriptions
+    }
+
     pub fn new_from(values: &HashMap<String, String>) -> RpcResult<ConnectionConfig> {
         let mut config = if let Some(config_b64) = values.get("config_b64") {
             let bytes = base64::decode(config_b64.as_bytes()).map_err(|e| {
@@ -103,6 +156,10 @@ impl ConnectionConfig {
         if config.cluster_uris.is_empty() {
             config.cluster_uris.push(DEFAULT_NATS_URI.to_string());
         }
+        config
+            .polling
+            .validate()
+            .map_err(RpcError::InvalidParameter)?;
         Ok(config)
     }
 }
@@ -115,6 +172,7 @@ impl Default for ConnectionConfig {
             auth_jwt: None,
             auth_seed: None,
             ping_interval_sec: None,
+            polling: PollingConfig::default(),
         }
     }
 }
@@ -127,6 +185,7 @@ impl Default for ConnectionConfig {
 #[derive(Debug)]
 pub struct NatsClientBundle {
     pub client: NatsClient,
+    /// Handles for each heartbeat subscription, keyed by the `subject|queue` entry from the config
     pub heartbeat_sub_handles: Vec<(String, JoinHandle<()>)>,
 }
 
@@ -180,34 +239,109 @@ impl NatsClientBundle {
             heartbeat_sub_handles: Vec::new(),
         };
         // Heartbeat subscriptions
-        for sub in cfg.subscriptions.iter().filter(|s| !s.is_empty()) {
-            let (sub, queue) = match sub.split_once('|') {
+        nats_client_bundle
+            .update_subscriptions(ld, &cfg.subscriptions, &heartbeat_tx)
+            .await?;
+
+        Ok(nats_client_bundle)
+    }
+
+    /// Subscribe to any heartbeat subjects in `subscriptions` which aren't already subscribed to,
+    /// and close any existing subscriptions which are no longer in it
+    pub async fn update_subscriptions(
+        &mut self,
+        ld: &LinkDefinition,
+        subscriptions: &[String],
+        heartbeat_tx: &HeartbeatTx,
+    ) -> RpcResult<()> {
+        let missing = self.missing_subscriptions(subscriptions);
+        let added = Self::subscribe_all(&self.client, ld, &missing, heartbeat_tx).await?;
+        self.replace_subscriptions(subscriptions, added);
+        Ok(())
+    }
+
+    /// Entries in `subscriptions` which aren't subscribed to yet
+    pub fn missing_subscriptions(&self, subscriptions: &[String]) -> Vec<String> {
+        let wanted: HashSet<&String> = subscriptions.iter().filter(|s| !s.is_empty()).collect();
+        wanted
+            .into_iter()
+            .filter(|entry| !self.heartbeat_sub_handles.iter().any(|(e, _)| e == *entry))
+            .cloned()
+            .collect()
+    }
+
+    /// Subscribe to every `subject|queue` entry, closing them all again if any of them fail.
+    /// Only the client is needed, so this can be done without holding on to the bundle.
+    pub async fn subscribe_all(
+        client: &NatsClient,
+        ld: &LinkDefinition,
+        entries: &[String],
+        heartbeat_tx: &HeartbeatTx,
+    ) -> RpcResult<Vec<(String, JoinHandle<()>)>> {
+        let mut added = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (sub, queue) = match entry.split_once('|') {
                 Some((sub, queue)) => (sub, Some(queue.to_string())),
-                None => (sub.as_str(), None),
+                None => (entry.as_str(), None),
             };
 
-            nats_client_bundle.heartbeat_sub_handles.push((
+            let subscribed = Self::subscribe_to_heartbeat(
+                client,
+                ld,
                 sub.to_string(),
-                nats_client_bundle
-                    .subscribe_to_heartbeat(ld, sub.to_string(), queue, heartbeat_tx.clone())
-                    .await?,
-            ));
+                queue,
+                heartbeat_tx.clone(),
+            )
+            .await;
+            match subscribed {
+                Ok(handle) => added.push((entry.to_owned(), handle)),
+                Err(e) => {
+                    for (_, handle) in added {
+                        handle.abort();
+                    }
+                    return Err(e);
+                }
+            }
         }
+        Ok(added)
+    }
 
-        Ok(nats_client_bundle)
+    /// Close any subscriptions which aren't in `subscriptions` and keep the `added` ones, unless
+    /// they're already subscribed to
+    pub fn replace_subscriptions(
+        &mut self,
+        subscriptions: &[String],
+        added: Vec<(String, JoinHandle<()>)>,
+    ) {
+        self.heartbeat_sub_handles.retain(|(entry, handle)| {
+            let keep = subscriptions.contains(entry);
+            if !keep {
+                debug!("Closing heartbeat subscription {entry}");
+                handle.abort();
+            }
+            keep
+        });
+
+        for (entry, handle) in added {
+            if self.heartbeat_sub_handles.iter().any(|(e, _)| *e == entry) {
+                handle.abort();
+            } else {
+                self.heartbeat_sub_handles.push((entry, handle));
+            }
+        }
     }
 
     /// Add a regular or queue subscription
     pub async fn subscribe_to_heartbeat(
-        &self,
+        client: &NatsClient,
         ld: &LinkDefinition,
         sub: String,
         queue: Option<String>,
         heartbeat_tx: HeartbeatTx,
     ) -> RpcResult<JoinHandle<()>> {
         let mut subscriber = match queue {
-            Some(queue) => self.client.queue_subscribe(sub.clone(), queue).await,
-            None => self.client.subscribe(sub.clone()).await,
+            Some(queue) => client.queue_subscribe(sub.clone(), queue).await,
+            None => client.subscribe(sub.clone()).await,
         }
         .map_err(|e| {
             error!(subject = %sub, error = %e, "error subscribing");
@@ -238,7 +372,7 @@ impl NatsClientBundle {
                     }
                 };
 
-                Self::send_heartbeat(&heartbeat_tx, link_def.clone(), msg, permit);
+                Self::send_heartbeat(&heartbeat_tx, link_def.clone(), msg, permit).await;
             }
         });
 
@@ -246,17 +380,117 @@ impl NatsClientBundle {
     }
 
     #[instrument(level = "debug", skip_all, fields(actor_id = %ld.actor_id, subject = %msg.subject))]
-    fn send_heartbeat(
+    async fn send_heartbeat(
         channel: &HeartbeatTx,
         ld: LinkDefinition,
         msg: Message,
         _permit: OwnedSemaphorePermit,
     ) {
-        if let Err(e) = channel.send((ld, msg, _permit)) {
-            error!(
-                errpr = %e,
-                "Unable to receive heartbeat"
-            )
+        let sensor_id = match serde_json::from_slice::<HeartbeatSource>(&msg.payload) {
+            Ok(source) => source.id,
+            Err(e) => {
+                error!("Failed to deserialize sensor info: {e:?}");
+                return;
+            }
+        };
+        let admission = {
+            let config = channel.config.read().await;
+            let mut limiter = channel.limiter.lock().unwrap();
+            limiter.admit(sensor_id, &msg.payload, &config, Instant::now())
+        };
+        match admission {
+            Admission::Accept => {}
+            Admission::Coalesced | Admission::Limited { first: false } => return,
+            // Only the first heartbeat dropped from each flood is reported, so the actor isn't
+            // flooded in turn
+            Admission::Limited { first: true } => {
+                warn!("Rate limiting heartbeats from {sensor_id}");
+                Self::report_flood(&ld, &msg.payload, &channel.config).await;
+                return;
+            }
+        }
+
+        // Heartbeats are dropped rather than queued without limit if they arrive faster than
+        // they can be handled, devices send them regularly so the next one will get through
+        match channel.queue.try_send((ld, msg, _permit)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => debug!("Heartbeat queue is full, dropping heartbeat"),
+            Err(e) => {
+                error!(
+                    errpr = %e,
+                    "Unable to receive heartbeat"
+                )
+            }
         }
     }
+
+    /// Tell the actor the sensor's heartbeats are being dropped
+    async fn report_flood(ld: &LinkDefinition, payload: &[u8], config: &SharedConfig) {
+        let sensor = match serde_json::from_slice::<Sensor>(payload) {
+            Ok(sensor) => sensor,
+            Err(e) => {
+                error!("Failed to deserialize sensor info: {e:?}");
+                return;
+            }
+        };
+        let limited = events::sensor_event(
+            &sensor,
+            "RATE_LIMITED",
+            Some("heartbeats dropped".to_string()),
+            events::now(),
+        );
+        let encoding = config.read().await.encoding();
+        NatsSensorPollingProvider::send_readings(vec![limited], ld, encoding).await;
+    }
+}
+
+/// Connect to the NATS server for tests which need one, `nats://127.0.0.1:4222` unless `NATS_URL`
+/// is set. Those tests are ignored by default, run them with `cargo test -- --ignored`.
+#[cfg(test)]
+pub async fn connect_test_server() -> NatsClient {
+    let url = std::env::var("NATS_URL").unwrap_or_else(|_| "nats://127.0.0.1:4222".to_string());
+    async_nats::connect(&url)
+        .await
+        .unwrap_or_else(|e| panic!("no NATS server at {url}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_connection() {
+        let config = ConnectionConfig {
+            subscriptions: vec!["picow.heartbeat".to_string()],
+            ..Default::default()
+        };
+
+        let resubscribed = ConnectionConfig {
+            subscriptions: vec!["sim.heartbeat".to_string()],
+            ..Default::default()
+        };
+        assert!(config.same_connection(&resubscribed));
+
+        let reconnected = ConnectionConfig {
+            cluster_uris: vec!["nats://10.0.0.1:4222".to_string()],
+            ..Default::default()
+        };
+        assert!(!config.same_connection(&reconnected));
+    }
+
+    #[test]
+    fn test_new_from() {
+        let values = HashMap::from([(
+            "config_json".to_string(),
+            r#"{"push_flush_interval_ms": 1000}"#.to_string(),
+        )]);
+        let config = ConnectionConfig::new_from(&values).unwrap();
+        assert_eq!(config.polling.push_flush_interval_ms, Some(1000));
+
+        let values = HashMap::from([(
+            "config_json".to_string(),
+            r#"{"push_flush_interval_ms": 0}"#.to_string(),
+        )]);
+        assert!(ConnectionConfig::new_from(&values).is_err());
+    }
 }
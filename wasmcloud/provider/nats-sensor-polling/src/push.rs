@@ -0,0 +1,257 @@
+//! Ingestion for push-mode sensors, which publish readings to their read topic without being
+//! polled. Incoming readings are buffered per sensor and sent to the actor as a single
+//! `PollResult` on every flush.
+
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+use wasmbus_rpc::error::{RpcError, RpcResult};
+use wasmcloud_interface_polling::SensorReading;
+
+use crate::config::SharedConfig;
+use crate::events;
+use crate::nats::NatsClient;
+use crate::sensor::Sensor;
+use crate::Sensors;
+
+/// Readings received from a push-mode sensor since the last flush
+struct PushBuffer {
+    readings: Vec<SensorReading>,
+    /// Number of readings dropped since the last flush because the sensor exceeded its limit
+    dropped: usize,
+    last_received: Instant,
+    /// Whether the sensor has already been reported as stale since its last reading
+    stale: bool,
+}
+
+impl PushBuffer {
+    fn new() -> Self {
+        Self {
+            readings: vec![],
+            dropped: 0,
+            last_received: Instant::now(),
+            stale: false,
+        }
+    }
+}
+
+/// Buffers for every push-mode sensor, along with the subscriptions feeding them.
+///
+/// Subscriptions are aborted when this is dropped.
+#[derive(Default)]
+pub struct PushIngest {
+    buffers: Mutex<HashMap<Uuid, PushBuffer>>,
+    subscriptions: Mutex<HashMap<Uuid, JoinHandle<()>>>,
+}
+
+impl Drop for PushIngest {
+    fn drop(&mut self) {
+        for handle in self.subscriptions.get_mut().values() {
+            handle.abort();
+        }
+    }
+}
+
+impl PushIngest {
+    /// Subscribe to the sensor's read topic for as long as the sensor is registered, buffering
+    /// any readings it publishes.
+    pub async fn subscribe(
+        self: &Arc<Self>,
+        sensor: &Sensor,
+        client: &NatsClient,
+        config: SharedConfig,
+    ) -> RpcResult<()> {
+        let read_topic = sensor.read_topic.to_owned();
+        let mut subscriber = client.subscribe(read_topic.clone()).await.map_err(|e| {
+            error!(subject = %read_topic, error = %e, "error subscribing to push-mode sensor");
+            RpcError::Nats(format!("subscription to {}: {}", read_topic, e))
+        })?;
+
+        self.buffers
+            .lock()
+            .await
+            .insert(sensor.id, PushBuffer::new());
+
+        // Only hold a weak reference so the subscription doesn't keep the ingest alive after
+        // the link has been deleted
+        let ingest: Weak<Self> = Arc::downgrade(self);
+        let sensor_id = sensor.id;
+        let sensor = sensor.clone();
+        let handle = tokio::spawn(async move {
+            while let Some(msg) = subscriber.next().await {
+                let ingest = match ingest.upgrade() {
+                    Some(ingest) => ingest,
+                    None => break,
+                };
+                let reading = events::sensor_reading(&sensor, &msg.payload, events::now());
+                let max_readings = config.read().await.push_max_readings();
+                ingest.buffer(sensor.id, reading, max_readings).await;
+            }
+            debug!("Subscription to push-mode sensor {} closed", sensor.id);
+        });
+
+        if let Some(old_handle) = self.subscriptions.lock().await.insert(sensor_id, handle) {
+            old_handle.abort();
+        }
+
+        Ok(())
+    }
+
+    /// Stop buffering readings from the sensor, discarding any which haven't been flushed
+    pub async fn unsubscribe(&self, sensor_id: Uuid) {
+        if let Some(handle) = self.subscriptions.lock().await.remove(&sensor_id) {
+            handle.abort();
+        }
+        self.buffers.lock().await.remove(&sensor_id);
+    }
+
+    async fn buffer(&self, sensor_id: Uuid, reading: SensorReading, max_readings: usize) {
+        let mut buffers = self.buffers.lock().await;
+        let buffer = buffers.entry(sensor_id).or_insert_with(PushBuffer::new);
+        buffer.last_received = Instant::now();
+        buffer.stale = false;
+        if buffer.readings.len() < max_readings {
+            buffer.readings.push(reading);
+        } else {
+            buffer.dropped += 1;
+        }
+    }
+
+    /// Take every reading buffered since the last flush.
+    ///
+    /// A `RATE_LIMITED` event is added for each sensor which had readings dropped, and a
+    /// `STALE_DATA` event for each sensor which hasn't published anything for
+    /// `stale_intervals` of its publish intervals, unless it doesn't have one.
+    pub async fn drain(&self, sensors: &Sensors, stale_intervals: u64) -> Vec<SensorReading> {
+        let timestamp = events::now();
+        let read_sensors = sensors.read().await;
+        let mut buffers = self.buffers.lock().await;
+
+        let mut readings = vec![];
+        for (sensor_id, buffer) in buffers.iter_mut() {
+            readings.append(&mut buffer.readings);
+
+            let sensor = match read_sensors.get(sensor_id) {
+                Some(sensor) => sensor,
+                None => continue,
+            };
+
+            if buffer.dropped > 0 {
+                warn!(
+                    "Push-mode sensor {} exceeded its rate limit, dropped {} readings",
+                    sensor_id, buffer.dropped
+                );
+                readings.push(events::sensor_event(
+                    sensor,
+                    "RATE_LIMITED",
+                    Some(format!("{} readings dropped", buffer.dropped)),
+                    timestamp,
+                ));
+                buffer.dropped = 0;
+            }
+
+            // Sensors without a publish interval only publish on changes, so they can't be
+            // expected to have published anything recently
+            let stale_after =
+                Duration::from_millis(sensor.poll_interval.saturating_mul(stale_intervals));
+            if sensor.poll_interval > 0
+                && !buffer.stale
+                && buffer.last_received.elapsed() > stale_after
+            {
+                warn!("No readings from push-mode sensor {sensor_id} for {stale_after:?}");
+                readings.push(events::sensor_event(sensor, "STALE_DATA", None, timestamp));
+                buffer.stale = true;
+            }
+        }
+
+        readings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::RwLock;
+
+    fn registered(poll_interval: u64) -> (Sensor, Sensors) {
+        let sensor: Sensor = serde_json::from_value(serde_json::json!({
+            "alias": "temp_01",
+            "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+            "poll_interval": poll_interval,
+            "read_topic": "temp_01/read",
+            "disconnect_topic": "temp_01/disconnect",
+            "ip_addr": "192.168.1.1",
+            "mac_addr": [0, 1, 2, 3, 4, 255],
+            "location": "sim",
+            "mode": "push"
+        }))
+        .unwrap();
+        let sensors = HashMap::from([(sensor.id, sensor.clone())]);
+        (sensor, Arc::new(RwLock::new(sensors)))
+    }
+
+    /// Pretend the sensor hasn't published anything since `since`
+    async fn silence(ingest: &PushIngest, sensor_id: Uuid, since: Instant) {
+        let mut buffers = ingest.buffers.lock().await;
+        buffers.get_mut(&sensor_id).unwrap().last_received = since;
+    }
+
+    fn reading(sensor: &Sensor, value: &str) -> SensorReading {
+        events::sensor_event(sensor, "SUCCESS", Some(value.to_string()), 0)
+    }
+
+    #[tokio::test]
+    async fn test_drain() {
+        let (sensor, sensors) = registered(1_000);
+        let ingest = PushIngest::default();
+        for value in ["1", "2", "3", "4"] {
+            ingest.buffer(sensor.id, reading(&sensor, value), 2).await;
+        }
+
+        // Readings over the limit are dropped and reported once
+        let readings = ingest.drain(&sensors, 3).await;
+        let values: Vec<_> = readings.iter().map(|r| r.value.as_deref()).collect();
+        assert_eq!(values, [Some("1"), Some("2"), Some("2 readings dropped")]);
+        assert_eq!(readings[2].status, "RATE_LIMITED");
+        assert!(ingest.drain(&sensors, 3).await.is_empty());
+
+        ingest.buffer(sensor.id, reading(&sensor, "5"), 2).await;
+        assert_eq!(ingest.drain(&sensors, 3).await, [reading(&sensor, "5")]);
+
+        // Readings are discarded once the sensor is unsubscribed from
+        ingest.buffer(sensor.id, reading(&sensor, "6"), 2).await;
+        ingest.unsubscribe(sensor.id).await;
+        assert!(ingest.drain(&sensors, 3).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stale() {
+        let (sensor, sensors) = registered(1_000);
+        let ingest = PushIngest::default();
+        ingest.buffer(sensor.id, reading(&sensor, "1"), 10).await;
+        assert_eq!(ingest.drain(&sensors, 3).await.len(), 1);
+
+        // Three intervals without a reading
+        let silent_since = Instant::now() - Duration::from_millis(3_500);
+        silence(&ingest, sensor.id, silent_since).await;
+        let readings = ingest.drain(&sensors, 3).await;
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].status, "STALE_DATA");
+        // Only reported once until the sensor publishes again
+        assert!(ingest.drain(&sensors, 3).await.is_empty());
+        ingest.buffer(sensor.id, reading(&sensor, "2"), 10).await;
+        assert_eq!(ingest.drain(&sensors, 3).await.len(), 1);
+
+        // Sensors without an interval never go stale
+        let (sensor, sensors) = registered(0);
+        let ingest = PushIngest::default();
+        ingest.buffer(sensor.id, reading(&sensor, "1"), 10).await;
+        silence(&ingest, sensor.id, silent_since).await;
+        assert_eq!(ingest.drain(&sensors, u64::MAX).await.len(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+//! Circuit breaker for misbehaving sensors. After enough consecutive failed reads a sensor is
+//! quarantined, which sends the actor a single `QUARANTINED` event instead of a stream of errors
+//! and slows the sensor down to one probe every probe interval. The first successful probe
+//! restores it, with a `RESTORED` event.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use uuid::Uuid;
+use wasmcloud_interface_polling::reading::{quality_class, QUALITY_BAD};
+use wasmcloud_interface_polling::SensorReading;
+
+use crate::events;
+use crate::sensor::Sensor;
+
+#[derive(Debug, Default)]
+struct Health {
+    /// Number of reads in a row which have failed
+    failures: u32,
+    /// When the sensor is next polled, if it's quarantined
+    probe_at: Option<Instant>,
+}
+
+/// Health of every polled sensor which has failed a read since it last succeeded
+#[derive(Default)]
+pub struct Quarantine {
+    sensors: Mutex<HashMap<Uuid, Health>>,
+}
+
+impl Quarantine {
+    /// Whether the sensor should be polled, which it always should unless it's quarantined and
+    /// its next probe isn't due
+    pub async fn due(&self, sensor_id: Uuid, now: Instant) -> bool {
+        match self.sensors.lock().await.get(&sensor_id) {
+            Some(Health {
+                probe_at: Some(probe_at),
+                ..
+            }) => *probe_at <= now,
+            _ => true,
+        }
+    }
+
+    /// Record a reading polled from the sensor, returning the readings to send to the actor.
+    ///
+    /// Failed reads from a quarantined sensor aren't sent at all, and the sensor is quarantined
+    /// once `threshold` reads in a row have failed. A threshold of 0 disables quarantine.
+    pub async fn record(
+        &self,
+        sensor: &Sensor,
+        reading: SensorReading,
+        threshold: u32,
+        probe_interval: Duration,
+        now: Instant,
+    ) -> Vec<SensorReading> {
+        let mut sensors = self.sensors.lock().await;
+        if quality_class(&reading.quality) != QUALITY_BAD {
+            return match sensors.remove(&sensor.id) {
+                Some(Health {
+                    probe_at: Some(_), ..
+                }) => vec![events::sensor_restored(sensor, reading.timestamp), reading],
+                _ => vec![reading],
+            };
+        }
+        if threshold == 0 {
+            return vec![reading];
+        }
+
+        let health = sensors.entry(sensor.id).or_default();
+        health.failures += 1;
+        if health.probe_at.is_some() {
+            health.probe_at = Some(now + probe_interval);
+            vec![]
+        } else if health.failures >= threshold {
+            health.probe_at = Some(now + probe_interval);
+            let quarantined = events::sensor_quarantined(
+                sensor,
+                health.failures,
+                &reading.status,
+                reading.timestamp,
+            );
+            vec![reading, quarantined]
+        } else {
+            vec![reading]
+        }
+    }
+
+    /// IDs of every quarantined sensor
+    pub async fn quarantined(&self) -> Vec<Uuid> {
+        self.sensors
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, health)| health.probe_at.is_some())
+            .map(|(sensor_id, _)| *sensor_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::failure::PollFailure;
+
+    fn sensor() -> Sensor {
+        serde_json::from_str(
+            r#"
+            {
+                "alias": "FAULTY_HumiditySensor-A1",
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "poll_interval": 1000,
+                "poll_topic": "humidity/poll",
+                "read_topic": "humidity/read",
+                "disconnect_topic": "humidity/disconnect",
+                "ip_addr": "192.168.1.1",
+                "mac_addr": [0, 1, 2, 3, 4, 255],
+                "location": "Filling Line 3"
+            }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_quarantine() {
+        let quarantine = Quarantine::default();
+        let sensor = sensor();
+        let probe_interval = Duration::from_secs(60);
+        let now = Instant::now();
+        let failed = || events::failed_reading(&sensor, &PollFailure::Timeout, 0);
+
+        for _ in 0..2 {
+            let sent = quarantine
+                .record(&sensor, failed(), 3, probe_interval, now)
+                .await;
+            assert_eq!(sent.len(), 1);
+        }
+        let sent = quarantine
+            .record(&sensor, failed(), 3, probe_interval, now)
+            .await;
+        assert_eq!(sent[1].status, "QUARANTINED");
+        assert!(!quarantine.due(sensor.id, now).await);
+        assert_eq!(quarantine.quarantined().await, vec![sensor.id]);
+
+        // Failed probes aren't sent
+        let probe = now + probe_interval;
+        assert!(quarantine.due(sensor.id, probe).await);
+        let sent = quarantine
+            .record(&sensor, failed(), 3, probe_interval, probe)
+            .await;
+        assert!(sent.is_empty());
+        assert!(!quarantine.due(sensor.id, probe).await);
+
+        let reading = events::sensor_reading(&sensor, br#""55.2""#, 0);
+        let sent = quarantine
+            .record(&sensor, reading, 3, probe_interval, probe + probe_interval)
+            .await;
+        assert_eq!(sent[0].status, "RESTORED");
+        assert_eq!(sent[1].status, "SUCCESS");
+        assert!(quarantine.due(sensor.id, probe).await);
+        assert!(quarantine.quarantined().await.is_empty());
+    }
+}
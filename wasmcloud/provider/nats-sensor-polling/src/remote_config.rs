@@ -0,0 +1,196 @@
+//! Remote configuration for sensors which advertise a `config_topic` in their heartbeat.
+//!
+//! Each configuration document pushed to a sensor is given a version. The sensor applies the
+//! document and reports the new version (along with its new settings) in its next heartbeat,
+//! which is what the provider waits for before treating the push as applied.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{oneshot, Mutex};
+use tracing::error;
+use uuid::Uuid;
+use wasmcloud_interface_polling::control::{ConfigRequest, ConfigResponse, ControlError};
+
+use crate::calibration::Calibration;
+use crate::events;
+use crate::nats::NatsClient;
+use crate::sensor::{Location, PollInterval, Sensor};
+
+/// The settings a configuration document can change. Anything left out is unchanged.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_interval: Option<PollInterval>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
+}
+
+/// Payload published to a sensor's config topic
+#[derive(Serialize)]
+pub struct ConfigDocument {
+    pub version: u64,
+    #[serde(flatten)]
+    pub update: ConfigUpdate,
+}
+
+/// Configuration pushes which are waiting for the sensor to report their version in a heartbeat
+#[derive(Default)]
+pub struct PendingConfigs {
+    waiters: Mutex<HashMap<Uuid, Vec<(u64, oneshot::Sender<Sensor>)>>>,
+}
+
+impl PendingConfigs {
+    /// Reserve the next config version for the sensor, returning the version along with a
+    /// receiver which resolves once a heartbeat reports that version.
+    pub async fn register(&self, sensor: &Sensor) -> (u64, oneshot::Receiver<Sensor>) {
+        let mut waiters = self.waiters.lock().await;
+        let pending = waiters.entry(sensor.id).or_default();
+        pending.retain(|(_, tx)| !tx.is_closed());
+
+        // Versions still waiting to be applied are skipped so concurrent pushes can't collide
+        let version = pending
+            .iter()
+            .map(|(version, _)| *version)
+            .chain(std::iter::once(sensor.config_version))
+            .max()
+            .unwrap_or_default()
+            + 1;
+        let (tx, rx) = oneshot::channel();
+        pending.push((version, tx));
+
+        (version, rx)
+    }
+
+    /// Resolve every push which the sensor's heartbeat shows has been applied
+    pub async fn notify(&self, sensor: &Sensor) {
+        let mut waiters = self.waiters.lock().await;
+        if let Some(pending) = waiters.get_mut(&sensor.id) {
+            let (applied, waiting): (Vec<_>, Vec<_>) = pending
+                .drain(..)
+                .partition(|(version, _)| *version <= sensor.config_version);
+            for (_, tx) in applied {
+                // The receiver is dropped if the push already timed out
+                let _ = tx.send(sensor.clone());
+            }
+            *pending = waiting;
+            if pending.is_empty() {
+                waiters.remove(&sensor.id);
+            }
+        }
+    }
+}
+
+/// Build a response for a configuration push which wasn't applied
+pub fn failed(
+    req: &ConfigRequest,
+    version: u64,
+    error_type: &str,
+    description: String,
+) -> ConfigResponse {
+    ConfigResponse {
+        target_id: req.target_id.to_owned(),
+        version,
+        applied: false,
+        error: Some(ControlError {
+            error_type: error_type.to_string(),
+            description: Some(description),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Push a configuration document to the sensor and wait for it to be reported in the sensor's
+/// heartbeat, or for the timeout to elapse
+pub async fn push_config(
+    client: &NatsClient,
+    pending: &PendingConfigs,
+    sensor: &Sensor,
+    req: &ConfigRequest,
+    default_timeout: Duration,
+) -> ConfigResponse {
+    let config_topic = match &sensor.config_topic {
+        Some(topic) => topic.to_owned(),
+        None => {
+            return failed(
+                req,
+                sensor.config_version,
+                "NOT_CONFIGURABLE",
+                format!("sensor {} can't be configured remotely", sensor.id),
+            )
+        }
+    };
+    let update = match serde_json::from_str::<ConfigUpdate>(&req.config) {
+        Ok(update) => update,
+        Err(e) => return failed(req, sensor.config_version, "INVALID_CONFIG", e.to_string()),
+    };
+    let timeout = req
+        .timeout_ms
+        .map(|ms| Duration::from_millis(ms as u64))
+        .unwrap_or(default_timeout);
+
+    // Register before publishing so a quick heartbeat can't be missed
+    let (version, applied) = pending.register(sensor).await;
+    let payload = match serde_json::to_vec(&ConfigDocument { version, update }) {
+        Ok(payload) => payload,
+        Err(e) => return failed(req, version, "SER_ERROR", e.to_string()),
+    };
+    if let Err(e) = client.publish(config_topic, payload.into()).await {
+        error!("Error pushing config to sensor: {e:?}");
+        return failed(req, version, "PUBLISH_ERROR", e.to_string());
+    }
+
+    match tokio::time::timeout(timeout, applied).await {
+        Ok(Ok(current)) => ConfigResponse {
+            target_id: req.target_id.to_owned(),
+            version: current.config_version,
+            applied: true,
+            old: Some(events::settings_json(&sensor.settings())),
+            new: Some(events::settings_json(&current.settings())),
+            error: None,
+        },
+        _ => failed(
+            req,
+            version,
+            "TIMEOUT",
+            format!(
+                "sensor {} didn't report config version {version} within {timeout:?}",
+                sensor.id
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_update_deserialize() {
+        let update: ConfigUpdate = serde_json::from_str(r#"{"poll_interval": 30000}"#).unwrap();
+        assert_eq!(update.poll_interval, Some(30000));
+        assert_eq!(update.alias, None);
+
+        assert!(serde_json::from_str::<ConfigUpdate>(r#"{"id": "not-allowed"}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_document_serialize() {
+        let document = ConfigDocument {
+            version: 2,
+            update: ConfigUpdate {
+                alias: Some("temp_02".to_string()),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&document).unwrap(),
+            r#"{"version":2,"alias":"temp_02"}"#
+        );
+    }
+}
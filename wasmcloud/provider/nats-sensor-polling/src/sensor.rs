@@ -1,30 +1,253 @@
 use macaddr::MacAddr6;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
 use std::net::IpAddr;
 use uuid::Uuid;
 
+use crate::calibration::Calibration;
+
 // ms
 pub type PollInterval = u64;
 
 // TODO: strict new types for valid NATS topics etc
 
+/// How readings are obtained from a sensor, set with the `mode` field in its heartbeat
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SensorMode {
+    /// The provider publishes to the sensor's `poll_topic` every `poll_interval` and waits for
+    /// the reading on its `read_topic`
+    #[default]
+    Poll,
+    /// The sensor publishes readings to its `read_topic` on its own, roughly every
+    /// `poll_interval`, and the provider stays subscribed to it
+    Push,
+}
+
+/// Where a sensor is installed. Heartbeats can give either a free-form name, as older devices
+/// do, or the sensor's place in the ISA-95 equipment hierarchy.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Location {
+    Name(String),
+    #[serde(deserialize_with = "AssetLocation::deserialize_levels")]
+    Asset(AssetLocation),
+}
+
+impl Location {
+    /// The location's levels from the enterprise down, e.g. `acme/plant_1/packaging/line_3`,
+    /// or its name if it's free-form
+    pub fn path(&self) -> String {
+        match self {
+            Location::Name(name) => name.to_owned(),
+            Location::Asset(asset) => asset.levels().join("/"),
+        }
+    }
+
+    /// The sensor's coordinates, if its location gives them
+    pub fn gps(&self) -> Option<GpsCoordinates> {
+        match self {
+            Location::Name(_) => None,
+            Location::Asset(asset) => asset.gps,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.path())
+    }
+}
+
+/// A sensor's place in the ISA-95 equipment hierarchy. Levels below the lowest one known can be
+/// left out, e.g. a sensor which isn't part of a line only needs an enterprise, site and area,
+/// but a level can't be given without the ones above it.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct AssetLocation {
+    pub enterprise: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub area: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gps: Option<GpsCoordinates>,
+}
+
+impl AssetLocation {
+    /// Every known level from the enterprise down
+    pub fn levels(&self) -> Vec<&str> {
+        std::iter::once(&self.enterprise)
+            .chain(
+                [&self.site, &self.area, &self.line, &self.cell]
+                    .into_iter()
+                    .flatten(),
+            )
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Deserialize the location, rejecting it if a level is given below one which isn't, e.g. a
+    /// cell without its line, as its path would put the level in the wrong place
+    fn deserialize_levels<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let asset = Self::deserialize(deserializer)?;
+        let levels = [
+            ("site", &asset.site),
+            ("area", &asset.area),
+            ("line", &asset.line),
+            ("cell", &asset.cell),
+        ];
+        for pair in levels.windows(2) {
+            if let [(above, None), (level, Some(_))] = pair {
+                return Err(serde::de::Error::custom(format!(
+                    "{level} given without {above}"
+                )));
+            }
+        }
+        Ok(asset)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct GpsCoordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Metres above sea level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+}
+
+impl fmt::Display for GpsCoordinates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.latitude, self.longitude)?;
+        match self.altitude {
+            Some(altitude) => write!(f, ",{altitude}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Values a sensor can read, either bound can be left out
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct ValueRange {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+impl ValueRange {
+    pub fn contains(&self, value: f64) -> bool {
+        self.min.map_or(true, |min| value >= min) && self.max.map_or(true, |max| value <= max)
+    }
+}
+
+/// One of the values a sensor reads at once, e.g. a single particle size of a particle counter
+/// or one axis of a vibration sensor
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Channel {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<ValueRange>,
+}
+
 // TODO extra sensor fields:
 //  - value type
-//  - value range
-//  - multiple channels? (e.g. for particle sensors which have channels for each particle size)
-//      - could implement using an enum for value type, with one of the options being an array
-//  - calibration values (offset etc)
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Sensor {
     pub alias: String,
     pub id: Uuid,
     pub poll_interval: PollInterval,
+    #[serde(default)] // Push-mode sensors don't need to be polled
     pub poll_topic: String,
     pub read_topic: String, // Only necessary because MQTT v3.* doesn't have reply topics for req/resp
     pub disconnect_topic: String,
     pub ip_addr: IpAddr,
     pub mac_addr: MacAddr6, // TODO: + EUI-64 format,
-    pub location: String,   // TODO: gps coords instead? location "name" could be part of alias
+    pub location: Location,
+    #[serde(default)]
+    pub mode: SensorMode,
+    /// Topic for sending commands to the sensor, if it accepts any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_topic: Option<String>,
+    /// Topic the sensor acknowledges commands on. If this isn't set then the sensor is expected
+    /// to reply to the command message directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ack_topic: Option<String>,
+    /// Topic for pushing configuration documents to the sensor, if it can be configured remotely
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_topic: Option<String>,
+    /// Version of the last configuration document the sensor applied, 0 if it's never been
+    /// configured remotely
+    #[serde(default)]
+    pub config_version: u64,
+    /// Unit of the sensor's values as a UCUM code, e.g. `Cel` or `[ppm]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// Values the sensor can read, after calibration. Values outside it are reported as
+    /// `OUT_OF_RANGE` rather than sent on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<ValueRange>,
+    /// Applied to every value read from the sensor, which are sent uncalibrated if this isn't
+    /// set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
+    /// Every value the sensor reads at once, if it reads more than one. The sensor responds with
+    /// an object of each channel's value, or an array of them in this order, and its
+    /// calibration isn't applied to them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<Channel>,
+}
+
+impl Sensor {
+    pub fn settings(&self) -> SensorSettings {
+        SensorSettings {
+            alias: self.alias.to_owned(),
+            poll_interval: self.poll_interval,
+            location: self.location.to_owned(),
+            calibration: self.calibration.to_owned(),
+        }
+    }
+
+    /// Whether a heartbeat changes anything about how the sensor is read, or about what's
+    /// recorded with its readings, compared to the previous one
+    pub fn changed_from(&self, previous: &Sensor) -> bool {
+        self.settings() != previous.settings()
+            || self.config_version != previous.config_version
+            || self.mode != previous.mode
+            || self.poll_topic != previous.poll_topic
+            || self.read_topic != previous.read_topic
+            || self.unit != previous.unit
+            || self.range != previous.range
+            || self.channels != previous.channels
+    }
+
+    /// Whether the sensor has to be rescheduled or resubscribed to after changing from
+    /// `previous`. Polls always use the sensor's latest topics, so only push-mode sensors have
+    /// to be resubscribed to when their topic changes.
+    pub fn needs_restart(&self, previous: &Sensor) -> bool {
+        match (previous.mode, self.mode) {
+            (SensorMode::Poll, SensorMode::Poll) => self.poll_interval != previous.poll_interval,
+            (SensorMode::Push, SensorMode::Push) => self.read_topic != previous.read_topic,
+            _ => true,
+        }
+    }
+}
+
+/// The part of a sensor's heartbeat which can be changed by pushing a configuration document to
+/// its `config_topic`
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct SensorSettings {
+    pub alias: String,
+    pub poll_interval: PollInterval,
+    pub location: Location,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
 }
 
 // TODO: unit tests, mostly so i can make sure i use the correct format
@@ -60,7 +283,172 @@ mod tests {
         assert_eq!(sensor.disconnect_topic, "test-sensor/disconnect");
         assert_eq!(sensor.ip_addr, IpAddr::from([192, 168, 1, 1]));
         assert_eq!(sensor.mac_addr, MacAddr6::new(0, 1, 2, 3, 4, 0xff));
-        assert_eq!(sensor.location, "test-location");
+        assert_eq!(sensor.location, Location::Name("test-location".to_string()));
+        assert_eq!(sensor.mode, SensorMode::Poll);
+        assert_eq!(sensor.config_topic, None);
+        assert_eq!(sensor.config_version, 0);
+        assert_eq!(sensor.calibration, None);
+        assert!(sensor.channels.is_empty());
+    }
+
+    #[test]
+    fn test_multi_channel_deserialize() {
+        let sensor_json = r#"
+            {
+                "alias": "particles_01",
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "poll_interval": 1000,
+                "poll_topic": "particles_01/poll",
+                "read_topic": "particles_01/read",
+                "disconnect_topic": "particles_01/disconnect",
+                "ip_addr": "192.168.1.1",
+                "mac_addr": [0, 1, 2, 3, 4, 255],
+                "location": "clean-room",
+                "channels": [
+                    {"name": "pm1_0", "unit": "ug/m3", "range": {"min": 0, "max": 1000}},
+                    {"name": "pm2_5", "unit": "ug/m3"}
+                ]
+           }
+           "#;
+        let sensor: Sensor = serde_json::from_str(sensor_json).unwrap();
+        assert_eq!(sensor.channels.len(), 2);
+        let range = sensor.channels[0].range.unwrap();
+        assert!(range.contains(0.0));
+        assert!(!range.contains(1000.5));
+        assert_eq!(sensor.channels[1].range, None);
+    }
+
+    #[test]
+    fn test_changed_from() {
+        let sensor_json = r#"
+            {
+                "alias": "test-sensor",
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "poll_interval": 1000,
+                "poll_topic": "test-sensor/poll",
+                "read_topic": "test-sensor/read",
+                "disconnect_topic": "test-sensor/disconnect",
+                "ip_addr": "192.168.1.1",
+                "mac_addr": [0, 1, 2, 3, 4, 255],
+                "location": "test-location"
+           }
+           "#;
+        let previous: Sensor = serde_json::from_str(sensor_json).unwrap();
+        assert!(!previous.changed_from(&previous));
+
+        // New topics are picked up by the next poll
+        let mut sensor = previous.clone();
+        sensor.poll_topic = "test-sensor/poll2".to_string();
+        assert!(sensor.changed_from(&previous));
+        assert!(!sensor.needs_restart(&previous));
+
+        sensor.poll_interval = 2000;
+        assert!(sensor.needs_restart(&previous));
+
+        let mut sensor = previous.clone();
+        sensor.mode = SensorMode::Push;
+        assert!(sensor.changed_from(&previous));
+        assert!(sensor.needs_restart(&previous));
+
+        // Push-mode sensors are only resubscribed to when their read topic changes
+        let previous = sensor.clone();
+        sensor.poll_interval = 2000;
+        assert!(!sensor.needs_restart(&previous));
+        sensor.read_topic = "test-sensor/read2".to_string();
+        assert!(sensor.changed_from(&previous));
+        assert!(sensor.needs_restart(&previous));
+    }
+
+    #[test]
+    fn test_push_sensor_deserialize() {
+        let sensor_json = r#"
+            {
+                "alias": "plc-gateway",
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "poll_interval": 250,
+                "read_topic": "plc-gateway/read",
+                "disconnect_topic": "plc-gateway/disconnect",
+                "ip_addr": "192.168.1.2",
+                "mac_addr": [0, 1, 2, 3, 4, 255],
+                "location": "test-location",
+                "mode": "push"
+           }
+           "#;
+        let sensor: Sensor = serde_json::from_str(sensor_json).unwrap();
+        assert_eq!(sensor.mode, SensorMode::Push);
+        assert_eq!(sensor.poll_topic, "");
+        assert_eq!(sensor.read_topic, "plc-gateway/read");
+    }
+
+    #[test]
+    fn test_actuator_deserialize() {
+        let sensor_json = r#"
+            {
+                "alias": "valve-01",
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "poll_interval": 1000,
+                "poll_topic": "valve-01/poll",
+                "read_topic": "valve-01/read",
+                "disconnect_topic": "valve-01/disconnect",
+                "command_topic": "valve-01/command",
+                "ack_topic": "valve-01/ack",
+                "ip_addr": "192.168.1.3",
+                "mac_addr": [0, 1, 2, 3, 4, 255],
+                "location": "test-location"
+           }
+           "#;
+        let sensor: Sensor = serde_json::from_str(sensor_json).unwrap();
+        assert_eq!(sensor.command_topic.as_deref(), Some("valve-01/command"));
+        assert_eq!(sensor.ack_topic.as_deref(), Some("valve-01/ack"));
+    }
+
+    #[test]
+    fn test_asset_location_deserialize() {
+        let sensor_json = r#"
+            {
+                "alias": "temp_01",
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "poll_interval": 1000,
+                "poll_topic": "temp_01/poll",
+                "read_topic": "temp_01/read",
+                "disconnect_topic": "temp_01/disconnect",
+                "ip_addr": "192.168.1.1",
+                "mac_addr": [0, 1, 2, 3, 4, 255],
+                "location": {
+                    "enterprise": "acme",
+                    "site": "plant_1",
+                    "area": "packaging",
+                    "line": "line_3",
+                    "cell": "cell_2",
+                    "gps": {"latitude": 51.5072, "longitude": -0.1276}
+                }
+           }
+           "#;
+        let sensor: Sensor = serde_json::from_str(sensor_json).unwrap();
+        let Location::Asset(asset) = &sensor.location else {
+            panic!("expected an asset location");
+        };
+        assert_eq!(asset.cell.as_deref(), Some("cell_2"));
+        assert_eq!(asset.gps.unwrap().altitude, None);
+        assert_eq!(
+            sensor.location.path(),
+            "acme/plant_1/packaging/line_3/cell_2"
+        );
+        assert_eq!(
+            sensor.location.gps().unwrap().to_string(),
+            "51.5072,-0.1276"
+        );
+
+        // The cell can't be placed without its line
+        let location =
+            r#"{"enterprise": "acme", "site": "plant_1", "area": "packaging", "cell": "cell_2"}"#;
+        assert!(serde_json::from_str::<Location>(location).is_err());
+        let location = r#"{"enterprise": "acme", "area": "packaging"}"#;
+        assert!(serde_json::from_str::<Location>(location).is_err());
+        let location = r#"{"enterprise": "acme", "site": "plant_1"}"#;
+        let location: Location = serde_json::from_str(location).unwrap();
+        assert_eq!(location.path(), "acme/plant_1");
+        assert_eq!(location.gps(), None);
     }
 
     #[test]
@@ -81,7 +469,7 @@ mod tests {
         assert_eq!(sensor.disconnect_topic, "picow/f3088463-5623-476f-a1b5-ecb49446a443/disconnect");
         assert_eq!(sensor.ip_addr, IpAddr::from([1, 2, 3, 4]));
         assert_eq!(sensor.mac_addr, MacAddr6::new(40, 205, 193, 3, 226, 159));
-        assert_eq!(sensor.location, "rp-pico-w");
+        assert_eq!(sensor.location.to_string(), "rp-pico-w");
     }
 
     #[test]
@@ -95,7 +483,16 @@ mod tests {
             disconnect_topic: "test-sensor/disconnect".to_string(),
             ip_addr: IpAddr::from([192, 168, 1, 1]),
             mac_addr: MacAddr6::new(0, 1, 2, 3, 4, 255),
-            location: "test-location".to_string(),
+            location: Location::Name("test-location".to_string()),
+            mode: SensorMode::Poll,
+            command_topic: None,
+            ack_topic: None,
+            config_topic: None,
+            config_version: 0,
+            unit: None,
+            range: None,
+            calibration: None,
+            channels: vec![],
         };
         let sensor_json = serde_json::to_string_pretty(&sensor).unwrap();
         let expected_json = r#"{
@@ -114,7 +511,9 @@ mod tests {
     4,
     255
   ],
-  "location": "test-location"
+  "location": "test-location",
+  "mode": "poll",
+  "config_version": 0
 }"#;
         println!("{}", sensor_json);
         assert_eq!(sensor_json, expected_json);
@@ -0,0 +1,517 @@
+//! Sharding of sensors across multiple provider instances linked to the same actor, so each sensor
+//! is polled by exactly one live instance.
+//!
+//! Every instance holds a lease which it renews by publishing a [`Lease`] to the members subject
+//! each lease interval, and forgets any other instance whose lease hasn't been renewed for
+//! [`LEASE_EXPIRY_INTERVALS`] intervals. Each sensor is owned by whichever live instance has the
+//! highest rendezvous hash for it, so when an instance joins or leaves only the sensors it owns
+//! move.
+//!
+//! Heartbeat subscriptions should use a queue group (`subject|queue`) when sharding, so each
+//! heartbeat is delivered to a single instance. An instance receiving a heartbeat for a sensor it
+//! doesn't own forwards it to the owner's heartbeat subject, and releases the sensor if it used
+//! to own it.
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, RwLock};
+use tracing::{debug, error, info, instrument};
+use uuid::Uuid;
+
+use crate::nats::NatsClient;
+
+/// Number of lease intervals an instance can miss before its sensors are taken over
+pub const LEASE_EXPIRY_INTERVALS: u32 = 3;
+
+/// Announcement that an instance is alive, published every lease interval
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Lease {
+    pub instance_id: Uuid,
+    /// How often the instance renews its lease, in ms
+    pub lease_ms: u64,
+}
+
+/// What to do with a heartbeat received by this instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// This instance owns the sensor
+    Local,
+    /// Another instance owns the sensor
+    Forward(Uuid),
+    /// This instance hasn't been running long enough to know who owns the sensor
+    Wait,
+}
+
+/// The live instances known to a single instance
+#[derive(Debug)]
+pub struct Membership {
+    instance_id: Uuid,
+    /// When each other instance's lease expires
+    leases: HashMap<Uuid, Instant>,
+    /// Sensors aren't claimed before this, so a new instance has a chance to hear from the
+    /// others before polling anything
+    settled_at: Instant,
+}
+
+impl Membership {
+    pub fn new(instance_id: Uuid, lease: Duration, now: Instant) -> Self {
+        Self {
+            instance_id,
+            leases: HashMap::new(),
+            settled_at: now + lease,
+        }
+    }
+
+    /// Record a lease announcement, returns whether the instance is new to this one
+    pub fn renew(&mut self, lease: &Lease, now: Instant) -> bool {
+        if lease.instance_id == self.instance_id {
+            return false;
+        }
+        let expires_at = now + Duration::from_millis(lease.lease_ms) * LEASE_EXPIRY_INTERVALS;
+        self.leases.insert(lease.instance_id, expires_at).is_none()
+    }
+
+    /// Forget every instance whose lease has expired, returning their IDs
+    pub fn expire(&mut self, now: Instant) -> Vec<Uuid> {
+        let expired: Vec<Uuid> = self
+            .leases
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &expired {
+            self.leases.remove(id);
+        }
+        expired
+    }
+
+    /// Every live instance, including this one
+    pub fn members(&self, now: Instant) -> Vec<Uuid> {
+        let mut members: Vec<Uuid> = self
+            .leases
+            .iter()
+            .filter(|(_, expires_at)| **expires_at > now)
+            .map(|(id, _)| *id)
+            .collect();
+        members.push(self.instance_id);
+        members
+    }
+
+    /// The live instance with the highest rendezvous hash for the sensor
+    pub fn owner(&self, sensor_id: Uuid, now: Instant) -> Uuid {
+        self.members(now)
+            .into_iter()
+            .max_by_key(|member| (score(*member, sensor_id), *member))
+            .unwrap_or(self.instance_id)
+    }
+
+    pub fn route(&self, sensor_id: Uuid, now: Instant) -> Route {
+        let owner = self.owner(sensor_id, now);
+        if owner != self.instance_id {
+            Route::Forward(owner)
+        } else if now < self.settled_at {
+            Route::Wait
+        } else {
+            Route::Local
+        }
+    }
+
+    pub fn owns(&self, sensor_id: Uuid, now: Instant) -> bool {
+        self.route(sensor_id, now) == Route::Local
+    }
+}
+
+/// Rendezvous hash of a sensor for an instance. This has to be the same on every instance, so
+/// it's FNV-1a (with a final mix to spread similar IDs) rather than the std hasher.
+fn score(instance_id: Uuid, sensor_id: Uuid) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in instance_id.as_bytes().iter().chain(sensor_id.as_bytes()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash
+}
+
+/// This instance's membership of the shard for a single actor link
+pub struct Shard {
+    pub instance_id: Uuid,
+    /// Prefix of every subject used by the shard, scoped to the actor
+    subject: String,
+    lease: Duration,
+    membership: RwLock<Membership>,
+    /// Notified whenever an instance joins or leaves
+    changes: watch::Sender<()>,
+}
+
+impl Shard {
+    pub fn new(subject: &str, actor_id: &str, lease: Duration) -> Self {
+        let instance_id = Uuid::new_v4();
+        let (changes, _) = watch::channel(());
+        Self {
+            instance_id,
+            subject: format!("{subject}.{actor_id}"),
+            lease,
+            membership: RwLock::new(Membership::new(instance_id, lease, Instant::now())),
+            changes,
+        }
+    }
+
+    fn members_subject(&self) -> String {
+        format!("{}.members", self.subject)
+    }
+
+    /// Subject that heartbeats for sensors owned by the instance are forwarded to
+    pub fn forward_subject(&self, instance_id: Uuid) -> String {
+        format!("{}.heartbeat.{}", self.subject, instance_id)
+    }
+
+    /// Whether the heartbeat was forwarded by another instance, in which case it must not be
+    /// forwarded again
+    pub fn is_forwarded(&self, subject: &str) -> bool {
+        subject == self.forward_subject(self.instance_id)
+    }
+
+    pub async fn route(&self, sensor_id: Uuid) -> Route {
+        self.membership
+            .read()
+            .await
+            .route(sensor_id, Instant::now())
+    }
+
+    pub async fn owns(&self, sensor_id: Uuid) -> bool {
+        self.membership.read().await.owns(sensor_id, Instant::now())
+    }
+
+    /// Receiver which is notified whenever sensor ownership may have changed
+    pub fn changes(&self) -> watch::Receiver<()> {
+        self.changes.subscribe()
+    }
+
+    pub async fn members(&self) -> Vec<Uuid> {
+        self.membership.read().await.members(Instant::now())
+    }
+
+    /// Send a heartbeat on to the instance which owns its sensor
+    pub async fn forward(&self, client: &NatsClient, owner: Uuid, payload: Vec<u8>) {
+        if let Err(e) = client
+            .publish(self.forward_subject(owner), payload.into())
+            .await
+        {
+            error!("Error forwarding heartbeat to instance {owner}: {e:?}");
+        }
+    }
+
+    async fn announce(&self, client: &NatsClient) {
+        let lease = Lease {
+            instance_id: self.instance_id,
+            lease_ms: self.lease.as_millis() as u64,
+        };
+        match serde_json::to_vec(&lease) {
+            Ok(payload) => {
+                if let Err(e) = client.publish(self.members_subject(), payload.into()).await {
+                    error!("Error renewing shard lease: {e:?}");
+                }
+            }
+            Err(e) => error!("Error serialising shard lease: {e:?}"),
+        }
+    }
+
+    /// Renew this instance's lease and track the other instances' leases until the subscription
+    /// is closed
+    #[instrument(level = "info", skip_all, fields(instance_id = %self.instance_id))]
+    pub async fn run(self: Arc<Self>, client: NatsClient) {
+        let mut subscriber = match client.subscribe(self.members_subject()).await {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Error subscribing to shard members: {e:?}");
+                return;
+            }
+        };
+
+        let mut lease_clock = tokio::time::interval(self.lease);
+        loop {
+            tokio::select! {
+                _ = lease_clock.tick() => {
+                    self.announce(&client).await;
+                    let expired = self.membership.write().await.expire(Instant::now());
+                    if !expired.is_empty() {
+                        info!("Shard lease expired for instances {expired:?}, rebalancing sensors");
+                        self.changes.send_replace(());
+                    }
+                }
+                msg = subscriber.next() => {
+                    let msg = match msg {
+                        Some(msg) => msg,
+                        None => break,
+                    };
+                    let lease = match serde_json::from_slice::<Lease>(&msg.payload) {
+                        Ok(lease) => lease,
+                        Err(e) => {
+                            debug!("Ignoring malformed shard lease: {e:?}");
+                            continue;
+                        }
+                    };
+                    let joined = self.membership.write().await.renew(&lease, Instant::now());
+                    if joined {
+                        let id = lease.instance_id;
+                        info!("Instance {id} joined the shard, rebalancing sensors");
+                        // Announce straight away so the new instance doesn't have to wait a full
+                        // interval to learn about this one
+                        self.announce(&client).await;
+                        self.changes.send_replace(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::push::PushIngest;
+    use crate::sensor::Sensor;
+    use crate::{NatsSensorPollingProvider, Schedule, Sensors};
+
+    const LEASE: Duration = Duration::from_millis(1_000);
+
+    /// Simulate a set of provider instances which have all heard each other's leases
+    fn providers(count: usize, now: Instant) -> Vec<Membership> {
+        let ids: Vec<Uuid> = (0..count).map(|_| Uuid::new_v4()).collect();
+        let mut providers: Vec<Membership> = ids
+            .iter()
+            .map(|id| Membership::new(*id, LEASE, now))
+            .collect();
+        exchange_leases(&mut providers, &ids, now);
+        providers
+    }
+
+    fn exchange_leases(providers: &mut [Membership], live: &[Uuid], now: Instant) {
+        for provider in providers.iter_mut() {
+            for id in live {
+                provider.renew(
+                    &Lease {
+                        instance_id: *id,
+                        lease_ms: LEASE.as_millis() as u64,
+                    },
+                    now,
+                );
+            }
+        }
+    }
+
+    /// The instance each sensor is owned by, asserting there's exactly one owner per sensor
+    fn owners(providers: &[Membership], sensors: &[Uuid], now: Instant) -> HashMap<Uuid, Uuid> {
+        sensors
+            .iter()
+            .map(|sensor_id| {
+                let owners: Vec<Uuid> = providers
+                    .iter()
+                    .filter(|p| p.owns(*sensor_id, now))
+                    .map(|p| p.instance_id)
+                    .collect();
+                assert_eq!(owners.len(), 1, "sensor {sensor_id} owned by {owners:?}");
+                (*sensor_id, owners[0])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_new_instance_waits_before_claiming() {
+        let now = Instant::now();
+        let membership = Membership::new(Uuid::new_v4(), LEASE, now);
+        let sensor_id = Uuid::new_v4();
+        assert_eq!(membership.route(sensor_id, now), Route::Wait);
+        assert_eq!(membership.route(sensor_id, now + LEASE), Route::Local);
+    }
+
+    #[test]
+    fn test_sensors_rebalance_on_failure() {
+        let start = Instant::now();
+        let mut providers = providers(3, start);
+        let sensors: Vec<Uuid> = (0..300).map(|_| Uuid::new_v4()).collect();
+
+        let now = start + LEASE;
+        let before = owners(&providers, &sensors, now);
+        for provider in &providers {
+            let owned = before
+                .values()
+                .filter(|owner| **owner == provider.instance_id)
+                .count();
+            assert!(
+                owned > 0,
+                "provider {} owns no sensors",
+                provider.instance_id
+            );
+        }
+
+        // The last provider stops renewing its lease, the others carry on until it expires
+        let failed = providers.pop().unwrap().instance_id;
+        let live: Vec<Uuid> = providers.iter().map(|p| p.instance_id).collect();
+        let mut now = now;
+        for _ in 0..LEASE_EXPIRY_INTERVALS {
+            now += LEASE;
+            exchange_leases(&mut providers, &live, now);
+        }
+        for provider in providers.iter_mut() {
+            assert_eq!(provider.expire(now), vec![failed]);
+        }
+
+        let after = owners(&providers, &sensors, now);
+        for sensor_id in &sensors {
+            if before[sensor_id] == failed {
+                assert!(live.contains(&after[sensor_id]));
+            } else {
+                // Only the failed provider's sensors move
+                assert_eq!(before[sensor_id], after[sensor_id]);
+            }
+        }
+    }
+
+    /// Wait until every shard has heard from `count` live instances, including itself
+    async fn wait_for_members(shards: &[&Shard], count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let mut agreed = true;
+            for shard in shards {
+                agreed &= shard.members().await.len() == count;
+            }
+            if agreed {
+                return;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "instances didn't agree on {count} members"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// A polled sensor, scheduled as if this instance had been polling it
+    async fn polled_sensor(id: Uuid, sensors: &Sensors, schedule: &Schedule) {
+        let sensor: Sensor = serde_json::from_value(serde_json::json!({
+            "alias": "temp",
+            "id": id,
+            "poll_interval": 1000,
+            "poll_topic": "temp/poll",
+            "read_topic": "temp/read",
+            "disconnect_topic": "temp/disconnect",
+            "ip_addr": "192.168.1.1",
+            "mac_addr": [0, 1, 2, 3, 4, 255],
+            "location": "sim"
+        }))
+        .unwrap();
+        schedule
+            .write()
+            .await
+            .entry(sensor.poll_interval)
+            .or_default()
+            .insert(id);
+        sensors.write().await.insert(id, sensor);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a NATS server"]
+    async fn test_instances_over_nats() {
+        let client = crate::nats::connect_test_server().await;
+        // Each run gets its own subjects, so runs against the same server don't see each other
+        let subject = format!("test.shard.{}", Uuid::new_v4());
+        let lease = Duration::from_millis(100);
+        let first = Arc::new(Shard::new(&subject, "actor", lease));
+        let second = Arc::new(Shard::new(&subject, "actor", lease));
+        let _first_task = tokio::spawn(first.clone().run(client.clone()));
+        let second_task = tokio::spawn(second.clone().run(client.clone()));
+
+        // The first instance was polling every sensor before the second joined
+        let (sensors, schedule) = (Sensors::default(), Schedule::default());
+        let sensor_ids: Vec<Uuid> = (0..50).map(|_| Uuid::new_v4()).collect();
+        for id in &sensor_ids {
+            polled_sensor(*id, &sensors, &schedule).await;
+        }
+
+        // Announcing their leases is enough for each instance to learn about the other, and
+        // once they've settled every sensor is owned by exactly one of them
+        wait_for_members(&[&first, &second], 2).await;
+        tokio::time::sleep(lease).await;
+        let mut second_owned = Vec::new();
+        for id in &sensor_ids {
+            assert_ne!(first.owns(*id).await, second.owns(*id).await);
+            if second.owns(*id).await {
+                second_owned.push(*id);
+            }
+        }
+        assert!(!second_owned.is_empty(), "second instance owns no sensors");
+
+        // The first instance stops polling the sensors the second now owns
+        let push = PushIngest::default();
+        let released =
+            NatsSensorPollingProvider::release_unowned(&sensors, &schedule, &push, &first).await;
+        let mut released: Vec<Uuid> = released.iter().map(|sensor| sensor.id).collect();
+        released.sort();
+        second_owned.sort();
+        assert_eq!(released, second_owned);
+        let scheduled = schedule.read().await[&1000].len();
+        assert_eq!(scheduled, sensor_ids.len() - second_owned.len());
+        assert_eq!(sensors.read().await.len(), scheduled);
+
+        // Heartbeats for them are forwarded to the second instance
+        let sensor_id = second_owned[0];
+        assert_eq!(
+            first.route(sensor_id).await,
+            Route::Forward(second.instance_id)
+        );
+        let mut forwarded = client
+            .subscribe(second.forward_subject(second.instance_id))
+            .await
+            .unwrap();
+        client.flush().await.unwrap();
+        first
+            .forward(&client, second.instance_id, b"heartbeat".to_vec())
+            .await;
+        let msg = tokio::time::timeout(Duration::from_secs(5), forwarded.next())
+            .await
+            .expect("heartbeat wasn't forwarded")
+            .unwrap();
+        assert_eq!(msg.payload.as_ref(), b"heartbeat");
+        assert!(second.is_forwarded(&msg.subject));
+        assert!(!first.is_forwarded(&msg.subject));
+
+        // Once the second instance stops renewing its lease and it expires, the first is told
+        // to rebalance and owns every sensor again
+        let mut changes = first.changes();
+        second_task.abort();
+        tokio::time::timeout(Duration::from_secs(5), changes.changed())
+            .await
+            .expect("expiry didn't trigger a rebalance")
+            .unwrap();
+        assert_eq!(first.members().await, vec![first.instance_id]);
+        for id in &sensor_ids {
+            assert!(first.owns(*id).await);
+        }
+    }
+
+    #[test]
+    fn test_forward_to_owner() {
+        let start = Instant::now();
+        let providers = providers(2, start);
+        let now = start + LEASE;
+        let sensor_id = Uuid::new_v4();
+        let owner = providers[0].owner(sensor_id, now);
+        assert_eq!(providers[1].owner(sensor_id, now), owner);
+
+        for provider in &providers {
+            let expected = if provider.instance_id == owner {
+                Route::Local
+            } else {
+                Route::Forward(owner)
+            };
+            assert_eq!(provider.route(sensor_id, now), expected);
+        }
+    }
+}
@@ -0,0 +1,794 @@
+//! Sparkplug B support, so off-the-shelf edge nodes can be used without custom firmware.
+//!
+//! Sparkplug messages arrive through the MQTT bridge on subjects like
+//! `spBv1//0.{group}.{type}.{node}[.{device}]`, since NATS maps the `.` in `spBv1.0` to `//`.
+//! Every metric in a node or device birth certificate is registered as a push-mode [`Sensor`],
+//! data messages become readings for those sensors, and deaths take them offline again.
+//!
+//! Data messages usually only identify metrics by the alias given to them in the birth
+//! certificate, so births are tracked for every edge node. Nodes which publish data before their
+//! birth has been seen (e.g. because the provider started after them) are asked to rebirth.
+
+use macaddr::MacAddr6;
+use prost::Message;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+use uuid::Uuid;
+use wasmcloud_interface_polling::SensorReading;
+
+use crate::events;
+use crate::failure::PollFailure;
+use crate::sensor::{Location, Sensor, SensorMode};
+
+const BD_SEQ: &str = "bdSeq";
+const REBIRTH: &str = "Node Control/Rebirth";
+/// Metrics with these prefixes are for controlling the node rather than readings
+const CONTROL_PREFIXES: [&str; 3] = ["Node Control/", "Device Control/", "Properties/"];
+
+/// Protobuf messages from the Sparkplug B spec's `sparkplug_b.proto`, only including the fields
+/// needed for scalar metrics. Anything else (datasets, templates, metadata) is skipped when
+/// decoding.
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Payload {
+        /// ms since the unix epoch
+        #[prost(uint64, optional, tag = "1")]
+        pub timestamp: Option<u64>,
+        #[prost(message, repeated, tag = "2")]
+        pub metrics: Vec<Metric>,
+        #[prost(uint64, optional, tag = "3")]
+        pub seq: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Metric {
+        #[prost(string, optional, tag = "1")]
+        pub name: Option<String>,
+        #[prost(uint64, optional, tag = "2")]
+        pub alias: Option<u64>,
+        /// ms since the unix epoch
+        #[prost(uint64, optional, tag = "3")]
+        pub timestamp: Option<u64>,
+        #[prost(uint32, optional, tag = "4")]
+        pub datatype: Option<u32>,
+        #[prost(bool, optional, tag = "7")]
+        pub is_null: Option<bool>,
+        #[prost(oneof = "Value", tags = "10, 11, 12, 13, 14, 15")]
+        pub value: Option<Value>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Value {
+        #[prost(uint32, tag = "10")]
+        IntValue(u32),
+        #[prost(uint64, tag = "11")]
+        LongValue(u64),
+        #[prost(float, tag = "12")]
+        FloatValue(f32),
+        #[prost(double, tag = "13")]
+        DoubleValue(f64),
+        #[prost(bool, tag = "14")]
+        BooleanValue(bool),
+        #[prost(string, tag = "15")]
+        StringValue(String),
+    }
+
+    /// Metric datatypes which change how a value is interpreted
+    pub mod datatype {
+        pub const INT8: u32 = 1;
+        pub const INT16: u32 = 2;
+        pub const INT32: u32 = 3;
+        pub const INT64: u32 = 4;
+        pub const BOOLEAN: u32 = 11;
+    }
+}
+
+use proto::{datatype, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    NBirth,
+    NDeath,
+    DBirth,
+    DDeath,
+    NData,
+    DData,
+    NCmd,
+    DCmd,
+}
+
+impl MessageType {
+    fn parse(message_type: &str) -> Option<Self> {
+        match message_type {
+            "NBIRTH" => Some(MessageType::NBirth),
+            "NDEATH" => Some(MessageType::NDeath),
+            "DBIRTH" => Some(MessageType::DBirth),
+            "DDEATH" => Some(MessageType::DDeath),
+            "NDATA" => Some(MessageType::NData),
+            "DDATA" => Some(MessageType::DData),
+            "NCMD" => Some(MessageType::NCmd),
+            "DCMD" => Some(MessageType::DCmd),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            MessageType::NBirth => "NBIRTH",
+            MessageType::NDeath => "NDEATH",
+            MessageType::DBirth => "DBIRTH",
+            MessageType::DDeath => "DDEATH",
+            MessageType::NData => "NDATA",
+            MessageType::DData => "DDATA",
+            MessageType::NCmd => "NCMD",
+            MessageType::DCmd => "DCMD",
+        }
+    }
+
+    fn is_device(&self) -> bool {
+        matches!(
+            self,
+            MessageType::DBirth | MessageType::DDeath | MessageType::DData | MessageType::DCmd
+        )
+    }
+}
+
+/// The parts of a Sparkplug subject
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topic {
+    /// The first token of the subject, kept as it is so replies use the same mapping
+    pub namespace: String,
+    pub group: String,
+    pub message_type: MessageType,
+    pub node: String,
+    pub device: Option<String>,
+}
+
+impl Topic {
+    /// Parse a subject, `None` if it isn't a Sparkplug B node or device message (e.g. `STATE`)
+    pub fn parse(subject: &str) -> Option<Topic> {
+        let mut tokens = subject.split('.');
+        let namespace = tokens.next()?;
+        if !namespace.starts_with("spBv1") {
+            return None;
+        }
+        let group = tokens.next()?;
+        let message_type = MessageType::parse(tokens.next()?)?;
+        let node = tokens.next()?;
+        let device = tokens.next();
+        if tokens.next().is_some() || message_type.is_device() != device.is_some() {
+            return None;
+        }
+
+        Some(Topic {
+            namespace: namespace.to_string(),
+            group: group.to_string(),
+            message_type,
+            node: node.to_string(),
+            device: device.map(String::from),
+        })
+    }
+
+    /// Subject for a message of the given type to or from the same node or device
+    fn subject(&self, message_type: MessageType) -> String {
+        let mut subject = format!(
+            "{}.{}.{}.{}",
+            self.namespace,
+            self.group,
+            message_type.as_str(),
+            self.node
+        );
+        if let (true, Some(device)) = (message_type.is_device(), &self.device) {
+            subject.push('.');
+            subject.push_str(device);
+        }
+        subject
+    }
+
+    /// `node` or `node/device`
+    fn path(&self) -> String {
+        match &self.device {
+            Some(device) => format!("{}/{}", self.node, device),
+            None => self.node.clone(),
+        }
+    }
+
+    /// Stable ID for the edge node, used to decide which provider instance asks it to rebirth
+    pub fn node_id(&self) -> Uuid {
+        Uuid::new_v5(
+            &Uuid::NAMESPACE_URL,
+            format!("spBv1.0/{}/{}", self.group, self.node).as_bytes(),
+        )
+    }
+
+    /// The sensor registered for one of the node's or device's metrics. Its ID is derived from
+    /// the metric's full path, so it's the same every time the node is born.
+    fn sensor(&self, metric: &str) -> Sensor {
+        let alias = format!("{}/{}", self.path(), metric);
+        let (data, death) = match self.device {
+            Some(_) => (MessageType::DData, MessageType::DDeath),
+            None => (MessageType::NData, MessageType::NDeath),
+        };
+
+        Sensor {
+            id: Uuid::new_v5(
+                &Uuid::NAMESPACE_URL,
+                format!("spBv1.0/{}/{}", self.group, alias).as_bytes(),
+            ),
+            alias,
+            // Sparkplug metrics are reported by exception, so there's no interval to expect
+            // them on
+            poll_interval: 0,
+            poll_topic: String::new(),
+            read_topic: self.subject(data),
+            disconnect_topic: self.subject(death),
+            // Births don't include the node's addresses
+            ip_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            mac_addr: MacAddr6::nil(),
+            location: Location::Name(self.group.clone()),
+            mode: SensorMode::Push,
+            command_topic: None,
+            ack_topic: None,
+            config_topic: None,
+            config_version: 0,
+            unit: None,
+            range: None,
+            calibration: None,
+            channels: vec![],
+        }
+    }
+}
+
+/// Metrics from a node's or device's last birth certificate
+#[derive(Default)]
+struct Certificate {
+    /// Datatype of each metric, by name
+    metrics: HashMap<String, u32>,
+    /// Name of each metric, by alias
+    aliases: HashMap<u64, String>,
+}
+
+#[derive(Default)]
+struct NodeState {
+    /// Birth/death sequence number from the node's birth, so deaths from an earlier session can
+    /// be ignored
+    bd_seq: Option<u64>,
+    /// Certificates of the node (keyed by `None`) and each of its devices
+    certificates: HashMap<Option<String>, Certificate>,
+}
+
+/// The result of handling a Sparkplug message
+#[derive(Default)]
+pub struct Update {
+    /// Sensors which were born or published data, to be registered if they aren't already
+    pub online: Vec<Sensor>,
+    /// Sensors which died along with their node or device
+    pub offline: Vec<Sensor>,
+    /// Events for the actor, along with the ID of the sensor each one is for
+    pub events: Vec<(Uuid, SensorReading)>,
+    /// Subject and payload of a rebirth request to publish to the node
+    pub rebirth: Option<(String, Vec<u8>)>,
+}
+
+/// Births of every edge node seen on the link's Sparkplug subject, keyed by group and node ID
+#[derive(Default)]
+pub struct EdgeNodes {
+    nodes: HashMap<(String, String), NodeState>,
+    /// Nodes which have been asked to rebirth, so they're only asked once
+    rebirths: HashSet<(String, String)>,
+}
+
+impl EdgeNodes {
+    pub fn handle(&mut self, topic: &Topic, payload: &[u8]) -> Result<Update, String> {
+        let payload = proto::Payload::decode(payload)
+            .map_err(|e| format!("invalid Sparkplug B payload: {e}"))?;
+        let timestamp = events::now();
+
+        let update = match topic.message_type {
+            MessageType::NBirth | MessageType::DBirth => self.birth(topic, &payload, timestamp),
+            MessageType::NData | MessageType::DData => self.data(topic, &payload, timestamp),
+            MessageType::NDeath => self.node_death(topic, &payload, timestamp),
+            MessageType::DDeath => self.device_death(topic, timestamp),
+            // Commands are for the edge nodes, not for us
+            MessageType::NCmd | MessageType::DCmd => Update::default(),
+        };
+        Ok(update)
+    }
+
+    fn birth(&mut self, topic: &Topic, payload: &proto::Payload, timestamp: u64) -> Update {
+        let key = (topic.group.clone(), topic.node.clone());
+        if topic.message_type == MessageType::NBirth {
+            // A node birth replaces everything from the node's previous session, devices included
+            let bd_seq = payload
+                .metrics
+                .iter()
+                .find(|metric| metric.name.as_deref() == Some(BD_SEQ))
+                .and_then(|metric| match metric.value {
+                    Some(Value::LongValue(bd_seq)) => Some(bd_seq),
+                    _ => None,
+                });
+            self.nodes.insert(
+                key.clone(),
+                NodeState {
+                    bd_seq,
+                    ..Default::default()
+                },
+            );
+            self.rebirths.remove(&key);
+        }
+        let node = match self.nodes.get_mut(&key) {
+            Some(node) => node,
+            // A device can't be born before its node
+            None => return self.request_rebirth(topic),
+        };
+
+        let mut certificate = Certificate::default();
+        let mut update = Update::default();
+        for metric in &payload.metrics {
+            let name = match metric.name.as_deref() {
+                Some(name) if !is_control(name) => name,
+                _ => continue,
+            };
+            let metric_datatype = metric.datatype.unwrap_or_default();
+            certificate
+                .metrics
+                .insert(name.to_string(), metric_datatype);
+            if let Some(alias) = metric.alias {
+                certificate.aliases.insert(alias, name.to_string());
+            }
+
+            // Births carry each metric's current value as well
+            let sensor = topic.sensor(name);
+            update.events.push((
+                sensor.id,
+                reading(
+                    &sensor,
+                    metric,
+                    metric_datatype,
+                    payload.timestamp,
+                    timestamp,
+                ),
+            ));
+            update.online.push(sensor);
+        }
+        node.certificates.insert(topic.device.clone(), certificate);
+        update
+    }
+
+    fn data(&mut self, topic: &Topic, payload: &proto::Payload, timestamp: u64) -> Update {
+        let key = (topic.group.clone(), topic.node.clone());
+        let certificate = match self
+            .nodes
+            .get(&key)
+            .and_then(|node| node.certificates.get(&topic.device))
+        {
+            Some(certificate) => certificate,
+            None => return self.request_rebirth(topic),
+        };
+
+        let mut update = Update::default();
+        let mut unknown = false;
+        for metric in &payload.metrics {
+            let name = match (&metric.name, metric.alias) {
+                (Some(name), _) => name,
+                (None, Some(alias)) => match certificate.aliases.get(&alias) {
+                    Some(name) => name,
+                    None => {
+                        unknown = true;
+                        continue;
+                    }
+                },
+                (None, None) => continue,
+            };
+            if is_control(name) {
+                continue;
+            }
+            let metric_datatype = match (metric.datatype, certificate.metrics.get(name)) {
+                (Some(metric_datatype), _) => metric_datatype,
+                (None, Some(metric_datatype)) => *metric_datatype,
+                (None, None) => {
+                    unknown = true;
+                    continue;
+                }
+            };
+
+            let sensor = topic.sensor(name);
+            update.events.push((
+                sensor.id,
+                reading(
+                    &sensor,
+                    metric,
+                    metric_datatype,
+                    payload.timestamp,
+                    timestamp,
+                ),
+            ));
+            update.online.push(sensor);
+        }
+
+        // Metrics which weren't in the birth mean the node has changed since it was born
+        if unknown {
+            update.rebirth = self.request_rebirth(topic).rebirth;
+        }
+        update
+    }
+
+    fn node_death(&mut self, topic: &Topic, payload: &proto::Payload, timestamp: u64) -> Update {
+        let key = (topic.group.clone(), topic.node.clone());
+        let bd_seq = payload.metrics.iter().find_map(|metric| match metric {
+            proto::Metric {
+                name: Some(name),
+                value: Some(Value::LongValue(bd_seq)),
+                ..
+            } if name == BD_SEQ => Some(*bd_seq),
+            _ => None,
+        });
+        match (self.nodes.get(&key), bd_seq) {
+            // The death certificate is from an earlier session than the current birth, e.g. the
+            // broker only noticed the old connection had dropped after the node reconnected
+            (Some(node), Some(bd_seq)) if node.bd_seq.is_some() && node.bd_seq != Some(bd_seq) => {
+                return Update::default()
+            }
+            (None, _) => return Update::default(),
+            _ => {}
+        }
+
+        let node = self.nodes.remove(&key).unwrap_or_default();
+        let mut update = Update::default();
+        for (device, certificate) in node.certificates {
+            let topic = Topic {
+                device,
+                ..topic.clone()
+            };
+            offline(&topic, &certificate, timestamp, &mut update);
+        }
+        update
+    }
+
+    fn device_death(&mut self, topic: &Topic, timestamp: u64) -> Update {
+        let key = (topic.group.clone(), topic.node.clone());
+        let mut update = Update::default();
+        let certificate = self
+            .nodes
+            .get_mut(&key)
+            .and_then(|node| node.certificates.remove(&topic.device));
+        if let Some(certificate) = certificate {
+            offline(topic, &certificate, timestamp, &mut update);
+        }
+        update
+    }
+
+    /// Ask the node to publish its births again, unless it's already been asked
+    fn request_rebirth(&mut self, topic: &Topic) -> Update {
+        if !self
+            .rebirths
+            .insert((topic.group.clone(), topic.node.clone()))
+        {
+            return Update::default();
+        }
+
+        let payload = proto::Payload {
+            timestamp: Some(events::now()),
+            metrics: vec![proto::Metric {
+                name: Some(REBIRTH.to_string()),
+                datatype: Some(datatype::BOOLEAN),
+                value: Some(Value::BooleanValue(true)),
+                ..Default::default()
+            }],
+            seq: None,
+        };
+        let node = Topic {
+            device: None,
+            ..topic.clone()
+        };
+        Update {
+            rebirth: Some((node.subject(MessageType::NCmd), payload.encode_to_vec())),
+            ..Default::default()
+        }
+    }
+}
+
+fn is_control(name: &str) -> bool {
+    name == BD_SEQ
+        || CONTROL_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+fn offline(topic: &Topic, certificate: &Certificate, timestamp: u64, update: &mut Update) {
+    for name in certificate.metrics.keys() {
+        let sensor = topic.sensor(name);
+        update
+            .events
+            .push((sensor.id, events::sensor_offline(&sensor, timestamp)));
+        update.offline.push(sensor);
+    }
+}
+
+/// A reading of the metric, timestamped by the node with the metric's own timestamp if it has one
+/// or the payload's otherwise
+fn reading(
+    sensor: &Sensor,
+    metric: &proto::Metric,
+    metric_datatype: u32,
+    payload_timestamp: Option<u64>,
+    timestamp: u64,
+) -> SensorReading {
+    let reading = match metric_value(metric, metric_datatype) {
+        Some(value) => events::sensor_event(sensor, "SUCCESS", Some(value), timestamp),
+        None => {
+            let failure = PollFailure::MalformedPayload("metric has no scalar value".to_string());
+            events::failed_reading(sensor, &failure, timestamp)
+        }
+    };
+    SensorReading {
+        source_timestamp: metric.timestamp.or(payload_timestamp),
+        ..reading
+    }
+}
+
+/// The metric's value as a string, `None` if it's null or not a scalar
+fn metric_value(metric: &proto::Metric, metric_datatype: u32) -> Option<String> {
+    if metric.is_null == Some(true) {
+        return None;
+    }
+    // Signed integers are sent as their two's complement in the unsigned fields
+    let value = match (metric.value.as_ref()?, metric_datatype) {
+        (Value::IntValue(v), datatype::INT8) => (*v as i8).to_string(),
+        (Value::IntValue(v), datatype::INT16) => (*v as i16).to_string(),
+        (Value::IntValue(v), datatype::INT32) => (*v as i32).to_string(),
+        (Value::IntValue(v), _) => v.to_string(),
+        (Value::LongValue(v), datatype::INT64) => (*v as i64).to_string(),
+        (Value::LongValue(v), _) => v.to_string(),
+        (Value::FloatValue(v), _) => v.to_string(),
+        (Value::DoubleValue(v), _) => v.to_string(),
+        (Value::BooleanValue(v), _) => v.to_string(),
+        (Value::StringValue(v), _) => v.clone(),
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLOAT: u32 = 9;
+
+    fn metric(
+        name: Option<&str>,
+        alias: Option<u64>,
+        datatype: Option<u32>,
+        value: Value,
+    ) -> proto::Metric {
+        proto::Metric {
+            name: name.map(String::from),
+            alias,
+            datatype,
+            value: Some(value),
+            ..Default::default()
+        }
+    }
+
+    fn payload(metrics: Vec<proto::Metric>) -> Vec<u8> {
+        proto::Payload {
+            timestamp: Some(1_700_000_000_000),
+            metrics,
+            seq: Some(0),
+        }
+        .encode_to_vec()
+    }
+
+    fn node_birth(bd_seq: u64) -> Vec<u8> {
+        payload(vec![
+            metric(
+                Some(BD_SEQ),
+                None,
+                Some(datatype::INT64),
+                Value::LongValue(bd_seq),
+            ),
+            metric(
+                Some(REBIRTH),
+                None,
+                Some(datatype::BOOLEAN),
+                Value::BooleanValue(false),
+            ),
+            metric(
+                Some("Supply/Voltage"),
+                Some(1),
+                Some(FLOAT),
+                Value::FloatValue(229.5),
+            ),
+        ])
+    }
+
+    fn node_death(bd_seq: u64) -> Vec<u8> {
+        payload(vec![metric(
+            Some(BD_SEQ),
+            None,
+            Some(datatype::INT64),
+            Value::LongValue(bd_seq),
+        )])
+    }
+
+    fn handle(edge_nodes: &mut EdgeNodes, subject: &str, payload: &[u8]) -> Update {
+        let topic = Topic::parse(subject).unwrap();
+        edge_nodes.handle(&topic, payload).unwrap()
+    }
+
+    fn values(update: &Update) -> Vec<(String, String, String)> {
+        let mut values: Vec<_> = update
+            .events
+            .iter()
+            .map(|(_, event)| {
+                (
+                    event.source.clone(),
+                    event.status.clone(),
+                    event.value.clone().unwrap(),
+                )
+            })
+            .collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn test_parse_topic() {
+        let topic = Topic::parse("spBv1//0.plant_1.DDATA.edge_01.press_01").unwrap();
+        assert_eq!(topic.group, "plant_1");
+        assert_eq!(topic.message_type, MessageType::DData);
+        assert_eq!(topic.node, "edge_01");
+        assert_eq!(topic.device.as_deref(), Some("press_01"));
+        assert_eq!(
+            topic.subject(MessageType::NCmd),
+            "spBv1//0.plant_1.NCMD.edge_01"
+        );
+        assert_eq!(
+            topic.subject(MessageType::DDeath),
+            "spBv1//0.plant_1.DDEATH.edge_01.press_01"
+        );
+
+        assert!(Topic::parse("spBv1//0.plant_1.NDATA.edge_01").is_some());
+        assert!(Topic::parse("spBv1//0.STATE.scada_host").is_none());
+        assert!(Topic::parse("spBv1//0.plant_1.NDATA.edge_01.press_01").is_none());
+        assert!(Topic::parse("spBv1//0.plant_1.DDATA.edge_01").is_none());
+        assert!(Topic::parse("picow.heartbeat").is_none());
+    }
+
+    #[test]
+    fn test_birth_data_and_death() {
+        let mut edge_nodes = EdgeNodes::default();
+
+        let update = handle(
+            &mut edge_nodes,
+            "spBv1//0.plant_1.NBIRTH.edge_01",
+            &node_birth(3),
+        );
+        assert_eq!(update.online.len(), 1);
+        assert_eq!(update.online[0].alias, "edge_01/Supply/Voltage");
+        assert_eq!(update.online[0].location.to_string(), "plant_1");
+        assert_eq!(update.online[0].mode, SensorMode::Push);
+        assert_eq!(
+            values(&update),
+            [(
+                "plant_1.edge_01/Supply/Voltage".to_string(),
+                "SUCCESS".to_string(),
+                "229.5".to_string()
+            )]
+        );
+        assert_eq!(update.events[0].1.source_timestamp, Some(1_700_000_000_000));
+
+        let birth = payload(vec![
+            metric(
+                Some("Temperature"),
+                Some(10),
+                Some(datatype::INT16),
+                Value::IntValue(0),
+            ),
+            metric(
+                Some("Running"),
+                Some(11),
+                Some(datatype::BOOLEAN),
+                Value::BooleanValue(true),
+            ),
+        ]);
+        let update = handle(
+            &mut edge_nodes,
+            "spBv1//0.plant_1.DBIRTH.edge_01.press_01",
+            &birth,
+        );
+        assert_eq!(update.online.len(), 2);
+        let temperature_id = update
+            .online
+            .iter()
+            .find(|sensor| sensor.alias == "edge_01/press_01/Temperature")
+            .unwrap()
+            .id;
+
+        // Data only carries aliases, and signed values are sent as their two's complement
+        let data = payload(vec![
+            metric(None, Some(10), None, Value::IntValue(-12_i16 as u16 as u32)),
+            metric(None, Some(11), None, Value::BooleanValue(false)),
+        ]);
+        let update = handle(
+            &mut edge_nodes,
+            "spBv1//0.plant_1.DDATA.edge_01.press_01",
+            &data,
+        );
+        assert!(update.rebirth.is_none());
+        assert_eq!(
+            values(&update),
+            [
+                (
+                    "plant_1.edge_01/press_01/Running".to_string(),
+                    "SUCCESS".to_string(),
+                    "false".to_string()
+                ),
+                (
+                    "plant_1.edge_01/press_01/Temperature".to_string(),
+                    "SUCCESS".to_string(),
+                    "-12".to_string()
+                ),
+            ]
+        );
+        assert!(update.events.iter().any(|(id, _)| *id == temperature_id));
+
+        // A death from an earlier session is ignored
+        let update = handle(
+            &mut edge_nodes,
+            "spBv1//0.plant_1.NDEATH.edge_01",
+            &node_death(2),
+        );
+        assert!(update.offline.is_empty());
+
+        let update = handle(
+            &mut edge_nodes,
+            "spBv1//0.plant_1.NDEATH.edge_01",
+            &node_death(3),
+        );
+        assert_eq!(update.offline.len(), 3);
+        assert!(update
+            .events
+            .iter()
+            .all(|(_, event)| event.status == "OFFLINE"));
+
+        // The node is gone, so its data can't be resolved until it's born again
+        let update = handle(
+            &mut edge_nodes,
+            "spBv1//0.plant_1.DDATA.edge_01.press_01",
+            &data,
+        );
+        assert!(update.events.is_empty());
+        assert!(update.rebirth.is_some());
+    }
+
+    #[test]
+    fn test_rebirth_request() {
+        let mut edge_nodes = EdgeNodes::default();
+        let data = payload(vec![metric(None, Some(1), None, Value::FloatValue(230.0))]);
+
+        let update = handle(&mut edge_nodes, "spBv1//0.plant_1.NDATA.edge_01", &data);
+        let (subject, request) = update.rebirth.unwrap();
+        assert_eq!(subject, "spBv1//0.plant_1.NCMD.edge_01");
+        let request = proto::Payload::decode(&request[..]).unwrap();
+        assert_eq!(request.metrics[0].name.as_deref(), Some(REBIRTH));
+        assert_eq!(request.metrics[0].value, Some(Value::BooleanValue(true)));
+
+        // Only asked once until it's born again
+        let update = handle(&mut edge_nodes, "spBv1//0.plant_1.NDATA.edge_01", &data);
+        assert!(update.rebirth.is_none());
+
+        handle(
+            &mut edge_nodes,
+            "spBv1//0.plant_1.NBIRTH.edge_01",
+            &node_birth(0),
+        );
+        let update = handle(&mut edge_nodes, "spBv1//0.plant_1.NDATA.edge_01", &data);
+        assert!(update.rebirth.is_none());
+        assert_eq!(update.events.len(), 1);
+
+        // An alias which wasn't in the birth means the node has changed
+        let data = payload(vec![metric(None, Some(99), None, Value::FloatValue(1.0))]);
+        let update = handle(&mut edge_nodes, "spBv1//0.plant_1.NDATA.edge_01", &data);
+        assert!(update.rebirth.is_some());
+    }
+}
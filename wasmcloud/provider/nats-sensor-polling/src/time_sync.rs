@@ -0,0 +1,285 @@
+//! NTP-style time synchronisation for sensors without a real time clock.
+//!
+//! A sensor publishes a [`TimeRequest`] containing its own send time (`t1`) to the time sync
+//! subject. The provider replies with a [`TimeResponse`] containing `t1` along with the time it
+//! received the request (`t2`) and the time it sent the reply (`t3`). The sensor records the time
+//! it received the reply (`t4`) and estimates:
+//!
+//! - clock offset: `((t2 - t1) + (t3 - t4)) / 2`, which is what the sensor has to add to its
+//!   clock to match the provider's, so it's positive when the sensor is behind
+//! - round trip: `(t4 - t1) - (t3 - t2)`
+//!
+//! The provider never sees `t4`, so sensors include the offset and round trip from their previous
+//! exchange in their next request, which the provider records as the sensor's clock status for
+//! as long as the sensor is registered.
+//!
+//! All times are unix timestamps in milliseconds.
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, error, instrument, warn};
+use uuid::Uuid;
+
+use crate::events;
+use crate::nats::NatsClient;
+use crate::sensor::Sensor;
+use crate::Sensors;
+
+/// Latest clock status reported by each sensor
+pub type ClockStatuses = Arc<RwLock<HashMap<Uuid, ClockStatus>>>;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TimeRequest {
+    /// ID of the sensor requesting the time
+    pub id: Uuid,
+    pub t1: u64,
+    /// Topic to send the response to, for sensors which can't use reply subjects (i.e. MQTT v3.*)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_topic: Option<String>,
+    /// Offset measured by the sensor's previous exchange, positive if the sensor was behind
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_ms: Option<i64>,
+    /// Round trip measured by the sensor's previous exchange
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round_trip_ms: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TimeResponse {
+    pub t1: u64,
+    pub t2: u64,
+    pub t3: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ClockStatus {
+    /// How far behind the provider the sensor's clock was, negative if it was ahead
+    pub offset_ms: i64,
+    pub round_trip_ms: Option<u64>,
+    /// When the sensor reported this status, in unix ms
+    pub reported_at: u64,
+}
+
+/// Answer time requests published to `subject` until the subscription is closed. Requests from
+/// sensors which haven't been registered are answered, but their clock status isn't recorded.
+#[instrument(level = "info", skip(client, sensors, clocks))]
+pub async fn serve(subject: String, client: NatsClient, sensors: Sensors, clocks: ClockStatuses) {
+    let mut subscriber = match client.subscribe(subject.clone()).await {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Error subscribing to time sync subject {subject}: {e:?}");
+            return;
+        }
+    };
+
+    while let Some(msg) = subscriber.next().await {
+        let t2 = events::now();
+        let request = match serde_json::from_slice::<TimeRequest>(&msg.payload) {
+            Ok(request) => request,
+            Err(e) => {
+                debug!("Ignoring malformed time request: {e:?}");
+                continue;
+            }
+        };
+
+        let reply_topic = match (msg.reply, &request.reply_topic) {
+            (Some(reply), _) => reply,
+            (None, Some(topic)) => crate::mqtt_to_nats(topic.to_owned()),
+            (None, None) => {
+                warn!(
+                    "Time request from sensor {} has nowhere to reply",
+                    request.id
+                );
+                continue;
+            }
+        };
+
+        let response = answer(
+            &mut *clocks.write().await,
+            &*sensors.read().await,
+            &request,
+            t2,
+        );
+        let payload = match serde_json::to_vec(&response) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Error serialising time response: {e:?}");
+                continue;
+            }
+        };
+        if let Err(e) = client.publish(reply_topic, payload.into()).await {
+            error!("Error replying to time request: {e:?}");
+        }
+    }
+}
+
+/// Record the clock status the request reports and answer it, `t2` being when it was received
+fn answer(
+    clocks: &mut HashMap<Uuid, ClockStatus>,
+    sensors: &HashMap<Uuid, Sensor>,
+    request: &TimeRequest,
+    t2: u64,
+) -> TimeResponse {
+    record_status(clocks, sensors, request, t2);
+    TimeResponse {
+        t1: request.t1,
+        t2,
+        t3: events::now(),
+    }
+}
+
+/// Record the clock status a registered sensor reported, and forget the statuses of any sensors
+/// which aren't registered anymore
+fn record_status(
+    clocks: &mut HashMap<Uuid, ClockStatus>,
+    sensors: &HashMap<Uuid, Sensor>,
+    request: &TimeRequest,
+    reported_at: u64,
+) {
+    clocks.retain(|id, _| sensors.contains_key(id));
+    match request.offset_ms {
+        Some(offset_ms) if sensors.contains_key(&request.id) => {
+            let status = ClockStatus {
+                offset_ms,
+                round_trip_ms: request.round_trip_ms,
+                reported_at,
+            };
+            clocks.insert(request.id, status);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sensor() -> Sensor {
+        serde_json::from_value(serde_json::json!({
+            "alias": "temp_01",
+            "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+            "poll_interval": 1000,
+            "poll_topic": "temp_01/poll",
+            "read_topic": "temp_01/read",
+            "disconnect_topic": "temp_01/disconnect",
+            "ip_addr": "192.168.1.1",
+            "mac_addr": [0, 1, 2, 3, 4, 255],
+            "location": "sim"
+        }))
+        .unwrap()
+    }
+
+    /// The offset and round trip a sensor estimates from the provider's response, using the
+    /// module docs' formulas
+    fn estimate(response: &TimeResponse, t4: u64) -> (i64, u64) {
+        let (t1, t2, t3, t4) = (
+            response.t1 as i64,
+            response.t2 as i64,
+            response.t3 as i64,
+            t4 as i64,
+        );
+        let offset = ((t2 - t1) + (t3 - t4)) / 2;
+        let round_trip = (t4 - t1) - (t3 - t2);
+        (offset, round_trip as u64)
+    }
+
+    /// Run two exchanges with the provider from a sensor whose clock is `skew` ms behind, giving
+    /// the clock status the provider records from the second
+    fn exchange(skew: i64) -> ClockStatus {
+        let sensor = sensor();
+        let sensors = HashMap::from([(sensor.id, sensor.clone())]);
+        let mut clocks = HashMap::new();
+        let sensor_clock = || (events::now() as i64 - skew) as u64;
+
+        let mut request = TimeRequest {
+            id: sensor.id,
+            t1: sensor_clock(),
+            reply_topic: None,
+            offset_ms: None,
+            round_trip_ms: None,
+        };
+        for _ in 0..2 {
+            // Requests and responses go over the wire as JSON
+            let received: TimeRequest =
+                serde_json::from_slice(&serde_json::to_vec(&request).unwrap()).unwrap();
+            let response = answer(&mut clocks, &sensors, &received, events::now());
+            let response: TimeResponse =
+                serde_json::from_slice(&serde_json::to_vec(&response).unwrap()).unwrap();
+            let (offset, round_trip) = estimate(&response, sensor_clock());
+            request = TimeRequest {
+                t1: sensor_clock(),
+                offset_ms: Some(offset),
+                round_trip_ms: Some(round_trip),
+                ..request
+            };
+        }
+        clocks.remove(&sensor.id).unwrap()
+    }
+
+    #[test]
+    fn test_offset_sign() {
+        // The recorded offset is positive when the sensor is behind
+        let status = exchange(1_500);
+        assert!((1_450..=1_550).contains(&status.offset_ms), "{status:?}");
+
+        // And negative when it's ahead
+        let status = exchange(-1_500);
+        assert!((-1_550..=-1_450).contains(&status.offset_ms), "{status:?}");
+    }
+
+    #[test]
+    fn test_record_status() {
+        let sensor = sensor();
+        let mut sensors = HashMap::from([(sensor.id, sensor.clone())]);
+        let mut clocks = HashMap::new();
+        let request = TimeRequest {
+            id: sensor.id,
+            t1: 0,
+            reply_topic: None,
+            offset_ms: Some(1_500),
+            round_trip_ms: Some(40),
+        };
+        record_status(&mut clocks, &sensors, &request, 1);
+        assert_eq!(clocks[&sensor.id].offset_ms, 1_500);
+
+        // Sensors which haven't been registered aren't recorded
+        let unknown = TimeRequest {
+            id: Uuid::new_v4(),
+            ..request.clone()
+        };
+        record_status(&mut clocks, &sensors, &unknown, 2);
+        assert_eq!(clocks.len(), 1);
+
+        // And sensors are forgotten once they've been removed
+        sensors.clear();
+        record_status(&mut clocks, &sensors, &request, 3);
+        assert!(clocks.is_empty());
+    }
+
+    #[test]
+    fn test_time_request_deserialize() {
+        let request_json = r#"
+            {
+                "id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3",
+                "t1": 1684000000000,
+                "reply_topic": "picow/a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3/time",
+                "offset_ms": -1250,
+                "round_trip_ms": 40
+            }
+            "#;
+        let request: TimeRequest = serde_json::from_str(request_json).unwrap();
+        assert_eq!(request.t1, 1684000000000);
+        assert_eq!(request.offset_ms, Some(-1250));
+        assert_eq!(request.round_trip_ms, Some(40));
+
+        let request: TimeRequest = serde_json::from_str(
+            r#"{"id": "a3b3c3d3-e3f3-a3b3-c3d3-e3f3a3b3c3d3", "t1": 1684000000000}"#,
+        )
+        .unwrap();
+        assert_eq!(request.reply_topic, None);
+        assert_eq!(request.offset_ms, None);
+    }
+}
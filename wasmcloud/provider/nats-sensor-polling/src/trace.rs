@@ -0,0 +1,14 @@
+//! Helpers for propagating the current span's trace context to sensors and actors.
+//!
+//! Traces are exported to an OTLP collector by wasmbus-rpc when the provider is started with
+//! `OTEL_TRACES_EXPORTER=otlp`, and `OTEL_EXPORTER_OTLP_ENDPOINT` pointing at the collector.
+
+use async_nats::HeaderMap;
+use wasmbus_rpc::otel::OtelHeaderInjector;
+
+pub use wasmcloud_interface_polling::trace::{actor_context, traceparent};
+
+/// NATS headers carrying the current span's trace context, for sensors which support headers
+pub fn span_headers() -> HeaderMap {
+    OtelHeaderInjector::default_with_span().into()
+}
@@ -0,0 +1,378 @@
+//! Reading and subscribing to nodes on OPC UA servers.
+//!
+//! The opcua client is synchronous, so everything here blocks and should be called from
+//! `spawn_blocking`. Sessions are opened anonymously with no security policy, one per endpoint,
+//! and reopened on the next request if the connection to the server is lost.
+
+use opcua::client::prelude::*;
+use opcua::sync::RwLock;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, error, warn};
+use wasmcloud_interface_polling::reading::{now, ReadingTarget};
+use wasmcloud_interface_polling::SensorReading;
+
+use crate::target::OpcUaTarget;
+
+/// Status codes which mean the server couldn't be reached, rather than anything being wrong with
+/// the node or its value
+const COMM_ERRORS: [StatusCode; 11] = [
+    StatusCode::BadTimeout,
+    StatusCode::BadCommunicationError,
+    StatusCode::BadConnectionClosed,
+    StatusCode::BadNotConnected,
+    StatusCode::BadServerNotConnected,
+    StatusCode::BadServerHalted,
+    StatusCode::BadSessionClosed,
+    StatusCode::BadSessionIdInvalid,
+    StatusCode::BadSecureChannelClosed,
+    StatusCode::BadNoCommunication,
+    StatusCode::BadTcpInternalError,
+];
+
+/// Map an OPC UA status code to the `status` of a reading
+pub fn reading_status(code: StatusCode) -> &'static str {
+    if code.is_good() {
+        "SUCCESS"
+    } else if code.is_uncertain() {
+        "UNCERTAIN"
+    } else if COMM_ERRORS.contains(&code.status()) {
+        "COMM_ERROR"
+    } else {
+        "VALUE_ERROR"
+    }
+}
+
+/// Build a reading from a value read from, or published by, the target's node. Bad values are
+/// recorded with the name of their status code in place of the value.
+pub fn reading(target: &OpcUaTarget, value: &DataValue, timestamp: u64) -> SensorReading {
+    let code = value.status.unwrap_or(StatusCode::Good);
+    let formatted = match (&value.value, code.is_bad()) {
+        (Some(variant), false) => format_variant(variant),
+        _ => code.name().to_string(),
+    };
+    SensorReading {
+        // Servers without a clock send the minimum date, which is before the unix epoch
+        source_timestamp: value
+            .source_timestamp
+            .as_ref()
+            .and_then(|ts| u64::try_from(ts.as_chrono().timestamp_millis()).ok()),
+        ..target.reading(reading_status(code), Some(formatted), timestamp)
+    }
+}
+
+fn bad_value(code: StatusCode) -> DataValue {
+    DataValue {
+        status: Some(code),
+        ..DataValue::null()
+    }
+}
+
+fn format_variant(variant: &Variant) -> String {
+    match variant {
+        Variant::Boolean(v) => v.to_string(),
+        Variant::SByte(v) => v.to_string(),
+        Variant::Byte(v) => v.to_string(),
+        Variant::Int16(v) => v.to_string(),
+        Variant::UInt16(v) => v.to_string(),
+        Variant::Int32(v) => v.to_string(),
+        Variant::UInt32(v) => v.to_string(),
+        Variant::Int64(v) => v.to_string(),
+        Variant::UInt64(v) => v.to_string(),
+        Variant::Float(v) => v.to_string(),
+        Variant::Double(v) => v.to_string(),
+        Variant::String(v) => v.as_ref().to_string(),
+        Variant::LocalizedText(v) => v.text.as_ref().to_string(),
+        Variant::DateTime(v) => v.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// A session with a server, which is stopped when this is dropped
+struct Connection {
+    session: Arc<RwLock<Session>>,
+    stop: Option<tokio::sync::oneshot::Sender<SessionCommand>>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(SessionCommand::Stop);
+        }
+    }
+}
+
+pub struct OpcUaClient {
+    client: Mutex<Client>,
+    /// Open sessions, keyed by endpoint URL
+    connections: Mutex<HashMap<String, Connection>>,
+}
+
+impl OpcUaClient {
+    pub fn new(pki_dir: &str) -> Result<Self, String> {
+        let client = ClientBuilder::new()
+            .application_name("OPC UA Polling Provider")
+            .application_uri("urn:jclmnop:opcua-polling")
+            .product_uri("urn:jclmnop:opcua-polling")
+            .create_sample_keypair(true)
+            .trust_server_certs(true)
+            .pki_dir(pki_dir)
+            .session_retry_limit(3)
+            .client()
+            .ok_or_else(|| "invalid OPC UA client configuration".to_string())?;
+
+        Ok(Self {
+            client: Mutex::new(client),
+            connections: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The endpoint's session, if it's already open
+    fn open_session(&self, endpoint: &str) -> Option<Arc<RwLock<Session>>> {
+        let connections = self.connections.lock().unwrap();
+        connections
+            .get(endpoint)
+            .map(|connection| connection.session.clone())
+    }
+
+    /// The session for the endpoint, opening one if there isn't one already. Connecting blocks
+    /// until the server answers, so only other connections wait on it, and requests to endpoints
+    /// which already have a session carry on.
+    fn session(&self, endpoint: &str) -> Result<Arc<RwLock<Session>>, StatusCode> {
+        if let Some(session) = self.open_session(endpoint) {
+            return Ok(session);
+        }
+
+        let mut client = self.client.lock().unwrap();
+        // Another request may have opened the session while this one was waiting for the client
+        if let Some(session) = self.open_session(endpoint) {
+            return Ok(session);
+        }
+
+        debug!("Opening OPC UA session with {endpoint}");
+        let description: EndpointDescription = (
+            endpoint,
+            SecurityPolicy::None.to_str(),
+            MessageSecurityMode::None,
+            UserTokenPolicy::anonymous(),
+        )
+            .into();
+        let session = client.connect_to_endpoint(description, IdentityToken::Anonymous)?;
+        // Run the session in the background so subscriptions are published and it's kept alive
+        let stop = Session::run_async(session.clone());
+        self.connections.lock().unwrap().insert(
+            endpoint.to_string(),
+            Connection {
+                session: session.clone(),
+                stop: Some(stop),
+            },
+        );
+        Ok(session)
+    }
+
+    /// Close the endpoint's session if the status code means it's no longer usable, so it's
+    /// reopened on the next request
+    fn check_connection(&self, endpoint: &str, code: StatusCode) {
+        if reading_status(code) == "COMM_ERROR" {
+            warn!("Lost OPC UA session with {endpoint}: {code}");
+            self.connections.lock().unwrap().remove(endpoint);
+        }
+    }
+
+    /// Read the current value of the target's node
+    pub fn read(&self, target: &OpcUaTarget) -> SensorReading {
+        let timestamp = now();
+        let node_id = match target.node_id() {
+            Ok(node_id) => node_id,
+            Err(e) => return target.reading("VALUE_ERROR", Some(e), timestamp),
+        };
+        let read_value_id = ReadValueId {
+            node_id,
+            attribute_id: AttributeId::Value as u32,
+            index_range: UAString::null(),
+            data_encoding: QualifiedName::null(),
+        };
+
+        let result = self.session(&target.endpoint).and_then(|session| {
+            session
+                .read()
+                .read(&[read_value_id], TimestampsToReturn::Both, 0.0)
+        });
+        match result {
+            Ok(values) => match values.first() {
+                Some(value) => reading(target, value, timestamp),
+                None => target.reading("VALUE_ERROR", None, timestamp),
+            },
+            Err(code) => {
+                error!(
+                    "Error reading {} from {}: {code}",
+                    target.node_id, target.endpoint
+                );
+                self.check_connection(&target.endpoint, code);
+                reading(target, &bad_value(code), timestamp)
+            }
+        }
+    }
+
+    /// Monitor the target's node, sending a reading every time the server publishes a change.
+    /// Returns the ID of the subscription so it can be deleted.
+    pub fn subscribe(
+        &self,
+        target: &OpcUaTarget,
+        publishing_interval: Duration,
+        readings: UnboundedSender<SensorReading>,
+    ) -> Result<u32, StatusCode> {
+        let node_id = target.node_id().map_err(|_| StatusCode::BadNodeIdInvalid)?;
+        let session = self.session(&target.endpoint)?;
+        let session = session.read();
+
+        let callback_target = target.clone();
+        let subscription_id = session.create_subscription(
+            publishing_interval.as_millis() as f64,
+            10,
+            30,
+            0,
+            0,
+            true,
+            DataChangeCallback::new(move |changed_items| {
+                let timestamp = now();
+                for item in changed_items {
+                    let reading = reading(&callback_target, item.last_value(), timestamp);
+                    if readings.send(reading).is_err() {
+                        debug!("Target {} was removed, dropping change", callback_target.id);
+                    }
+                }
+            }),
+        )?;
+
+        let items: Vec<MonitoredItemCreateRequest> = vec![node_id.into()];
+        let results =
+            session.create_monitored_items(subscription_id, TimestampsToReturn::Both, &items)?;
+        match results.first().map(|result| result.status_code) {
+            Some(code) if code.is_good() => Ok(subscription_id),
+            code => {
+                let _ = session.delete_subscription(subscription_id);
+                Err(code.unwrap_or(StatusCode::BadUnexpectedError))
+            }
+        }
+    }
+
+    pub fn unsubscribe(&self, endpoint: &str, subscription_id: u32) {
+        let session = match self.connections.lock().unwrap().get(endpoint) {
+            Some(connection) => connection.session.clone(),
+            // The session has already been closed, taking its subscriptions with it
+            None => return,
+        };
+        let result = session.read().delete_subscription(subscription_id);
+        if let Err(code) = result {
+            warn!("Error deleting subscription {subscription_id} on {endpoint}: {code}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::TargetMode;
+    use crate::test_server::TestServer;
+    use uuid::Uuid;
+
+    fn target(endpoint: String, node_id: String, mode: TargetMode) -> OpcUaTarget {
+        OpcUaTarget {
+            id: Uuid::new_v4(),
+            alias: "line1_temp".to_string(),
+            location: "plant_1".to_string(),
+            endpoint,
+            node_id,
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_reading_status() {
+        assert_eq!(reading_status(StatusCode::Good), "SUCCESS");
+        assert_eq!(
+            reading_status(StatusCode::UncertainLastUsableValue),
+            "UNCERTAIN"
+        );
+        assert_eq!(reading_status(StatusCode::BadTimeout), "COMM_ERROR");
+        assert_eq!(reading_status(StatusCode::BadNodeIdUnknown), "VALUE_ERROR");
+    }
+
+    #[test]
+    fn test_bad_value_reading() {
+        let target = target(
+            "opc.tcp://127.0.0.1:4840".to_string(),
+            "ns=2;s=Temperature".to_string(),
+            TargetMode::Read,
+        );
+        let reading = reading(&target, &bad_value(StatusCode::BadNodeIdUnknown), 0);
+        assert_eq!(reading.status, "VALUE_ERROR");
+        assert_eq!(reading.value.as_deref(), Some("BadNodeIdUnknown"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_read_and_subscribe() {
+        let server = TestServer::start(21.5).await;
+        let client = Arc::new(OpcUaClient::new("./pki-test").unwrap());
+
+        let temperature = target(
+            server.endpoint(),
+            server.temperature_node(),
+            TargetMode::Read,
+        );
+        let read_client = client.clone();
+        let reading = tokio::task::spawn_blocking(move || read_client.read(&temperature))
+            .await
+            .unwrap();
+        assert_eq!(reading.status, "SUCCESS");
+        assert_eq!(reading.value.as_deref(), Some("21.5"));
+
+        let missing = target(
+            server.endpoint(),
+            server.temperature_node().replace("Temperature", "Missing"),
+            TargetMode::Read,
+        );
+        let read_client = client.clone();
+        let reading = tokio::task::spawn_blocking(move || read_client.read(&missing))
+            .await
+            .unwrap();
+        assert_eq!(reading.status, "VALUE_ERROR");
+
+        let subscribed = target(
+            server.endpoint(),
+            server.temperature_node(),
+            TargetMode::Subscribe,
+        );
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let subscribe_client = client.clone();
+        tokio::task::spawn_blocking(move || {
+            subscribe_client.subscribe(&subscribed, Duration::from_millis(100), tx)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        // The initial value is published as soon as the monitored item is created
+        let reading = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reading.value.as_deref(), Some("21.5"));
+
+        server.set_temperature(23.0);
+        let reading = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reading.status, "SUCCESS");
+        assert_eq!(reading.value.as_deref(), Some("23"));
+
+        // Sessions have their own runtimes, which can't be dropped from async code
+        tokio::task::spawn_blocking(move || drop(client))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use wasmcloud_interface_polling::config::PollingSettings;
+use wasmcloud_interface_polling::targets::ProviderConfig;
+
+const DEFAULT_PKI_DIR: &str = "pki";
+
+/// Configuration for polling OPC UA servers, from the provider's config or a link's values
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OpcUaConfig {
+    /// For subscribed targets the poll interval is the publishing interval
+    #[serde(flatten)]
+    pub polling: PollingSettings,
+    /// Directory the client's certificates are kept in, and created in if there aren't any
+    #[serde(default)]
+    pub pki_dir: Option<String>,
+}
+
+impl OpcUaConfig {
+    pub fn pki_dir(&self) -> &str {
+        self.pki_dir.as_deref().unwrap_or(DEFAULT_PKI_DIR)
+    }
+}
+
+impl ProviderConfig for OpcUaConfig {
+    fn polling(&self) -> &PollingSettings {
+        &self.polling
+    }
+
+    fn merge(&self, extra: &OpcUaConfig) -> OpcUaConfig {
+        let mut out = self.clone();
+        out.polling = self.polling.merge(&extra.polling);
+        if extra.pki_dir.is_some() {
+            out.pki_dir = extra.pki_dir.clone();
+        }
+        out
+    }
+
+    fn unchangeable_setting(&self, new: &OpcUaConfig) -> Option<&'static str> {
+        (self.pki_dir() != new.pki_dir()).then_some("PKI directory")
+    }
+}
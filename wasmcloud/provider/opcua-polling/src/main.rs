@@ -0,0 +1,321 @@
+//! Implementation for wasmcloud:polling which reads nodes from OPC UA servers. Targets are added
+//! and removed by the actor with `AddPollTarget` and `RemovePollTarget`, and are either read every
+//! poll interval or monitored by the server, which publishes any changes. The node's status code
+//! becomes the `status` of the readings sent to the actor.
+mod client;
+mod config;
+mod target;
+#[cfg(test)]
+mod test_server;
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tracing::{debug, error, instrument};
+use wasmbus_rpc::core::HostData;
+use wasmbus_rpc::{core::LinkDefinition, provider::prelude::*};
+use wasmcloud_interface_polling::targets::{self, Actors, LinkedActor, PolledTarget, SharedConfig};
+use wasmcloud_interface_polling::{
+    AddPollTargetRequest, AddPollTargetResponse, PollRequest, PollResult, Polling, PollingReceiver,
+    RemovePollTargetRequest, RemovePollTargetResponse, SensorReading,
+};
+
+use crate::client::OpcUaClient;
+use crate::config::OpcUaConfig;
+use crate::target::{OpcUaTarget, TargetMode};
+
+/// An actor's link, its client keeps a session open to each server its targets are on
+type OpcUaActor = LinkedActor<OpcUaConfig, NodeTarget, Arc<OpcUaClient>>;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // handle lattice control messages and forward rpc to the provider dispatch
+    // returns when provider receives a shutdown control message
+    let host_data = load_host_data()?;
+    let provider = OpcUaPollingProvider::new(host_data)?;
+
+    provider_main(provider, Some("OPC-UA-Polling Provider".to_string()))?;
+
+    eprintln!("OPC-UA-Polling provider exiting");
+    Ok(())
+}
+
+/// A target which is being polled, and how
+struct NodeTarget {
+    target: OpcUaTarget,
+    /// ID of the subscription monitoring the target's node, if it's subscribed to
+    subscription: Option<u32>,
+}
+
+/// Implementation for wasmcloud:polling
+#[derive(Default, Clone, Provider)]
+#[services(Polling)]
+struct OpcUaPollingProvider {
+    actors: Actors<OpcUaConfig, NodeTarget, Arc<OpcUaClient>>,
+    default_config: OpcUaConfig,
+}
+
+// use default implementations of provider message handlers
+impl ProviderDispatch for OpcUaPollingProvider {}
+
+impl OpcUaPollingProvider {
+    fn new(host_data: HostData) -> Result<Self, anyhow::Error> {
+        match &host_data.config_json {
+            Some(config) if !config.trim().is_empty() => Ok(Self {
+                default_config: serde_json::from_str(config)?,
+                ..Default::default()
+            }),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Send every change published for a subscribed target to the actor, batching any changes
+    /// which arrive together
+    #[instrument(level = "info", skip_all, fields(actor_id = %ld.actor_id))]
+    async fn forward_changes(
+        ld: LinkDefinition,
+        mut changes: UnboundedReceiver<SensorReading>,
+        config: SharedConfig<OpcUaConfig>,
+    ) {
+        while let Some(reading) = changes.recv().await {
+            let mut readings = vec![reading];
+            while let Ok(reading) = changes.try_recv() {
+                readings.push(reading);
+            }
+            let encoding = config.read().await.polling.encoding();
+            targets::send_readings(readings, &ld, encoding).await
+        }
+    }
+
+    async fn read(client: &Arc<OpcUaClient>, target: &OpcUaTarget) -> Option<SensorReading> {
+        let (client, target) = (client.clone(), target.clone());
+        match tokio::task::spawn_blocking(move || client.read(&target)).await {
+            Ok(reading) => Some(reading),
+            Err(e) => {
+                error!("OPC UA read task failed: {e}");
+                None
+            }
+        }
+    }
+
+    /// Stop polling the target, deleting its subscription if it has one
+    fn stop_target(client: &Arc<OpcUaClient>, polled: PolledTarget<NodeTarget>) {
+        if let Some(subscription_id) = polled.target.subscription {
+            let client = client.clone();
+            let endpoint = polled.target.target.endpoint.clone();
+            tokio::task::spawn_blocking(move || client.unsubscribe(&endpoint, subscription_id));
+        }
+    }
+}
+
+/// OPC UA sessions have their own runtimes, which can't be dropped from async code
+fn drop_actor(actor: OpcUaActor) {
+    tokio::task::spawn_blocking(move || drop(actor));
+}
+
+/// Handle provider control commands
+/// put_link (new actors link command), del_link (remove link command), and shutdown
+#[async_trait]
+impl ProviderHandler for OpcUaPollingProvider {
+    /// Targets are added by the actor after the link is put, so this only needs to set up the
+    /// link's client. If the link is put again its config is updated for any running targets.
+    #[instrument(level = "info", skip(self, ld), fields(actor_id = %ld.actor_id))]
+    async fn put_link(&self, ld: &LinkDefinition) -> RpcResult<bool> {
+        debug!("putting link for actors {:?}", ld);
+        let config = match targets::link_config(&self.default_config, ld) {
+            Some(config) => config,
+            None => return Ok(false),
+        };
+        let config = match targets::relink(&self.actors, ld, config).await {
+            Some(config) => config,
+            None => return Ok(true),
+        };
+
+        let pki_dir = config.pki_dir().to_string();
+        let client = match tokio::task::spawn_blocking(move || OpcUaClient::new(&pki_dir)).await {
+            Ok(Ok(client)) => client,
+            Ok(Err(e)) => {
+                error!("Failed to create OPC UA client: {e}");
+                return Ok(false);
+            }
+            Err(e) => return Err(RpcError::ProviderInit(e.to_string())),
+        };
+
+        self.actors.write().await.insert(
+            ld.actor_id.to_string(),
+            LinkedActor::new(ld, config, Arc::new(client)),
+        );
+
+        Ok(true)
+    }
+
+    /// Handle notification that a link is dropped: stop polling the actor's targets and close
+    /// its sessions
+    #[instrument(level = "info", skip(self))]
+    async fn delete_link(&self, actor_id: &str) {
+        debug!("deleting link for actors {}", actor_id);
+        if let Some(actor) = targets::unlink(&self.actors, actor_id).await {
+            drop_actor(actor);
+        }
+    }
+
+    /// Handle shutdown request with any cleanup necessary
+    async fn shutdown(&self) -> Result<(), Infallible> {
+        for (_, actor) in self.actors.write().await.drain() {
+            drop_actor(actor);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Polling for OpcUaPollingProvider {
+    /// Read the targets with the IDs given as a JSON array in `request_data` straight away, or
+    /// every target if there isn't one. Subscribed targets are read as well.
+    async fn poll_tx(&self, ctx: &Context, arg: &PollRequest) -> RpcResult<PollResult> {
+        let actor_id = targets::actor_id(ctx)?;
+        let (client, targets, encoding) = {
+            let read_actors = self.actors.read().await;
+            let actor = read_actors
+                .get(actor_id)
+                .ok_or_else(|| targets::not_linked(actor_id))?;
+            let targets: Vec<OpcUaTarget> = match actor.requested(arg) {
+                Ok(nodes) => nodes.into_iter().map(|node| node.target.clone()).collect(),
+                Err(poll_result) => return Ok(poll_result),
+            };
+            let encoding = actor.config.read().await.polling.encoding();
+            (actor.state.clone(), targets, encoding)
+        };
+
+        let mut readings = Vec::with_capacity(targets.len());
+        for target in &targets {
+            if let Some(reading) = Self::read(&client, target).await {
+                readings.push(reading);
+            }
+        }
+        Ok(targets::poll_result(&readings, encoding))
+    }
+
+    /// Start polling the [`OpcUaTarget`] given as JSON in `target_data`, replacing any existing
+    /// target with the same ID
+    async fn add_poll_target(
+        &self,
+        ctx: &Context,
+        arg: &AddPollTargetRequest,
+    ) -> RpcResult<AddPollTargetResponse> {
+        let actor_id = targets::actor_id(ctx)?;
+        let target = match targets::parse_target(&arg.target_data, OpcUaTarget::validate) {
+            Ok(target) => target,
+            Err(e) => return Ok(AddPollTargetResponse { error: Some(e) }),
+        };
+
+        let (client, config, ld, poll_interval) = {
+            let read_actors = self.actors.read().await;
+            let actor = read_actors
+                .get(actor_id)
+                .ok_or_else(|| targets::not_linked(actor_id))?;
+            let poll_interval = match actor.poll_interval(arg.poll_interval).await {
+                Ok(poll_interval) => poll_interval,
+                Err(e) => return Ok(AddPollTargetResponse { error: Some(e) }),
+            };
+            (
+                actor.state.clone(),
+                actor.config.clone(),
+                actor.ld.clone(),
+                poll_interval,
+            )
+        };
+
+        // Subscribing connects to the server if there isn't a session already, which can take a
+        // while, so it's done before the actors are locked
+        let (handle, subscription) = match target.mode {
+            TargetMode::Read => {
+                let (read_client, read_target) = (client.clone(), target.clone());
+                let handle = tokio::spawn(targets::poll_every(
+                    ld,
+                    target.id,
+                    config,
+                    poll_interval,
+                    move |_| {
+                        let (client, target) = (read_client.clone(), read_target.clone());
+                        async move { Vec::from_iter(Self::read(&client, &target).await) }
+                    },
+                ));
+                (handle, None)
+            }
+            TargetMode::Subscribe => {
+                let (tx, rx) = unbounded_channel();
+                let subscribe_client = client.clone();
+                let subscribed = target.clone();
+                let subscription = tokio::task::spawn_blocking(move || {
+                    subscribe_client.subscribe(&subscribed, poll_interval, tx)
+                })
+                .await
+                .map_err(|e| RpcError::Other(e.to_string()))?;
+                let subscription_id = match subscription {
+                    Ok(subscription_id) => subscription_id,
+                    Err(code) => {
+                        return Ok(AddPollTargetResponse {
+                            error: Some(targets::polling_error(
+                                "SUBSCRIPTION_FAILED",
+                                code.name().to_string(),
+                            )),
+                        })
+                    }
+                };
+                let handle = tokio::spawn(Self::forward_changes(ld, rx, config));
+                (handle, Some(subscription_id))
+            }
+        };
+        let polled = PolledTarget::new(
+            NodeTarget {
+                target,
+                subscription,
+            },
+            handle,
+        );
+
+        let mut write_actors = self.actors.write().await;
+        // The link may have been deleted, or deleted and put again with a new client, meanwhile
+        let actor = match write_actors.get_mut(actor_id) {
+            Some(actor) if Arc::ptr_eq(&actor.state, &client) => actor,
+            _ => {
+                Self::stop_target(&client, polled);
+                tokio::task::spawn_blocking(move || drop(client));
+                return Err(targets::not_linked(actor_id));
+            }
+        };
+        let target = &polled.target.target;
+        if let Some(existing) = actor.targets.remove(&target.id) {
+            Self::stop_target(&actor.state, existing);
+        }
+        debug!(
+            "Polling OPC UA node {} on {} every {:?}",
+            target.node_id, target.endpoint, poll_interval
+        );
+        actor.targets.insert(target.id, polled);
+
+        Ok(AddPollTargetResponse { error: None })
+    }
+
+    /// Stop polling the target with the ID given as JSON in `target_data`
+    async fn remove_poll_target(
+        &self,
+        ctx: &Context,
+        arg: &RemovePollTargetRequest,
+    ) -> RpcResult<RemovePollTargetResponse> {
+        let actor_id = targets::actor_id(ctx)?;
+        let mut write_actors = self.actors.write().await;
+        let actor = write_actors
+            .get_mut(actor_id)
+            .ok_or_else(|| targets::not_linked(actor_id))?;
+        let error = match actor.remove(&arg.target_data) {
+            Ok(polled) => {
+                Self::stop_target(&actor.state, polled);
+                None
+            }
+            Err(e) => Some(e),
+        };
+
+        Ok(RemovePollTargetResponse { error })
+    }
+}
@@ -0,0 +1,93 @@
+use opcua::types::NodeId;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+use wasmcloud_interface_polling::reading::ReadingTarget;
+
+/// An OPC UA node to poll, sent as the `targetData` of an `AddPollTarget` request
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct OpcUaTarget {
+    pub id: Uuid,
+    pub alias: String,
+    pub location: String,
+    /// URL of the server's endpoint, e.g. `opc.tcp://10.0.0.5:4840`
+    pub endpoint: String,
+    /// Node ID in its string form, e.g. `ns=2;s=Line1.Temperature`
+    pub node_id: String,
+    #[serde(default)]
+    pub mode: TargetMode,
+}
+
+/// How values are obtained from the node
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetMode {
+    /// The provider reads the node every poll interval
+    #[default]
+    Read,
+    /// The server samples the node as a monitored item and publishes any changes every poll
+    /// interval, so nothing is sent while the value doesn't change
+    Subscribe,
+}
+
+impl OpcUaTarget {
+    pub fn node_id(&self) -> Result<NodeId, String> {
+        NodeId::from_str(&self.node_id).map_err(|_| format!("invalid node ID: {}", self.node_id))
+    }
+
+    /// Check the target can be polled before it's added
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.endpoint.starts_with("opc.tcp://") {
+            return Err(format!("unsupported endpoint URL: {}", self.endpoint));
+        }
+        self.node_id().map(|_| ())
+    }
+}
+
+impl ReadingTarget for OpcUaTarget {
+    fn target_id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn location(&self) -> &str {
+        &self.location
+    }
+
+    fn alias(&self) -> &str {
+        &self.alias
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_deserialize() {
+        let target_json = r#"
+            {
+                "id": "8d3e7c1a-4b2f-4e6d-9a8c-7b6a5d4c3b2a",
+                "alias": "line1_temp",
+                "location": "plant_1",
+                "endpoint": "opc.tcp://10.0.0.5:4840",
+                "node_id": "ns=2;s=Line1.Temperature",
+                "mode": "subscribe"
+            }
+            "#;
+        let target: OpcUaTarget = serde_json::from_str(target_json).unwrap();
+        assert_eq!(target.mode, TargetMode::Subscribe);
+        assert_eq!(target.validate(), Ok(()));
+        assert_eq!(
+            target.node_id().unwrap(),
+            NodeId::new(2, "Line1.Temperature")
+        );
+
+        let bad_node = target_json.replace("ns=2;s=Line1.Temperature", "Line1.Temperature");
+        let target: OpcUaTarget = serde_json::from_str(&bad_node).unwrap();
+        assert!(target.validate().is_err());
+
+        let read_mode = target_json.replace(r#""mode": "subscribe""#, r#""mode": "read""#);
+        let target: OpcUaTarget = serde_json::from_str(&read_mode).unwrap();
+        assert_eq!(target.mode, TargetMode::Read);
+    }
+}
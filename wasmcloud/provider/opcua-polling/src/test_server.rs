@@ -0,0 +1,93 @@
+//! Local OPC UA server with a single temperature variable, standing in for real machinery in
+//! tests. It's the server from the same open source `opcua` crate as the client.
+
+use opcua::server::prelude::*;
+use opcua::sync::RwLock;
+use std::sync::Arc;
+use std::time::Duration;
+
+const NAMESPACE: &str = "urn:jclmnop:opcua-polling-test";
+
+pub struct TestServer {
+    port: u16,
+    namespace: u16,
+    address_space: Arc<RwLock<AddressSpace>>,
+}
+
+impl TestServer {
+    /// Start serving on a free local port, for as long as the test runs
+    pub async fn start(temperature: f64) -> Self {
+        // Find a free port, there's a small chance of it being taken before the server binds it
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let server = ServerBuilder::new_anonymous("OPC UA Polling Test Server")
+            .application_uri(NAMESPACE)
+            .host_and_port("127.0.0.1", port)
+            .discovery_urls(vec![format!("opc.tcp://127.0.0.1:{port}/")])
+            .create_sample_keypair(true)
+            .pki_dir("./pki-test-server")
+            .server()
+            .unwrap();
+
+        let address_space = server.address_space();
+        let namespace = {
+            let mut address_space = address_space.write();
+            let namespace = address_space.register_namespace(NAMESPACE).unwrap();
+            let folder_id = address_space
+                .add_folder("Sensors", "Sensors", &NodeId::objects_folder_id())
+                .unwrap();
+            let node_id = NodeId::new(namespace, "Temperature");
+            let _ = address_space.add_variables(
+                vec![Variable::new(
+                    &node_id,
+                    "Temperature",
+                    "Temperature",
+                    temperature,
+                )],
+                &folder_id,
+            );
+            namespace
+        };
+
+        // The server runs its own runtime, so it needs its own thread
+        std::thread::spawn(move || server.run());
+
+        for _ in 0..50 {
+            if tokio::net::TcpStream::connect(("127.0.0.1", port))
+                .await
+                .is_ok()
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        Self {
+            port,
+            namespace,
+            address_space,
+        }
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("opc.tcp://127.0.0.1:{}/", self.port)
+    }
+
+    pub fn temperature_node(&self) -> String {
+        format!("ns={};s=Temperature", self.namespace)
+    }
+
+    pub fn set_temperature(&self, temperature: f64) {
+        let now = DateTime::now();
+        self.address_space.write().set_variable_value(
+            NodeId::new(self.namespace, "Temperature"),
+            temperature,
+            &now,
+            &now,
+        );
+    }
+}